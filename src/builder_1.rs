@@ -1,11 +1,15 @@
 #![allow(dead_code)]
 
-use rand::{thread_rng, Rng};
+use rand::Rng;
+use crate::rng;
 use itertools::Itertools;
 
 use std::collections::HashSet;
 use array2d::Array2D;
 use std::fmt::{Display, Formatter, Error};
+use std::time::Instant;
+use crate::engine::{GridBuilder, BuildConfig, BuildStats};
+use crate::grid_constraint_solve::SudokuError;
 
 const NO_VALUE: u8 = 0;
 const RUN_INVARIANT: bool = false;
@@ -146,7 +150,8 @@ impl Grid {
         let mut options = vec![];
         for (cell_index, cell) in grid_to_now.cells.iter().enumerate() {
             if cell.value == NO_VALUE {
-                for value in cell.rem_values.iter() {
+                // Sorted so that the same seed gives the same grid, since HashSet order varies.
+                for value in cell.rem_values.iter().sorted() {
                     options.push((cell_index as u8, value));
                 }
             }
@@ -161,7 +166,7 @@ impl Grid {
             // while attempt_count <= max_attempts {
             //rintln!("Top of loop: unsolved_cell_count = {}, options.len() == {}", grid_to_now.unsolved_cell_count, options.len());
             //rintln!("Top of loop: filled_cell_count = {}, options.len() == {}, attempt_count = {}", filled_cell_count, options.len(), attempt_count);
-            let option = options.remove(thread_rng().gen_range(0, options.len()));
+            let option = options.remove(rng().gen_range(0, options.len()));
             let mut grid = grid_to_now.clone();
             //rintln!("\tOption is {} with value {}", &grid_to_now.cell(option.0), option.1);
             let remaining_options = grid.set_value(option.0, *option.1);
@@ -406,3 +411,29 @@ fn value_to_char(value: u8) -> String {
         _ => value.to_string()
     }
 }
+
+// The builder_1 engine for engine::GridBuilder. It has no limits of any kind and keeps no counts.
+pub struct Engine;
+
+impl GridBuilder for Engine {
+    fn name(&self) -> &'static str {
+        "builder_1"
+    }
+
+    fn max_cell_count(&self) -> usize {
+        // Cell indexes are u8.
+        256
+    }
+
+    fn honors_time_limit(&self) -> bool {
+        false
+    }
+
+    fn build_values(&self, config: &BuildConfig, stats: &mut BuildStats) -> Result<Vec<u8>, SudokuError> {
+        let start_time = Instant::now();
+        match Grid::build(config.width, config.height, config.block_width, config.block_height) {
+            Some(grid) => Ok(grid.cells.iter().map(|cell| cell.value).collect()),
+            None => Err(stats.failure(start_time, false)),
+        }
+    }
+}
@@ -3,13 +3,15 @@
 // Differences from builder_obj:
 // - Added logging and the controller object.
 
-use rand::{thread_rng, Rng};
+use rand::Rng;
 use itertools::Itertools;
 use std::collections::HashSet;
 use array2d::Array2D;
 use std::time::{Instant, Duration};
 
 use crate::*;
+use crate::engine::{GridBuilder, BuildConfig, BuildStats};
+use crate::grid_constraint_solve::SudokuError;
 
 const NO_VALUE: u8 = 0;
 const RUN_INVARIANT: bool = false;
@@ -187,7 +189,8 @@ impl Grid {
         let mut options = vec![];
         for (cell_index, cell) in grid_to_now.cells.iter().enumerate() {
             if cell.value == NO_VALUE {
-                for value in cell.rem_values.iter() {
+                // Sorted so that the same seed gives the same grid, since HashSet order varies.
+                for value in cell.rem_values.iter().sorted() {
                     options.push((cell_index as u8, value));
                 }
             }
@@ -202,7 +205,7 @@ impl Grid {
             // while attempt_count <= max_attempts {
             //rintln!("Top of loop: unsolved_cell_count = {}, options.len() == {}", grid_to_now.unsolved_cell_count, options.len());
             //rintln!("Top of loop: filled_cell_count = {}, options.len() == {}, attempt_count = {}", filled_cell_count, options.len(), attempt_count);
-            let option = options.remove(thread_rng().gen_range(0, options.len()));
+            let option = options.remove(rng().gen_range(0, options.len()));
             let mut grid = grid_to_now.clone();
             controller.continue_branch = true;
             //rintln!("\tOption is {} with value {}", &grid_to_now.cell(option.0), option.1);
@@ -480,3 +483,33 @@ fn value_to_char(value: u8) -> String {
         _ => value.to_string()
     }
 }
+
+// The builder_obj_log engine for engine::GridBuilder. The BuildController gives it a time limit,
+// a tried grid count, and a count of calls to build_next_cell() that serves as the node count.
+pub struct Engine;
+
+impl GridBuilder for Engine {
+    fn name(&self) -> &'static str {
+        "builder_obj_log"
+    }
+
+    fn max_cell_count(&self) -> usize {
+        // Related cell indexes are u8.
+        256
+    }
+
+    fn honors_time_limit(&self) -> bool {
+        true
+    }
+
+    fn build_values(&self, config: &BuildConfig, stats: &mut BuildStats) -> Result<Vec<u8>, SudokuError> {
+        let start_time = Instant::now();
+        let (grid, controller) = Grid::build(config.width, config.height, config.block_width, config.block_height, config.max_tried_grid_count, config.time_limit);
+        stats.node_count = controller.event_counts.get("build_next_cell()").map(|count| *count as usize);
+        stats.tried_grid_count = Some(controller.tried_grids.len());
+        match grid {
+            Some(grid) => Ok(grid.cells.iter().map(|cell| cell.value).collect()),
+            None => Err(stats.failure(start_time, !controller.continue_build)),
+        }
+    }
+}
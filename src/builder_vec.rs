@@ -4,7 +4,7 @@
 // - Eliminated cell objects, putting as much as possible in fixed-length vectors in Grid.
 // - In other ways reduced creating objects.
 
-use rand::{thread_rng, Rng};
+use rand::Rng;
 use itertools::Itertools;
 use bit_vec::BitVec;
 use std::sync::Mutex;
@@ -17,6 +17,8 @@ use std::ops::Range;
 use std::time::Instant;
 
 use crate::*;
+use crate::engine::{GridBuilder, BuildConfig, BuildStats};
+use crate::grid_constraint_solve::SudokuError;
 
 const NO_VALUE: u8 = 0;
 const RUN_INVARIANT: bool = false;
@@ -258,7 +260,7 @@ impl Grid {
             .filter(|(_, value)| **value == NO_VALUE)
             .map(|x| x.0)
             .collect::<Vec<_>>();
-        let try_cell_index = try_cell_indexes[thread_rng().gen_range(0, try_cell_indexes.len())] as u8;
+        let try_cell_index = try_cell_indexes[rng().gen_range(0, try_cell_indexes.len())] as u8;
         */
 
         let try_cell_index = grid_to_now.choose_try_cell_index();
@@ -270,7 +272,7 @@ impl Grid {
 
         let mut try_values = grid_to_now.remaining_values(try_cell_index);
         while !try_values.is_empty() {
-            // let try_value = try_values.remove(thread_rng().gen_range(0, try_values.len()));
+            // let try_value = try_values.remove(rng().gen_range(0, try_values.len()));
             let try_value = try_values.remove(0);
 
             // Register the grid we're about to try, and if this call returns false it means
@@ -335,10 +337,10 @@ impl Grid {
             // while attempt_count <= max_attempts {
             //rintln!("Top of loop: unsolved_cell_count = {}, options.len() == {}", grid_to_now.unsolved_cell_count, options.len());
             //rintln!("Top of loop: filled_cell_count = {}, options.len() == {}, attempt_count = {}", filled_cell_count, options.len(), attempt_count);
-            let try_cell_index = remaining_cell_indexes.remove(thread_rng().gen_range(0, remaining_cell_indexes.len()));
+            let try_cell_index = remaining_cell_indexes.remove(rng().gen_range(0, remaining_cell_indexes.len()));
             let mut try_values = grid_to_now.remaining_values(try_cell_index);
             while !try_values.is_empty() {
-                let try_value = try_values.remove(thread_rng().gen_range(0, try_values.len()));
+                let try_value = try_values.remove(rng().gen_range(0, try_values.len()));
 
                 // Register the grid we're about to try, and if this call returns false it means
                 // we've already tried this grid.
@@ -564,7 +566,7 @@ impl Grid {
             let set = self.remaining_value_counts_map.get(i as usize).unwrap();
             if set.len() > 0 {
                 let v = set.iter().collect::<Vec<_>>();
-                let random_index = thread_rng().gen_range(0, v.len());
+                let random_index = rng().gen_range(0, v.len());
                 //bg!(&v, random_index);
                 // println!("choose_try_cell_index(): min = {}, found_count = {}, random_index = {}", min, found_count, random_index);
                 return *v[random_index];
@@ -579,7 +581,7 @@ impl Grid {
     fn choose_try_cell_index(&self) -> u8 {
         let min = *self.remaining_value_counts.iter().filter(|count| **count > 0).min().unwrap();
         let found_indexes = self.remaining_value_counts.iter().filter(|count| **count == min).collect::<Vec<_>>();
-        let random_index = thread_rng().gen_range(0, found_indexes.len());
+        let random_index = rng().gen_range(0, found_indexes.len());
         *found_indexes[random_index]
     }
     */
@@ -588,7 +590,7 @@ impl Grid {
     fn choose_try_cell_index(&self) -> u8 {
         let min = *self.remaining_value_counts.iter().filter(|count| **count > 0).min().unwrap();
         let found_count = self.remaining_value_counts.iter().filter(|count| **count == min).count();
-        let random_index = thread_rng().gen_range(0, found_count);
+        let random_index = rng().gen_range(0, found_count);
         //rintln!("choose_try_cell_index(): min = {}, found_count = {}, random_index = {}", min, found_count, random_index);
 
         let mut i = 0;
@@ -607,7 +609,7 @@ impl Grid {
 
     /*
     fn choose_try_cell_index(&self) -> u8 {
-        let random_index = thread_rng().gen_range(0, self.cells_with_min_cell_remaining_value_count);
+        let random_index = rng().gen_range(0, self.cells_with_min_cell_remaining_value_count);
 
         let found_count = self.remaining_value_counts.iter().filter(|count| **count == self.min_cell_remaining_value_count).count();
         println!("choose_try_cell_index(): min_cell_remaining_value_count = {}, cells_with_min_cell_remaining_value_count = {}, random_index = {}, found_count = {}", self.min_cell_remaining_value_count, self.cells_with_min_cell_remaining_value_count, random_index, found_count);
@@ -927,3 +929,32 @@ fn value_to_char(value: u8) -> String {
         _ => value.to_string()
     }
 }
+
+// The builder_vec engine for engine::GridBuilder. It has no time limit. The tried grids are kept in
+// the global TRIED_GRIDS, so only one build should run at a time.
+pub struct Engine;
+
+impl GridBuilder for Engine {
+    fn name(&self) -> &'static str {
+        "builder_vec"
+    }
+
+    fn max_cell_count(&self) -> usize {
+        // Related cell indexes are u8.
+        256
+    }
+
+    fn honors_time_limit(&self) -> bool {
+        false
+    }
+
+    fn build_values(&self, config: &BuildConfig, stats: &mut BuildStats) -> Result<Vec<u8>, SudokuError> {
+        let start_time = Instant::now();
+        let grid = Grid::build(config.width, config.height, config.block_width, config.block_height, config.max_tried_grid_count);
+        stats.tried_grid_count = Some(TRIED_GRIDS.lock().unwrap().len());
+        match grid {
+            Some(grid) => Ok(grid.values),
+            None => Err(stats.failure(start_time, false)),
+        }
+    }
+}
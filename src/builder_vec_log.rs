@@ -3,7 +3,7 @@
 // Differences from builder_vec:
 // - Added logging and the controller object.
 
-use rand::{thread_rng, Rng};
+use rand::Rng;
 use itertools::Itertools;
 use bit_vec::BitVec;
 use array2d::Array2D;
@@ -14,6 +14,8 @@ use std::time::Duration;
 //use std::fmt::{Display, Formatter, Error};
 
 use crate::*;
+use crate::engine::{GridBuilder, BuildConfig, BuildStats};
+use crate::grid_constraint_solve::SudokuError;
 
 const NO_VALUE: u8 = 0;
 const RUN_INVARIANT: bool = false;
@@ -228,7 +230,7 @@ impl Grid {
         let mut try_values = grid_to_now.remaining_values(try_cell_index);
         //bg!(&try_values);
         while !try_values.is_empty() {
-            let try_value = try_values.remove(thread_rng().gen_range(0, try_values.len()));
+            let try_value = try_values.remove(rng().gen_range(0, try_values.len()));
 
             // Register the grid we're about to try, and if this call returns false it means
             // we've already tried this grid.
@@ -307,14 +309,14 @@ impl Grid {
             //rintln!("Top of loop: filled_cell_count = {}, options.len() == {}, attempt_count = {}", filled_cell_count, options.len(), attempt_count);
             println!("build_next_cell(): top of loop: filled_cell_count() = {}, remaining_cell_indexes.len() = {}", grid_to_now.filled_cell_count(), remaining_cell_indexes.len());
 
-            let try_cell_index = remaining_cell_indexes.remove(thread_rng().gen_range(0, remaining_cell_indexes.len()));
+            let try_cell_index = remaining_cell_indexes.remove(rng().gen_range(0, remaining_cell_indexes.len()));
 
             assert_eq!(NO_VALUE, grid_to_now.values[try_cell_index as usize]);
 
             let mut try_values = grid_to_now.remaining_values(try_cell_index);
             //bg!(&try_values);
             while !try_values.is_empty() {
-                let try_value = try_values.remove(thread_rng().gen_range(0, try_values.len()));
+                let try_value = try_values.remove(rng().gen_range(0, try_values.len()));
 
                 // Register the grid we're about to try, and if this call returns false it means
                 // we've already tried this grid.
@@ -834,3 +836,33 @@ fn value_to_char(value: u8) -> String {
         _ => value.to_string()
     }
 }
+
+// The builder_vec_log engine for engine::GridBuilder. As in builder_obj_log, the BuildController
+// gives it a time limit, a tried grid count, and a count of calls to build_next_cell().
+pub struct Engine;
+
+impl GridBuilder for Engine {
+    fn name(&self) -> &'static str {
+        "builder_vec_log"
+    }
+
+    fn max_cell_count(&self) -> usize {
+        // Related cell indexes are u8.
+        256
+    }
+
+    fn honors_time_limit(&self) -> bool {
+        true
+    }
+
+    fn build_values(&self, config: &BuildConfig, stats: &mut BuildStats) -> Result<Vec<u8>, SudokuError> {
+        let start_time = Instant::now();
+        let (grid, controller) = Grid::build(config.width, config.height, config.block_width, config.block_height, config.max_tried_grid_count, config.time_limit);
+        stats.node_count = controller.event_counts.get("build_next_cell()").map(|count| *count as usize);
+        stats.tried_grid_count = Some(controller.tried_grids.len());
+        match grid {
+            Some(grid) => Ok(grid.values),
+            None => Err(stats.failure(start_time, !controller.continue_build)),
+        }
+    }
+}
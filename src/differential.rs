@@ -0,0 +1,543 @@
+#![allow(dead_code)]
+
+// Differential testing across the engines. Each case comes from a seed, which picks a block shape,
+// some extra rules, and how many cells to remove for a puzzle. For each case every engine that can
+// handle it builds a grid from the same seed, and each grid is checked against the rules by
+// validate_values(), which works out the related cells from the rules directly rather than trusting
+// any engine's related cell lists. Then a puzzle made from one of the grids goes to every solution
+// counter, and the counts have to agree.
+// A failure is shrunk before it's reported: first to the lowest seed that shows the same kind of
+// failure, then to the simplest case with that seed, so the report says how to reproduce it with
+// as small a grid as possible. check_case() runs one case on its own.
+// Builds and counts that hit a time or node limit are inconclusive rather than failures.
+
+use std::fmt::{self, Display, Formatter};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use itertools::Itertools;
+
+use crate::*;
+use crate::engine::{all_engines, BuildConfig};
+use crate::grid_constraint_solve::SudokuError;
+use crate::grid_constraint_solve::builder::{Builder, BuildStrategy, Cell, Rule, EXTRA_RULES, RULE_ROW, RULE_COLUMN, RULE_BLOCK};
+use crate::grid_constraint_solve::grid::Grid;
+use crate::grid_constraint_solve::solver::{Solver, TriedGrids, UniqueSolutionType};
+use crate::grid_constraint_solve::unique::UniquenessChecker;
+
+// Block shapes to choose from, roughly in order of grid size. The grid is always square with
+// sides of block_width * block_height.
+const BLOCK_SHAPES: [(u8, u8); 11] = [(1, 1), (2, 1), (1, 2), (2, 2), (3, 1), (1, 3), (3, 2), (2, 3), (4, 2), (2, 4), (3, 3)];
+// Each of builder::EXTRA_RULES has a one in EXTRA_RULE_ODDS chance of being in a case.
+const EXTRA_RULE_ODDS: u32 = 4;
+const BUILD_TIME_LIMIT_MILLISECONDS: u64 = 2_000;
+const BUILD_NODE_LIMIT: usize = 1_000_000;
+// Engines that don't stop at the time limit only get grids up to 6x6.
+const UNLIMITED_ENGINE_MAX_CELL_COUNT: usize = 36;
+const COUNT_TIME_LIMIT_MILLISECONDS: u64 = 2_000;
+const ORACLE_NODE_LIMIT: usize = 2_000_000;
+// Solution counts are capped at this so that counters that stop early can be compared with ones
+// that count everything.
+const SOLUTION_COUNT_LIMIT: usize = 20;
+// When shrinking, look at no more than this many lower seeds.
+const SHRINK_SEED_LIMIT: u64 = 200;
+
+pub fn main() {
+    try_differential();
+}
+
+fn try_differential() {
+    let report = DifferentialHarness::new().case_count(50).run();
+    println!("try_differential(): {}", report);
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DifferentialCase {
+    pub seed: u64,
+    pub block_width: u8,
+    pub block_height: u8,
+    pub rule_names: Vec<&'static str>,
+    pub remove_cell_count: u16,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Discrepancy {
+    // A grid or solution that breaks the rules or doesn't have one value for each cell.
+    InvalidGrid { engine: String, reason: String },
+    // An engine gave up without hitting a limit when there's a grid to be found.
+    BuildFailed { engine: String, reason: String },
+    // One engine found a grid with these rules and another searched everything and found none.
+    Diverged { found_by: String, unsatisfiable_by: String },
+    Panicked { engine: String, message: String },
+    // Each counter's result, capped at SOLUTION_COUNT_LIMIT, or at 2 for the counters that only
+    // tell zero, one, or many.
+    CountMismatch { counts: Vec<(String, usize)> },
+}
+
+#[derive(Clone, Debug)]
+pub struct DifferentialFailure {
+    pub case: DifferentialCase,
+    pub discrepancy: Discrepancy,
+    pub shrunk_case: DifferentialCase,
+    pub shrunk_discrepancy: Discrepancy,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CaseResult {
+    pub discrepancies: Vec<Discrepancy>,
+    pub run_count: usize,
+    pub inconclusive_count: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct DifferentialReport {
+    pub case_count: usize,
+    pub run_count: usize,
+    pub inconclusive_count: usize,
+    // The first failure of each kind, shrunk. Later failures of the same kind are only counted.
+    pub failures: Vec<DifferentialFailure>,
+    pub failure_count: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct DifferentialHarness {
+    pub first_seed: u64,
+    pub case_count: usize,
+    pub shrink: bool,
+}
+
+impl DifferentialHarness {
+
+    pub fn new() -> Self {
+        Self {
+            first_seed: 0,
+            case_count: 100,
+            shrink: true,
+        }
+    }
+
+    pub fn first_seed(mut self, first_seed: u64) -> Self {
+        self.first_seed = first_seed;
+        self
+    }
+
+    pub fn case_count(mut self, case_count: usize) -> Self {
+        self.case_count = case_count;
+        self
+    }
+
+    pub fn shrink(mut self, shrink: bool) -> Self {
+        self.shrink = shrink;
+        self
+    }
+
+    pub fn run(&self) -> DifferentialReport {
+        // Panics are reported as discrepancies, so keep the hook from printing them.
+        let _quiet_panics = quiet_panics();
+        let mut report = DifferentialReport::default();
+        for seed in self.first_seed..self.first_seed + self.case_count as u64 {
+            let case = DifferentialCase::from_seed(seed);
+            let result = check_case(&case);
+            report.case_count += 1;
+            report.run_count += result.run_count;
+            report.inconclusive_count += result.inconclusive_count;
+            for discrepancy in result.discrepancies {
+                report.failure_count += 1;
+                if report.failures.iter().any(|failure| failure.discrepancy.signature() == discrepancy.signature()) {
+                    continue;
+                }
+                let (shrunk_case, shrunk_discrepancy) = if self.shrink {
+                    shrink(&case, &discrepancy)
+                } else {
+                    (case.clone(), discrepancy.clone())
+                };
+                report.failures.push(DifferentialFailure { case: case.clone(), discrepancy, shrunk_case, shrunk_discrepancy });
+            }
+        }
+        report
+    }
+
+}
+
+impl Default for DifferentialHarness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DifferentialCase {
+
+    pub fn from_seed(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let (block_width, block_height) = BLOCK_SHAPES[rng.gen_range(0, BLOCK_SHAPES.len())];
+        let rule_names = EXTRA_RULES
+            .iter()
+            .filter(|_| rng.gen_range(0, EXTRA_RULE_ODDS) == 0)
+            .map(|(name, _)| *name)
+            .collect();
+        let size = block_width as u16 * block_height as u16;
+        let remove_cell_count = rng.gen_range(0, (size * size) / 2 + 1);
+        Self { seed, block_width, block_height, rule_names, remove_cell_count }
+    }
+
+    pub fn size(&self) -> u8 {
+        self.block_width * self.block_height
+    }
+
+    pub fn cell_count(&self) -> usize {
+        self.size() as usize * self.size() as usize
+    }
+
+    fn rules(&self) -> Vec<Rule> {
+        let mut rules = vec![RULE_ROW, RULE_COLUMN, RULE_BLOCK];
+        rules.extend(EXTRA_RULES
+            .iter()
+            .filter(|(name, _)| self.rule_names.contains(name))
+            .map(|(_, rule)| *rule));
+        rules
+    }
+
+    fn simpler_cases(&self) -> Vec<Self> {
+        // Each of these is simpler than this case in one way, so shrinking always finishes.
+        let mut cases = vec![];
+        for rule_name in self.rule_names.iter() {
+            let mut case = self.clone();
+            case.rule_names.retain(|name| name != rule_name);
+            cases.push(case);
+        }
+        for (block_width, block_height) in BLOCK_SHAPES.iter() {
+            if (*block_width as usize * *block_height as usize) < self.size() as usize {
+                let mut case = self.clone();
+                case.block_width = *block_width;
+                case.block_height = *block_height;
+                case.remove_cell_count = case.remove_cell_count.min((case.cell_count() / 2) as u16);
+                cases.push(case);
+            }
+        }
+        if self.remove_cell_count > 0 {
+            for remove_cell_count in [0, self.remove_cell_count / 2, self.remove_cell_count - 1].iter().unique() {
+                let mut case = self.clone();
+                case.remove_cell_count = *remove_cell_count;
+                cases.push(case);
+            }
+        }
+        cases
+    }
+
+}
+
+impl Discrepancy {
+    pub fn signature(&self) -> String {
+        // Two discrepancies with the same signature are taken to be the same bug when shrinking.
+        match self {
+            Discrepancy::InvalidGrid { engine, .. } => format!("InvalidGrid {}", engine),
+            Discrepancy::BuildFailed { engine, .. } => format!("BuildFailed {}", engine),
+            Discrepancy::Diverged { found_by, unsatisfiable_by } => format!("Diverged {} {}", found_by, unsatisfiable_by),
+            Discrepancy::Panicked { engine, .. } => format!("Panicked {}", engine),
+            Discrepancy::CountMismatch { .. } => "CountMismatch".to_string(),
+        }
+    }
+}
+
+pub fn check_case(case: &DifferentialCase) -> CaseResult {
+    let mut result = CaseResult::default();
+    let related_cell_lists = related_cell_lists(case);
+    if case.rule_names.is_empty() {
+        check_engines(case, &related_cell_lists, &mut result);
+    }
+    let grid = check_strategies(case, &related_cell_lists, &mut result);
+    if let Some(grid) = grid {
+        check_counters(case, &related_cell_lists, &grid, &mut result);
+    }
+    result
+}
+
+fn check_engines(case: &DifferentialCase, related_cell_lists: &[Vec<u16>], result: &mut CaseResult) {
+    // Every engine behind GridBuilder. They only know the standard rules.
+    let size = case.size();
+    let config = BuildConfig::new(size, size, case.block_width, case.block_height)
+        .limit_milliseconds(BUILD_TIME_LIMIT_MILLISECONDS)
+        .seed(case.seed);
+    for engine in all_engines() {
+        if !engine.honors_time_limit() && case.cell_count() > UNLIMITED_ENGINE_MAX_CELL_COUNT {
+            continue;
+        }
+        result.run_count += 1;
+        let engine_name = engine.name().to_string();
+        match catch_panic(|| engine.build(&config)) {
+            Err(message) => result.discrepancies.push(Discrepancy::Panicked { engine: engine_name, message }),
+            Ok(outcome) => match &outcome.result {
+                Ok(values) => if let Err(reason) = validate_values(case, related_cell_lists, values) {
+                    result.discrepancies.push(Discrepancy::InvalidGrid { engine: engine_name, reason });
+                },
+                Err(SudokuError::TimeLimitExceeded { .. }) | Err(SudokuError::AttemptBudgetExhausted { .. }) => result.inconclusive_count += 1,
+                Err(error) => result.discrepancies.push(Discrepancy::BuildFailed { engine: engine_name, reason: error.to_string() }),
+            },
+        }
+    }
+}
+
+fn check_strategies(case: &DifferentialCase, related_cell_lists: &[Vec<u16>], result: &mut CaseResult) -> Option<Grid> {
+    // Every grid_constraint_solve strategy with the case's rules. Returns the first valid grid.
+    let mut first_grid = None;
+    let mut found_by = vec![];
+    let mut unsatisfiable_by = vec![];
+    for strategy in [BuildStrategy::Auto, BuildStrategy::NextCell, BuildStrategy::Flat, BuildStrategy::FlatUsize, BuildStrategy::Fixed, BuildStrategy::Pattern].iter() {
+        result.run_count += 1;
+        let engine_name = format!("Builder {:?}", strategy);
+        let mut builder = Builder::with_block(case.block_width, case.block_height)
+            .limit_milliseconds(BUILD_TIME_LIMIT_MILLISECONDS)
+            .node_limit(BUILD_NODE_LIMIT);
+        for rule in case.rules().into_iter().skip(3) {
+            builder = builder.rule(rule);
+        }
+        builder.strategy = strategy.clone();
+        let build_result = {
+            let _seeded_rng = seeded_rng(Some(case.seed));
+            catch_panic(|| builder.build())
+        };
+        match build_result {
+            Err(message) => result.discrepancies.push(Discrepancy::Panicked { engine: engine_name, message }),
+            Ok(Ok(grid)) => match validate_values(case, related_cell_lists, &grid.values) {
+                Ok(()) => {
+                    found_by.push(engine_name);
+                    if first_grid.is_none() {
+                        first_grid = Some(grid);
+                    }
+                },
+                Err(reason) => result.discrepancies.push(Discrepancy::InvalidGrid { engine: engine_name, reason }),
+            },
+            Ok(Err(SudokuError::Unsatisfiable { .. })) if !case.rule_names.is_empty() => unsatisfiable_by.push(engine_name),
+            Ok(Err(SudokuError::TimeLimitExceeded { .. })) | Ok(Err(SudokuError::AttemptBudgetExhausted { .. })) => result.inconclusive_count += 1,
+            Ok(Err(error)) => result.discrepancies.push(Discrepancy::BuildFailed { engine: engine_name, reason: error.to_string() }),
+        }
+    }
+    if let (Some(found_by), Some(unsatisfiable_by)) = (found_by.first(), unsatisfiable_by.first()) {
+        result.discrepancies.push(Discrepancy::Diverged { found_by: found_by.clone(), unsatisfiable_by: unsatisfiable_by.clone() });
+    }
+    first_grid
+}
+
+fn check_counters(case: &DifferentialCase, related_cell_lists: &[Vec<u16>], grid: &Grid, result: &mut CaseResult) {
+    let mut puzzle = grid.clone();
+    {
+        let _seeded_rng = seeded_rng(Some(case.seed));
+        puzzle.remove_cells(case.remove_cell_count);
+    }
+
+    // Counters that count every solution up to the limit.
+    let mut full_counts: Vec<(String, Option<usize>)> = vec![];
+    let solver_count = catch_panic(|| Solver::new(&puzzle).limit_milliseconds(COUNT_TIME_LIMIT_MILLISECONDS).count_solutions());
+    full_counts.push(("Solver::count_solutions".to_string(), counter_result(solver_count, result)));
+    let checker_solutions = catch_panic(|| UniquenessChecker::new(&puzzle)
+        .and_then(|checker| checker.limit_milliseconds(COUNT_TIME_LIMIT_MILLISECONDS).solutions(&puzzle.values, SOLUTION_COUNT_LIMIT)));
+    if let Ok(Ok(solutions)) = &checker_solutions {
+        for solution in solutions.iter() {
+            let gives_clues = solution.iter().zip(puzzle.values.iter()).all(|(value, clue)| *clue == NO_VALUE || value == clue);
+            let validation = if gives_clues { validate_values(case, related_cell_lists, solution) } else { Err("The solution doesn't match the clues.".to_string()) };
+            if let Err(reason) = validation {
+                result.discrepancies.push(Discrepancy::InvalidGrid { engine: "UniquenessChecker::solutions".to_string(), reason });
+            }
+        }
+    }
+    full_counts.push(("UniquenessChecker::solutions".to_string(), counter_result(checker_solutions.map(|solutions| solutions.map(|solutions| solutions.len())), result)));
+    result.run_count += 1;
+    let oracle_count = oracle_count(related_cell_lists, &puzzle.values, case.size());
+    if oracle_count.is_none() {
+        result.inconclusive_count += 1;
+    }
+    full_counts.push(("oracle".to_string(), oracle_count));
+
+    // Counters that only tell zero, one, or many.
+    let mut type_counts: Vec<(String, Option<usize>)> = vec![];
+    let solution_type = catch_panic(|| Solver::new(&puzzle).limit_milliseconds(COUNT_TIME_LIMIT_MILLISECONDS).unique_solution_type(&mut TriedGrids::new()));
+    type_counts.push(("Solver::unique_solution_type".to_string(), counter_result(solution_type.map(|solution_type| solution_type.map(|solution_type| solution_type_count(&solution_type))), result)));
+    let uniqueness = catch_panic(|| Solver::new(&puzzle).limit_milliseconds(COUNT_TIME_LIMIT_MILLISECONDS).uniqueness());
+    type_counts.push(("Solver::uniqueness".to_string(), counter_result(uniqueness.map(|uniqueness| uniqueness.map(|uniqueness| solution_type_count(&uniqueness.solution_type()))), result)));
+
+    let full_counts = full_counts
+        .into_iter()
+        .filter_map(|(name, count)| count.map(|count| (name, count.min(SOLUTION_COUNT_LIMIT))))
+        .collect::<Vec<_>>();
+    let type_counts = type_counts
+        .into_iter()
+        .filter_map(|(name, count)| count.map(|count| (name, count)))
+        .collect::<Vec<_>>();
+    let full_counts_agree = full_counts.iter().map(|(_, count)| *count).unique().count() <= 1;
+    let type_counts_agree = full_counts.iter().map(|(_, count)| (*count).min(2))
+        .chain(type_counts.iter().map(|(_, count)| *count))
+        .unique()
+        .count() <= 1;
+    if !full_counts_agree || !type_counts_agree {
+        let mut counts = full_counts;
+        counts.extend(type_counts);
+        result.discrepancies.push(Discrepancy::CountMismatch { counts });
+    }
+}
+
+fn counter_result(counter_result: Result<Result<usize, SudokuError>, String>, result: &mut CaseResult) -> Option<usize> {
+    // A count of None leaves the counter out of the comparison. A limit makes it inconclusive and
+    // a panic is a discrepancy.
+    result.run_count += 1;
+    match counter_result {
+        Ok(Ok(count)) => Some(count),
+        Ok(Err(_)) => {
+            result.inconclusive_count += 1;
+            None
+        },
+        Err(message) => {
+            result.discrepancies.push(Discrepancy::Panicked { engine: "solution counter".to_string(), message });
+            None
+        },
+    }
+}
+
+fn solution_type_count(solution_type: &UniqueSolutionType) -> usize {
+    match solution_type {
+        UniqueSolutionType::Zero => 0,
+        UniqueSolutionType::One => 1,
+        UniqueSolutionType::Many => 2,
+    }
+}
+
+pub fn shrink(case: &DifferentialCase, discrepancy: &Discrepancy) -> (DifferentialCase, Discrepancy) {
+    // Find the lowest seed with the same kind of failure, then make that case as simple as it can
+    // be while it still fails.
+    let signature = discrepancy.signature();
+    let reproduce = |case: &DifferentialCase| check_case(case)
+        .discrepancies
+        .into_iter()
+        .find(|discrepancy| discrepancy.signature() == signature);
+    let mut shrunk = (case.clone(), discrepancy.clone());
+    for seed in 0..case.seed.min(SHRINK_SEED_LIMIT) {
+        let lower_case = DifferentialCase::from_seed(seed);
+        if let Some(discrepancy) = reproduce(&lower_case) {
+            shrunk = (lower_case, discrepancy);
+            break;
+        }
+    }
+    'simplify: loop {
+        for simpler_case in shrunk.0.simpler_cases() {
+            if let Some(discrepancy) = reproduce(&simpler_case) {
+                shrunk = (simpler_case, discrepancy);
+                continue 'simplify;
+            }
+        }
+        break;
+    }
+    shrunk
+}
+
+fn related_cell_lists(case: &DifferentialCase) -> Vec<Vec<u16>> {
+    // The related cells for each cell, worked out from the rules for every pair of cells. This is
+    // slow but doesn't depend on any engine. Cells are numbered from 1 in the rules.
+    let size = case.size();
+    let grid = Grid::new(size, size, case.block_width, case.block_height);
+    let cells = (0..case.cell_count() as u16)
+        .map(|index| {
+            let row = index / size as u16;
+            let column = index % size as u16;
+            let block = (row / case.block_height as u16) * case.block_height as u16 + (column / case.block_width as u16);
+            Cell::new(index + 1, row as i8 + 1, column as i8 + 1, block as i8 + 1)
+        })
+        .collect::<Vec<_>>();
+    let rules = case.rules();
+    cells
+        .iter()
+        .map(|cell| cells
+            .iter()
+            .filter(|other_cell| other_cell.index != cell.index && rules.iter().any(|rule| rule(&grid, cell, other_cell)))
+            .map(|other_cell| other_cell.index - 1)
+            .collect())
+        .collect()
+}
+
+pub fn validate_values(case: &DifferentialCase, related_cell_lists: &[Vec<u16>], values: &[u8]) -> Result<(), String> {
+    if values.len() != case.cell_count() {
+        return Err(format!("There are {} values but the grid has {} cells.", values.len(), case.cell_count()));
+    }
+    for (index, value) in values.iter().enumerate() {
+        if *value == NO_VALUE || *value > case.size() {
+            return Err(format!("Cell index {} has value {}, which is out of range.", index, value));
+        }
+        if let Some(related_index) = related_cell_lists[index].iter().find(|related_index| values[**related_index as usize] == *value) {
+            return Err(format!("Cell indexes {} and {} are related and both have value {}.", index, related_index, value));
+        }
+    }
+    Ok(())
+}
+
+fn oracle_count(related_cell_lists: &[Vec<u16>], values: &[u8], max_value: u8) -> Option<usize> {
+    // A plain depth-first count, capped at SOLUTION_COUNT_LIMIT. Returns None if it runs out of
+    // nodes first.
+    for (index, value) in values.iter().enumerate() {
+        if *value != NO_VALUE && related_cell_lists[index].iter().any(|related_index| values[*related_index as usize] == *value) {
+            return Some(0);
+        }
+    }
+    let mut values = values.to_vec();
+    let mut solution_count = 0;
+    let mut node_count = 0;
+    if oracle_search(related_cell_lists, &mut values, max_value, &mut solution_count, &mut node_count) {
+        Some(solution_count)
+    } else {
+        None
+    }
+}
+
+fn oracle_search(related_cell_lists: &[Vec<u16>], values: &mut Vec<u8>, max_value: u8, solution_count: &mut usize, node_count: &mut usize) -> bool {
+    // Returns false if the node limit was reached.
+    *node_count += 1;
+    if *node_count > ORACLE_NODE_LIMIT {
+        return false;
+    }
+    let index = match values.iter().position(|value| *value == NO_VALUE) {
+        Some(index) => index,
+        None => {
+            *solution_count += 1;
+            return true;
+        }
+    };
+    for value in 1..=max_value {
+        if related_cell_lists[index].iter().all(|related_index| values[*related_index as usize] != value) {
+            values[index] = value;
+            let completed = oracle_search(related_cell_lists, values, max_value, solution_count, node_count);
+            values[index] = NO_VALUE;
+            if !completed {
+                return false;
+            }
+            if *solution_count >= SOLUTION_COUNT_LIMIT {
+                break;
+            }
+        }
+    }
+    true
+}
+
+impl Display for DifferentialCase {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let size = self.size();
+        write!(f, "seed {}: {}x{} with {}x{} blocks, extra rules [{}], {} cells removed", self.seed, size, size, self.block_width, self.block_height, self.rule_names.iter().join(", "), self.remove_cell_count)
+    }
+}
+
+impl Display for Discrepancy {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Discrepancy::InvalidGrid { engine, reason } => write!(f, "{} gave an invalid grid: {}", engine, reason),
+            Discrepancy::BuildFailed { engine, reason } => write!(f, "{} failed: {}", engine, reason),
+            Discrepancy::Diverged { found_by, unsatisfiable_by } => write!(f, "{} found a grid but {} said there isn't one", found_by, unsatisfiable_by),
+            Discrepancy::Panicked { engine, message } => write!(f, "{} panicked: {}", engine, message),
+            Discrepancy::CountMismatch { counts } => write!(f, "solution counts disagree: {}", counts.iter().map(|(name, count)| format!("{} = {}", name, count)).join(", ")),
+        }
+    }
+}
+
+impl Display for DifferentialReport {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{} cases, {} runs, {} inconclusive, {} failures", self.case_count, self.run_count, self.inconclusive_count, self.failure_count)?;
+        for failure in self.failures.iter() {
+            write!(f, "\n{}\n    first seen at {}\n    shrunk to {}\n    {}", failure.discrepancy, failure.case, failure.shrunk_case, failure.shrunk_discrepancy)?;
+        }
+        Ok(())
+    }
+}
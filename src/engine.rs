@@ -0,0 +1,205 @@
+#![allow(dead_code)]
+
+// One interface over every generation of builder in the crate. Each builder module has an Engine
+// type that implements GridBuilder, so a benchmark or a correctness check can be written once and
+// run against all of them with all_engines().
+// The engines don't all support the same things. The older ones index cells with a u8 so they
+// can't go past 256 cells, several of them have no time limit, and only some count nodes or tried
+// grids. max_cell_count() and honors_time_limit() say what each engine can do, and the counts in
+// BuildStats are None where the engine doesn't keep them.
+
+use std::time::{Duration, Instant};
+use std::fmt::{self, Display, Formatter};
+use itertools::Itertools;
+
+use crate::*;
+use crate::grid_constraint_solve::SudokuError;
+
+pub fn main() {
+    try_all_engines();
+}
+
+fn try_all_engines() {
+    let config = BuildConfig::with_block(2, 2).limit_milliseconds(1_000).seed(1);
+    for engine in all_engines() {
+        let outcome = engine.build(&config);
+        match &outcome.result {
+            Ok(values) => println!("try_all_engines(): {}: {}", outcome.stats, values.iter().join("")),
+            Err(error) => println!("try_all_engines(): {}: {}", outcome.stats, error),
+        }
+    }
+}
+
+pub fn all_engines() -> Vec<Box<dyn GridBuilder>> {
+    use crate::grid_constraint_solve::builder::BuildStrategy;
+    vec![
+        Box::new(builder_1::Engine),
+        Box::new(builder_obj::Engine),
+        Box::new(builder_obj_log::Engine),
+        Box::new(builder_vec::Engine),
+        Box::new(builder_vec_log::Engine),
+        Box::new(builder_vec_large::Engine),
+        Box::new(grid_constraint::builder::Engine),
+        Box::new(grid_constraint_solve::builder::Engine::new(BuildStrategy::NextCell)),
+        Box::new(grid_constraint_solve::builder::Engine::new(BuildStrategy::Flat)),
+        Box::new(grid_constraint_solve::builder::Engine::new(BuildStrategy::FlatUsize)),
+        Box::new(grid_constraint_solve::builder::Engine::new(BuildStrategy::Fixed)),
+        Box::new(grid_constraint_solve::builder::Engine::new(BuildStrategy::Pattern)),
+    ]
+}
+
+#[derive(Clone, Debug)]
+pub struct BuildConfig {
+    pub width: u8,
+    pub height: u8,
+    pub block_width: u8,
+    pub block_height: u8,
+    pub time_limit: Option<Duration>,
+    pub max_tried_grid_count: Option<usize>,
+    pub seed: Option<u64>,
+}
+
+#[derive(Clone, Debug)]
+pub struct BuildStats {
+    pub engine_name: &'static str,
+    pub time: Duration,
+    pub node_count: Option<usize>,
+    pub tried_grid_count: Option<usize>,
+}
+
+#[derive(Clone, Debug)]
+pub struct BuildOutcome {
+    // The values of the completed grid, row by row.
+    pub result: Result<Vec<u8>, SudokuError>,
+    pub stats: BuildStats,
+}
+
+pub trait GridBuilder {
+
+    fn name(&self) -> &'static str;
+
+    fn max_cell_count(&self) -> usize;
+
+    fn honors_time_limit(&self) -> bool;
+
+    // Builds one grid. Called from build(), which has already checked the config and seeded the
+    // random number generator. Fill in whichever counts in stats the engine keeps. An engine that
+    // only knows that it stopped without a grid can use BuildStats::failure() for the error.
+    fn build_values(&self, config: &BuildConfig, stats: &mut BuildStats) -> Result<Vec<u8>, SudokuError>;
+
+    fn build(&self, config: &BuildConfig) -> BuildOutcome {
+        let mut stats = BuildStats::new(self.name());
+        let start_time = Instant::now();
+        let result = match self.check_config(config) {
+            Ok(()) => {
+                // The guard keeps the seed from leaking into whatever runs next on this thread,
+                // even if the build panics.
+                let _seeded_rng = seeded_rng(config.seed);
+                self.build_values(config, &mut stats)
+            },
+            Err(error) => Err(error),
+        };
+        stats.time = Instant::now() - start_time;
+        BuildOutcome { result, stats }
+    }
+
+    fn check_config(&self, config: &BuildConfig) -> Result<(), SudokuError> {
+        let invalid_dimensions = |reason: String| SudokuError::InvalidDimensions {
+            width: config.width,
+            height: config.height,
+            block_width: config.block_width,
+            block_height: config.block_height,
+            reason,
+        };
+        if config.width == 0 || config.height == 0 || config.block_width == 0 || config.block_height == 0 {
+            return Err(invalid_dimensions("The grid and block dimensions must be at least 1.".to_string()));
+        }
+        if config.block_width as u16 * config.block_height as u16 > u8::MAX as u16 {
+            return Err(invalid_dimensions(format!("The blocks have more than {} cells.", u8::MAX)));
+        }
+        let cell_count = config.cell_count();
+        if cell_count > self.max_cell_count() {
+            return Err(invalid_dimensions(format!("{} can't build a grid with {} cells. The most it can handle is {}.", self.name(), cell_count, self.max_cell_count())));
+        }
+        Ok(())
+    }
+
+}
+
+impl BuildConfig {
+
+    pub fn new(width: u8, height: u8, block_width: u8, block_height: u8) -> Self {
+        Self {
+            width,
+            height,
+            block_width,
+            block_height,
+            time_limit: None,
+            max_tried_grid_count: None,
+            seed: None,
+        }
+    }
+
+    pub fn with_block(block_width: u8, block_height: u8) -> Self {
+        // A size past u8::MAX doesn't fit in the config, so it's capped here and check_config()
+        // rejects the blocks.
+        let size = (block_width as u16 * block_height as u16).min(u8::MAX as u16) as u8;
+        Self::new(size, size, block_width, block_height)
+    }
+
+    pub fn limit_milliseconds(mut self, msec: u64) -> Self {
+        self.time_limit = Some(Duration::from_millis(msec));
+        self
+    }
+
+    pub fn max_tried_grid_count(mut self, max_tried_grid_count: usize) -> Self {
+        self.max_tried_grid_count = Some(max_tried_grid_count);
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn cell_count(&self) -> usize {
+        self.width as usize * self.height as usize
+    }
+
+}
+
+impl BuildStats {
+    pub fn new(engine_name: &'static str) -> Self {
+        Self {
+            engine_name,
+            time: Duration::default(),
+            node_count: None,
+            tried_grid_count: None,
+        }
+    }
+
+    pub fn failure(&self, start_time: Instant, time_limit_exceeded: bool) -> SudokuError {
+        // The error for an engine that stopped without a grid and can only say whether it ran out
+        // of time. Otherwise the search ran out of options.
+        let elapsed = Instant::now() - start_time;
+        let node_count = self.node_count.unwrap_or(0);
+        if time_limit_exceeded {
+            SudokuError::TimeLimitExceeded { elapsed, node_count }
+        } else {
+            SudokuError::Unsatisfiable { elapsed, node_count }
+        }
+    }
+}
+
+impl Display for BuildStats {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}: time = {:?}", self.engine_name, self.time)?;
+        if let Some(node_count) = self.node_count {
+            write!(f, ", node_count = {}", node_count)?;
+        }
+        if let Some(tried_grid_count) = self.tried_grid_count {
+            write!(f, ", tried_grid_count = {}", tried_grid_count)?;
+        }
+        Ok(())
+    }
+}
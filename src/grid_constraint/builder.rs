@@ -5,7 +5,7 @@
 // - Custom symbols.
 // This goes with grid.
 
-use rand::{thread_rng, Rng};
+use rand::Rng;
 use itertools::Itertools;
 // use bit_vec::BitVec;
 // use std::sync::Mutex;
@@ -17,6 +17,8 @@ use std::time::Instant;
 
 use crate::*;
 use crate::grid_constraint::grid::Grid;
+use crate::engine::{GridBuilder, BuildConfig, BuildStats};
+use crate::grid_constraint_solve::SudokuError;
 
 const RUN_INVARIANT: bool = false;
 const VERBOSE: u8 = 0;
@@ -310,7 +312,7 @@ impl Builder {
         //bg!(try_cell_index, &try_values);
         let mut branch_size = 0u8;
         while !try_values.is_empty() {
-            // let try_value = try_values.remove(thread_rng().gen_range(0, try_values.len()));
+            // let try_value = try_values.remove(rng().gen_range(0, try_values.len()));
             let try_value = try_values.remove(0);
             branch_size += 1;
             build_run.branch_sizes[branch_size as usize - 1] += 1;
@@ -520,7 +522,7 @@ impl Builder {
     fn choose_try_cell_index(grid: &Grid) -> u16 {
         let min = *grid.remaining_value_counts.iter().filter(|count| **count > 0).min().unwrap();
         let found_count = grid.remaining_value_counts.iter().filter(|count| **count == min).count();
-        let random_index = thread_rng().gen_range(0, found_count);
+        let random_index = rng().gen_range(0, found_count);
         //rintln!("choose_try_cell_index(): min = {}, found_count = {}, random_index = {}", min, found_count, random_index);
 
         let mut i = 0;
@@ -732,6 +734,40 @@ impl Cell {
     }
 }
 
+// The grid_constraint engine for engine::GridBuilder, using the standard row, column, and block
+// rules. Its counts come from the BuildRun.
+pub struct Engine;
+
+impl GridBuilder for Engine {
+    fn name(&self) -> &'static str {
+        "grid_constraint"
+    }
+
+    fn max_cell_count(&self) -> usize {
+        u8::MAX as usize * u8::MAX as usize
+    }
+
+    fn honors_time_limit(&self) -> bool {
+        true
+    }
+
+    fn build_values(&self, config: &BuildConfig, stats: &mut BuildStats) -> Result<Vec<u8>, SudokuError> {
+        let start_time = Instant::now();
+        let mut builder = Builder::new(config.width, config.height, config.block_width, config.block_height);
+        builder.time_limit = config.time_limit;
+        builder.max_tried_grid_count = config.max_tried_grid_count;
+        let result = builder.build();
+        if let Some(build_run) = builder.build_runs.last() {
+            stats.node_count = Some(build_run.fill_next_cell_count);
+            stats.tried_grid_count = Some(build_run.tried_grids.len());
+        }
+        // The only failure this builder reports is running out of time.
+        result
+            .map(|grid| grid.values)
+            .map_err(|_| stats.failure(start_time, true))
+    }
+}
+
 /*
 impl FailedGridSet {
     fn new() -> Self {
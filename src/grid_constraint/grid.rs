@@ -13,7 +13,7 @@ use array2d::Array2D;
 use std::ops::Range;
 //use std::fmt::{Display, Formatter, Error};
 // use std::time::Instant;
-use rand::{thread_rng, Rng};
+use rand::Rng;
 
 use crate::*;
 
@@ -126,7 +126,7 @@ impl Grid {
             .collect::<Vec<_>>();
         let remove_cell_count = remove_cell_count.min(indexes.len() as u16);
         for _ in 0..remove_cell_count.min(self.cell_count) {
-            let index = indexes.remove(thread_rng().gen_range(0, indexes.len()));
+            let index = indexes.remove(rng().gen_range(0, indexes.len()));
             self.values[index as usize] = NO_VALUE;
         }
         self.recalc_remaining_values();
@@ -0,0 +1,163 @@
+#![allow(dead_code)]
+
+// Whether a puzzle can be solved by singles alone, and if not, its backdoor: the smallest set of
+// cells whose values, if they were given, would let singles finish the puzzle. A single is either
+// an empty cell with only one remaining value (as in Solver::resolve_cells_with_one_remaining()) or
+// a value with only one possible cell in a row, column, or block. The backdoor size is a difficulty
+// measure that doesn't depend on a catalogue of solving techniques.
+// This goes with solver::Solver.
+
+use crate::*;
+use super::grid::Grid;
+use super::builder::Builder;
+use super::solver::{Solver, SolverTask};
+use super::unique::Uniqueness;
+use super::SudokuError;
+
+pub fn main() {
+    try_analyze_singles();
+}
+
+fn try_analyze_singles() {
+    let grid_size = 9;
+    let max_backdoor_size = 3;
+    let repeat_count = 5;
+
+    for _ in 0..repeat_count {
+        let grid = Builder::with_size(grid_size).build().unwrap();
+        let mut solver = Solver::new(&grid);
+        let puzzle = solver.reduce_minimal().unwrap().grid;
+        let mut solver = Solver::new(&puzzle);
+        match solver.analyze_singles(max_backdoor_size) {
+            Ok(analysis) => {
+                println!("try_analyze_singles(): clues = {}, {:?}, time = {:?}", puzzle.solved_cell_count(), analysis, solver.runner.time.unwrap());
+            },
+            Err(error) => println!("Solver error: {}", error),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SinglesAnalysis {
+    pub solvable_by_singles: bool,
+    // How many empty cells singles fill in before getting stuck.
+    pub filled_by_singles: u16,
+    // None if there's no backdoor within the size limit.
+    pub backdoor_size: Option<usize>,
+    // The cells and values of one smallest backdoor.
+    pub backdoor: Vec<(u16, u8)>,
+}
+
+pub(crate) enum SingleSearch {
+    Found(u16, u8),
+    Contradiction,
+    NotFound,
+}
+
+impl Solver {
+
+    pub fn solvable_by_singles(&mut self) -> Result<bool, SudokuError> {
+        Ok(self.analyze_singles(0)?.solvable_by_singles)
+    }
+
+    pub fn analyze_singles(&mut self, max_backdoor_size: usize) -> Result<SinglesAnalysis, SudokuError> {
+        // The puzzle needs a unique solution since the backdoor values come from it. Backdoors are
+        // tried in order of size, so the one returned is as small as possible.
+        let solution = match self.uniqueness()? {
+            Uniqueness::Zero => return Err(self.runner.failure_clone()),
+            Uniqueness::One { solution } => solution,
+            uniqueness @ Uniqueness::Many { .. } => return Err(SudokuError::MultipleSolutions { differing_indexes: uniqueness.differing_indexes() }),
+        };
+        self.task = SolverTask::Backdoor;
+        self.runner = self.runner.restart();
+
+        let houses = self.grid.houses();
+        let mut singles_grid = self.grid.clone();
+        if !self.resolve_singles(&mut singles_grid, &houses) {
+            return Err(SudokuError::Unsatisfiable { elapsed: self.runner.elapsed(), node_count: self.runner.node_count });
+        }
+        let mut analysis = SinglesAnalysis {
+            solvable_by_singles: singles_grid.unsolved_cell_count == 0,
+            filled_by_singles: singles_grid.solved_cell_count() - self.grid.solved_cell_count(),
+            backdoor_size: None,
+            backdoor: vec![],
+        };
+        if analysis.solvable_by_singles {
+            analysis.backdoor_size = Some(0);
+        } else {
+            for backdoor_size in 1..=max_backdoor_size {
+                if self.find_backdoor(&singles_grid, &solution, &houses, 0, backdoor_size, &mut analysis.backdoor)? {
+                    analysis.backdoor_size = Some(analysis.backdoor.len());
+                    break;
+                }
+            }
+        }
+        self.runner.success = Some(true);
+        self.runner.mark_end();
+        Ok(analysis)
+    }
+
+    fn find_backdoor(&mut self, grid: &Grid, solution: &[u8], houses: &[Vec<u16>], start_index: u16, remaining_size: usize, backdoor: &mut Vec<(u16, u8)>) -> Result<bool, SudokuError> {
+        // Try each empty cell from start_index on as the next member of the backdoor. The grid has
+        // already had singles applied, so cells that singles would fill anyway are never tried.
+        for index in start_index..grid.cell_count {
+            if grid.values[index as usize] != NO_VALUE {
+                continue;
+            }
+            if !self.runner.count_node(backdoor.len() as u16, grid.solved_cell_count()) || !self.runner.check_continue() {
+                return Err(self.runner.failure_clone());
+            }
+            let value = solution[index as usize];
+            let mut try_grid = grid.clone();
+            try_grid.set_value(index, value);
+            backdoor.push((index, value));
+            // The values all come from the solution so singles can't run into a contradiction.
+            self.resolve_singles(&mut try_grid, houses);
+            if try_grid.unsolved_cell_count == 0 {
+                return Ok(true);
+            }
+            if remaining_size > 1 && self.find_backdoor(&try_grid, solution, houses, index + 1, remaining_size - 1, backdoor)? {
+                return Ok(true);
+            }
+            backdoor.pop();
+        }
+        Ok(false)
+    }
+
+    pub(crate) fn resolve_singles(&mut self, grid: &mut Grid, houses: &[Vec<u16>]) -> bool {
+        // Fill in singles until there are none left. Returns false if some cell or some value in a
+        // house runs out of places to go.
+        loop {
+            if !self.resolve_cells_with_one_remaining(grid) {
+                return false;
+            }
+            match hidden_single(grid, houses) {
+                SingleSearch::Found(index, value) => grid.set_value(index, value),
+                SingleSearch::Contradiction => return false,
+                SingleSearch::NotFound => return true,
+            }
+        }
+    }
+
+}
+
+pub(crate) fn hidden_single(grid: &Grid, houses: &[Vec<u16>]) -> SingleSearch {
+    // Look for a value that has only one possible cell in a house. This only applies to houses
+    // with a cell for every value, since otherwise a value can be left out of the house entirely.
+    for house in houses.iter().filter(|house| house.len() == grid.max_value as usize) {
+        for value in 1..=grid.max_value {
+            if house.iter().any(|index| grid.values[*index as usize] == value) {
+                continue;
+            }
+            let mut possible_indexes = house
+                .iter()
+                .filter(|index| grid.values[**index as usize] == NO_VALUE && grid.has_remaining_value(**index, value));
+            match (possible_indexes.next(), possible_indexes.next()) {
+                (None, _) => return SingleSearch::Contradiction,
+                (Some(index), None) => return SingleSearch::Found(*index, value),
+                _ => {},
+            }
+        }
+    }
+    SingleSearch::NotFound
+}
@@ -0,0 +1,349 @@
+#![allow(dead_code)]
+
+// Summaries of the BuildRuns from a number of builds, to see why some configurations are slow or
+// stall. Each group of runs, usually all the runs from one Builder, becomes a row with success and
+// failure counts, node counts, how often the build had to go back, how many values it tried per
+// cell, and where the time went. The dead ends in every run are also combined into a histogram by
+// how many cells were filled at the time, which shows whether a configuration gets stuck early or
+// only close to the end.
+// Everything can be printed as a table or written as CSV.
+// This goes with builder::BuildRun.
+
+use std::time::Duration;
+use std::fmt::Write;
+
+use super::SudokuError;
+use super::builder::{Builder, BuildRun, BuildStrategy, RULE_KING, RULE_KNIGHT};
+
+// The percentiles of the number of values tried per cell shown in the report.
+const BRANCH_SIZE_PERCENTILES: [f64; 3] = [0.5, 0.9, 0.99];
+const HISTOGRAM_BUCKET_COUNT: usize = 10;
+const HISTOGRAM_BAR_WIDTH: usize = 40;
+
+pub fn main() {
+    try_build_report();
+}
+
+fn try_build_report() {
+    let run_count = 10;
+    let configurations = [
+        ("9x9 NextCell", 9, BuildStrategy::NextCell, false),
+        ("9x9 Fixed", 9, BuildStrategy::Fixed, false),
+        ("9x9 Fixed king knight", 9, BuildStrategy::Fixed, true),
+        ("16x16 Fixed", 16, BuildStrategy::Fixed, false),
+    ];
+    let mut report = BuildReport::new();
+    for (label, size, strategy, chess_rules) in configurations.iter() {
+        let mut builder = Builder::with_size(*size).limit_milliseconds(200);
+        if *chess_rules {
+            builder = builder.rule(RULE_KING).rule(RULE_KNIGHT);
+        }
+        builder.strategy = strategy.clone();
+        for _ in 0..run_count {
+            let _ = builder.build();
+        }
+        report.add(label, builder.build_runs());
+    }
+    println!("try_build_report():\n{}", report.to_table());
+    println!("{}", report.histogram_table());
+    println!("{}", report.to_csv());
+}
+
+#[derive(Clone, Debug)]
+pub struct BuildSummary {
+    pub label: String,
+    pub cell_count: usize,
+    pub run_count: usize,
+    pub success_count: usize,
+    pub time_limit_count: usize,
+    pub node_limit_count: usize,
+    pub cancelled_count: usize,
+    pub unsatisfiable_count: usize,
+    pub node_count_mean: f64,
+    pub node_count_max: usize,
+    pub dead_end_count_mean: f64,
+    // Dead ends per node over all the runs together.
+    pub backtrack_rate: f64,
+    pub restart_count_mean: f64,
+    pub try_value_count_mean: f64,
+    pub tried_grid_registered_count: usize,
+    pub tried_grid_skipped_count: usize,
+    pub tried_grid_found_count: usize,
+    // The number of values tried per cell at each of BRANCH_SIZE_PERCENTILES, or None if the
+    // strategy doesn't count them.
+    pub branch_size_percentiles: Option<Vec<u32>>,
+    pub time_mean: Duration,
+    pub time_max: Duration,
+    // Each of these is None if no run recorded it.
+    pub setup_time_mean: Option<Duration>,
+    pub loop_time_mean: Option<Duration>,
+    pub return_object_time_mean: Option<Duration>,
+    pub remaining_time_mean: Option<Duration>,
+    // The dead ends from every run added together, indexed by the filled cell count.
+    pub filled_cell_counts: Vec<usize>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct BuildReport {
+    pub summaries: Vec<BuildSummary>,
+}
+
+impl BuildReport {
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, label: &str, build_runs: &[BuildRun]) -> &BuildSummary {
+        self.summaries.push(BuildSummary::new(label, build_runs));
+        self.summaries.last().unwrap()
+    }
+
+    pub fn to_table(&self) -> String {
+        let headers = ["label", "runs", "success", "time limit", "node limit", "unsat", "nodes mean", "nodes max", "dead ends mean", "backtrack rate", "restarts mean",
+            "branch p50/p90/p99", "time mean", "time max", "setup", "loop", "return", "other"];
+        let rows = self.summaries
+            .iter()
+            .map(|summary| vec![
+                summary.label.clone(),
+                summary.run_count.to_string(),
+                format!("{:.0}%", summary.success_rate() * 100.0),
+                summary.time_limit_count.to_string(),
+                summary.node_limit_count.to_string(),
+                summary.unsatisfiable_count.to_string(),
+                format!("{:.0}", summary.node_count_mean),
+                summary.node_count_max.to_string(),
+                format!("{:.0}", summary.dead_end_count_mean),
+                format!("{:.3}", summary.backtrack_rate),
+                format!("{:.1}", summary.restart_count_mean),
+                summary.branch_size_percentiles.as_ref().map_or("-".to_string(), |percentiles| percentiles.iter().map(|size| size.to_string()).collect::<Vec<_>>().join("/")),
+                format_duration(Some(summary.time_mean)),
+                format_duration(Some(summary.time_max)),
+                format_duration(summary.setup_time_mean),
+                format_duration(summary.loop_time_mean),
+                format_duration(summary.return_object_time_mean),
+                format_duration(summary.remaining_time_mean),
+            ])
+            .collect::<Vec<_>>();
+        format_table(&headers, &rows)
+    }
+
+    pub fn histogram_table(&self) -> String {
+        // For each summary, the share of dead ends in each band of filled cell counts.
+        let mut s = String::new();
+        for summary in self.summaries.iter() {
+            let dead_end_count: usize = summary.filled_cell_counts.iter().sum();
+            writeln!(s, "{}: {} dead ends by filled cell count", summary.label, dead_end_count).unwrap();
+            if dead_end_count == 0 {
+                continue;
+            }
+            for (range, count) in summary.histogram_buckets(HISTOGRAM_BUCKET_COUNT) {
+                let share = count as f64 / dead_end_count as f64;
+                let bar = "#".repeat((share * HISTOGRAM_BAR_WIDTH as f64).round() as usize);
+                writeln!(s, "    {:>4}-{:<4} {:>10} {:>5.1}% {}", range.0, range.1, count, share * 100.0, bar).unwrap();
+            }
+        }
+        s
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut s = String::from("label,cell_count,run_count,success_count,time_limit_count,node_limit_count,cancelled_count,unsatisfiable_count,node_count_mean,node_count_max,\
+            dead_end_count_mean,backtrack_rate,restart_count_mean,try_value_count_mean,tried_grid_registered_count,tried_grid_skipped_count,tried_grid_found_count,\
+            branch_size_p50,branch_size_p90,branch_size_p99,time_mean_ms,time_max_ms,setup_time_mean_ms,loop_time_mean_ms,return_object_time_mean_ms,remaining_time_mean_ms\n");
+        for summary in self.summaries.iter() {
+            let branch_size_percentiles = match &summary.branch_size_percentiles {
+                Some(percentiles) => percentiles.iter().map(|size| size.to_string()).collect::<Vec<_>>(),
+                None => vec!["".to_string(); BRANCH_SIZE_PERCENTILES.len()],
+            };
+            writeln!(s, "{},{},{},{},{},{},{},{},{:.1},{},{:.1},{:.6},{:.1},{:.1},{},{},{},{},{},{},{},{},{},{}",
+                csv_field(&summary.label), summary.cell_count, summary.run_count, summary.success_count, summary.time_limit_count, summary.node_limit_count,
+                summary.cancelled_count, summary.unsatisfiable_count, summary.node_count_mean, summary.node_count_max, summary.dead_end_count_mean,
+                summary.backtrack_rate, summary.restart_count_mean, summary.try_value_count_mean, summary.tried_grid_registered_count, summary.tried_grid_skipped_count,
+                summary.tried_grid_found_count, branch_size_percentiles.join(","), csv_milliseconds(Some(summary.time_mean)),
+                csv_milliseconds(Some(summary.time_max)), csv_milliseconds(summary.setup_time_mean), csv_milliseconds(summary.loop_time_mean),
+                csv_milliseconds(summary.return_object_time_mean), csv_milliseconds(summary.remaining_time_mean)).unwrap();
+        }
+        s
+    }
+
+    pub fn histogram_csv(&self) -> String {
+        // One line for each filled cell count with any dead ends, for each summary.
+        let mut s = String::from("label,filled_cell_count,dead_end_count\n");
+        for summary in self.summaries.iter() {
+            for (filled_cell_count, count) in summary.filled_cell_counts.iter().enumerate() {
+                if *count > 0 {
+                    writeln!(s, "{},{},{}", csv_field(&summary.label), filled_cell_count, count).unwrap();
+                }
+            }
+        }
+        s
+    }
+
+}
+
+impl BuildSummary {
+
+    pub fn new(label: &str, build_runs: &[BuildRun]) -> Self {
+        let run_count = build_runs.len();
+        let mean = |total: f64| if run_count == 0 { 0.0 } else { total / run_count as f64 };
+        let count_failures = |is_kind: fn(&SudokuError) -> bool| build_runs
+            .iter()
+            .filter(|build_run| build_run.runner.failure.as_ref().is_some_and(is_kind))
+            .count();
+
+        let node_count_total: usize = build_runs.iter().map(|build_run| build_run.runner.node_count).sum();
+        let dead_end_count_total: usize = build_runs.iter().map(|build_run| build_run.dead_end_count()).sum();
+
+        let cell_count = build_runs.iter().map(|build_run| build_run.filled_cell_counts.len().saturating_sub(1)).max().unwrap_or(0);
+        let mut filled_cell_counts = vec![0; cell_count + 1];
+        for build_run in build_runs.iter() {
+            for (filled_cell_count, count) in build_run.filled_cell_counts.iter().enumerate() {
+                filled_cell_counts[filled_cell_count] += count;
+            }
+        }
+
+        let mut branch_sizes: Vec<u64> = vec![];
+        for build_run in build_runs.iter() {
+            if branch_sizes.len() < build_run.branch_sizes.len() {
+                branch_sizes.resize(build_run.branch_sizes.len(), 0);
+            }
+            for (index, count) in build_run.branch_sizes.iter().enumerate() {
+                branch_sizes[index] += *count as u64;
+            }
+        }
+
+        let times = build_runs.iter().map(|build_run| build_run.runner.time.unwrap_or_default()).collect::<Vec<_>>();
+        let time_total: Duration = times.iter().sum();
+
+        Self {
+            label: label.to_string(),
+            cell_count,
+            run_count,
+            success_count: build_runs.iter().filter(|build_run| build_run.runner.success == Some(true)).count(),
+            time_limit_count: count_failures(|failure| matches!(failure, SudokuError::TimeLimitExceeded { .. })),
+            node_limit_count: count_failures(|failure| matches!(failure, SudokuError::AttemptBudgetExhausted { .. })),
+            cancelled_count: count_failures(|failure| matches!(failure, SudokuError::Cancelled { .. })),
+            unsatisfiable_count: count_failures(|failure| matches!(failure, SudokuError::Unsatisfiable { .. })),
+            node_count_mean: mean(node_count_total as f64),
+            node_count_max: build_runs.iter().map(|build_run| build_run.runner.node_count).max().unwrap_or(0),
+            dead_end_count_mean: mean(dead_end_count_total as f64),
+            backtrack_rate: if node_count_total == 0 { 0.0 } else { dead_end_count_total as f64 / node_count_total as f64 },
+            restart_count_mean: mean(build_runs.iter().map(|build_run| build_run.restart_count).sum::<usize>() as f64),
+            try_value_count_mean: mean(build_runs.iter().map(|build_run| build_run.try_value_count).sum::<usize>() as f64),
+            tried_grid_registered_count: build_runs.iter().map(|build_run| build_run.tried_grid_registered_count).sum(),
+            tried_grid_skipped_count: build_runs.iter().map(|build_run| build_run.tried_grid_skipped_count).sum(),
+            tried_grid_found_count: build_runs.iter().map(|build_run| build_run.tried_grid_found_count).sum(),
+            branch_size_percentiles: branch_size_percentiles(&branch_sizes),
+            time_mean: if run_count == 0 { Duration::default() } else { time_total / run_count as u32 },
+            time_max: times.iter().max().copied().unwrap_or_default(),
+            setup_time_mean: mean_time(build_runs, |build_run| build_run.runner.setup_time),
+            loop_time_mean: mean_time(build_runs, |build_run| build_run.runner.loop_time),
+            return_object_time_mean: mean_time(build_runs, |build_run| build_run.runner.return_object_time),
+            remaining_time_mean: mean_time(build_runs, |build_run| build_run.runner.remaining_time),
+            filled_cell_counts,
+        }
+    }
+
+    pub fn success_rate(&self) -> f64 {
+        if self.run_count == 0 { 0.0 } else { self.success_count as f64 / self.run_count as f64 }
+    }
+
+    pub fn histogram_buckets(&self, bucket_count: usize) -> Vec<((usize, usize), usize)> {
+        // Split the filled cell counts from 0 through cell_count into at most bucket_count bands of
+        // equal width and return each band's lowest and highest count along with its dead ends.
+        let value_count = self.filled_cell_counts.len();
+        let bucket_width = value_count.div_ceil(bucket_count.max(1)).max(1);
+        self.filled_cell_counts
+            .chunks(bucket_width)
+            .enumerate()
+            .map(|(bucket_index, counts)| {
+                let low = bucket_index * bucket_width;
+                ((low, low + counts.len() - 1), counts.iter().sum())
+            })
+            .collect()
+    }
+
+}
+
+fn branch_size_percentiles(branch_sizes: &[u64]) -> Option<Vec<u32>> {
+    // branch_sizes[n - 1] is the number of cells that got as far as their nth value, so the
+    // number of cells that tried exactly n values is branch_sizes[n - 1] - branch_sizes[n].
+    let cell_count = *branch_sizes.first()?;
+    if cell_count == 0 {
+        return None;
+    }
+    let percentiles = BRANCH_SIZE_PERCENTILES
+        .iter()
+        .map(|percentile| {
+            let target = (percentile * cell_count as f64).ceil() as u64;
+            let mut cumulative = 0;
+            for (index, count) in branch_sizes.iter().enumerate() {
+                let next_count = branch_sizes.get(index + 1).copied().unwrap_or(0);
+                cumulative += count - next_count;
+                if cumulative >= target {
+                    return index as u32 + 1;
+                }
+            }
+            branch_sizes.len() as u32
+        })
+        .collect();
+    Some(percentiles)
+}
+
+fn mean_time<F>(build_runs: &[BuildRun], f: F) -> Option<Duration>
+    where F: Fn(&BuildRun) -> Option<Duration>
+{
+    let times = build_runs.iter().filter_map(f).collect::<Vec<_>>();
+    if times.is_empty() {
+        None
+    } else {
+        Some(times.iter().sum::<Duration>() / times.len() as u32)
+    }
+}
+
+fn format_duration(duration: Option<Duration>) -> String {
+    match duration {
+        Some(duration) => format!("{:.3}ms", duration.as_secs_f64() * 1_000.0),
+        None => "-".to_string(),
+    }
+}
+
+fn csv_milliseconds(duration: Option<Duration>) -> String {
+    match duration {
+        Some(duration) => format!("{:.3}", duration.as_secs_f64() * 1_000.0),
+        None => "".to_string(),
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains(',') || field.contains('"') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn format_table(headers: &[&str], rows: &[Vec<String>]) -> String {
+    // The first column is left aligned and the rest are right aligned.
+    let mut widths = headers.iter().map(|header| header.chars().count()).collect::<Vec<_>>();
+    for row in rows.iter() {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let format_row = |cells: Vec<&str>| cells
+        .iter()
+        .zip(widths.iter())
+        .enumerate()
+        .map(|(column_index, (cell, width))| if column_index == 0 { format!("{:<width$}", cell, width = width) } else { format!("{:>width$}", cell, width = width) })
+        .collect::<Vec<_>>()
+        .join("  ");
+    let mut s = format_row(headers.to_vec());
+    s.push('\n');
+    s.push_str(&widths.iter().map(|width| "-".repeat(*width)).collect::<Vec<_>>().join("  "));
+    for row in rows.iter() {
+        s.push('\n');
+        s.push_str(&format_row(row.iter().map(|cell| cell.as_str()).collect()));
+    }
+    s
+}
@@ -5,7 +5,8 @@
 // - Custom symbols.
 // This goes with grid.
 
-use rand::{thread_rng, Rng};
+use rand::Rng;
+use rand::seq::SliceRandom;
 use itertools::Itertools;
 // use bit_vec::BitVec;
 // use std::sync::Mutex;
@@ -16,63 +17,57 @@ use std::collections::hash_map::DefaultHasher;
 
 use crate::*;
 use super::grid::Grid;
-use super::Runner;
-
-const RUN_INVARIANT: bool = false;
-const VERBOSE: u8 = 0;
-const LOG_LEVEL: u8 = 2;
-
-const GRID_9_VALUE_COUNT: usize = 9;
-const GRID_9_CELL_COUNT: usize = GRID_9_VALUE_COUNT * GRID_9_VALUE_COUNT;
-// This is enough to cover a standard-rules grid. Revisit for more rules.
-// const GRID_9_MAX_MAX_RELATED_CELL_COUNT: usize = 20;
-// Recalculate this if the above value is changed.
-// const GRID_9_RELATED_CELL_LIST_SIZE: usize = 1_620;
-// Cell count times value count.
-const GRID_9_REMANING_VALUE_LIST_SIZE: usize = GRID_9_CELL_COUNT * GRID_9_VALUE_COUNT;
-
-const GRID_16_VALUE_COUNT: usize = 16;
-const GRID_16_CELL_COUNT: usize = GRID_16_VALUE_COUNT * GRID_16_VALUE_COUNT;
-const GRID_16_REMANING_VALUE_LIST_SIZE: usize = GRID_16_CELL_COUNT * GRID_16_VALUE_COUNT;
-const GRID_25_VALUE_COUNT: usize = 25;
-const GRID_25_CELL_COUNT: usize = GRID_25_VALUE_COUNT * GRID_25_VALUE_COUNT;
-const GRID_25_REMANING_VALUE_LIST_SIZE: usize = GRID_25_CELL_COUNT * GRID_25_VALUE_COUNT;
-const GRID_36_VALUE_COUNT: usize = 36;
-const GRID_36_CELL_COUNT: usize = GRID_36_VALUE_COUNT * GRID_36_VALUE_COUNT;
-const GRID_36_REMANING_VALUE_LIST_SIZE: usize = GRID_36_CELL_COUNT * GRID_36_VALUE_COUNT;
-const GRID_49_VALUE_COUNT: usize = 49;
-const GRID_49_CELL_COUNT: usize = GRID_49_VALUE_COUNT * GRID_49_VALUE_COUNT;
-const GRID_49_REMANING_VALUE_LIST_SIZE: usize = GRID_49_CELL_COUNT * GRID_49_VALUE_COUNT;
-
-
-type Rule = &'static dyn Fn(&Grid, &Cell, &Cell) -> bool;
-const RULE_ROW: Rule = &|_grid, cell_1, cell_2| cell_1.row == cell_2.row;
-const RULE_COLUMN: Rule = &|_grid, cell_1, cell_2| cell_1.column == cell_2.column;
-const RULE_BLOCK: Rule = &|_grid, cell_1, cell_2| cell_1.block == cell_2.block;
-const RULE_KING: Rule = &|_grid, cell_1, cell_2| cell_1.row_distance(cell_2) <= 1 && cell_1.column_distance(cell_2) <= 1;
-const RULE_BISHOP: Rule = &|_grid, cell_1, cell_2| cell_1.row_distance(cell_2) == cell_1.column_distance(cell_2);
-const RULE_BISHOP_2: Rule = &|_grid, cell_1, cell_2| {
+use super::{Runner, RunControl, CancellationToken, Progress, SudokuError};
+use super::diagnostics::{self, Checkpoint, Diagnostics, diagnostic};
+use super::build_report::BuildReport;
+use super::transform::{self, GridTransform};
+use log::Level;
+use crate::engine::{GridBuilder, BuildConfig, BuildStats};
+
+
+// BuildStrategy::Fixed sizes its arrays for one of these cell counts, whichever is the smallest
+// that fits the grid: 9x9, 16x16, 36x36, and 64x64. The remaining values for a cell are a u64.
+const FIXED_CELL_CAPACITY_SMALL: usize = 81;
+const FIXED_CELL_CAPACITY_MEDIUM: usize = 256;
+const FIXED_CELL_CAPACITY_LARGE: usize = 1_296;
+const FIXED_CELL_CAPACITY_MAX: usize = 4_096;
+const FIXED_MAX_VALUE_COUNT: usize = 64;
+const FIXED_CLOCK_CHECK_INTERVAL: usize = 4_096;
+
+pub type Rule = &'static dyn Fn(&Grid, &Cell, &Cell) -> bool;
+pub const RULE_ROW: Rule = &|_grid, cell_1, cell_2| cell_1.row == cell_2.row;
+pub const RULE_COLUMN: Rule = &|_grid, cell_1, cell_2| cell_1.column == cell_2.column;
+pub const RULE_BLOCK: Rule = &|_grid, cell_1, cell_2| cell_1.block == cell_2.block;
+pub const RULE_KING: Rule = &|_grid, cell_1, cell_2| cell_1.row_distance(cell_2) <= 1 && cell_1.column_distance(cell_2) <= 1;
+pub const RULE_BISHOP: Rule = &|_grid, cell_1, cell_2| cell_1.row_distance(cell_2) == cell_1.column_distance(cell_2);
+pub const RULE_BISHOP_2: Rule = &|_grid, cell_1, cell_2| {
     let row_distance = cell_1.row_distance(cell_2);
     let column_distance = cell_1.column_distance(cell_2);
     row_distance == column_distance && row_distance <= 2
 };
-const RULE_DIAGONALS: Rule = &|grid, cell_1, cell_2| {
+pub const RULE_DIAGONALS: Rule = &|grid, cell_1, cell_2| {
     (cell_1.row == cell_1.column && cell_2.row == cell_2.column)
         || (cell_1.row == cell_1.column_from_end(grid) && cell_2.row == cell_2.column_from_end(grid))
 };
-const RULE_KNIGHT: Rule = &|_grid, cell_1, cell_2| {
+pub const RULE_KNIGHT: Rule = &|_grid, cell_1, cell_2| {
     let row_distance = cell_1.row_distance(cell_2);
     let column_distance = cell_1.column_distance(cell_2);
     (row_distance == 2 && column_distance == 1) || (row_distance == 1 && column_distance == 2)
 };
+// The rules that may be added on top of row, column, and block, each with a name to use for it
+// in reports.
+pub const EXTRA_RULES: [(&str, Rule); 4] = [("king", RULE_KING), ("knight", RULE_KNIGHT), ("bishop_2", RULE_BISHOP_2), ("diagonals", RULE_DIAGONALS)];
 
 pub fn main() {
     // try_build();
     // try_build_flat();
-    try_build_flat_9();
+    try_build_fixed();
     try_build_flat_usize();
-    // profile_build_flat_9();
+    // profile_build_fixed();
     // try_large_flat();
+    // try_progress_and_cancel();
+    // try_restarts();
+    // try_build_pattern();
 }
 
 fn try_build() {
@@ -140,13 +135,13 @@ fn try_build_flat() {
     }
 }
 
-fn try_build_flat_9() {
+fn try_build_fixed() {
     let size = 9;
     let limit_msec = 10_000;
     let repeat_count = 20;
 
     let mut builder = Builder::with_size(size).limit_milliseconds(limit_msec);
-    builder.strategy = BuildStrategy::Flat9;
+    builder.strategy = BuildStrategy::Fixed;
     for _ in 0..repeat_count {
         let result = builder.build();
         match result {
@@ -156,7 +151,7 @@ fn try_build_flat_9() {
             Err(message) => println!("{}", message),
         }
         //bg!(&builder);
-        println!("try_build_flat_9(): size = {}, {}", size, builder.build_runs.last().unwrap().runner.times_as_string());
+        println!("try_build_fixed(): size = {}, {}", size, builder.build_runs.last().unwrap().runner.times_as_string());
     }
 }
 
@@ -185,13 +180,7 @@ fn try_large_flat() {
 
     for grid_size in [9, 16, 25, 36, 49].iter() {
         let mut builder = Builder::with_size(*grid_size);
-        builder.strategy = match grid_size {
-            9 => BuildStrategy::Flat9,
-            16 => BuildStrategy::Flat16,
-            25 => BuildStrategy::Flat25,
-            49 => BuildStrategy::Flat49,
-            _ => panic!("Unexpected grid_size = {}", grid_size)
-        };
+        builder.strategy = BuildStrategy::Fixed;
         for repeat in 1..=repeat_count {
             let result = builder.build();
             match result {
@@ -209,23 +198,121 @@ fn try_large_flat() {
     }
 }
 
-fn profile_build_flat_9() {
+fn try_progress_and_cancel() {
+    let size = 36;
+    let progress_interval = 10_000;
+    let cancel_after_msec = 2_000;
+
+    let cancellation_token = CancellationToken::new();
+    let mut builder = Builder::with_size(size)
+        .cancellation_token(cancellation_token.clone())
+        .progress_callback(progress_interval, |progress| {
+            println!("try_progress_and_cancel(): {:?}", progress);
+        });
+    // A pattern build would finish before there was anything to cancel.
+    builder.strategy = BuildStrategy::NextCell;
+
+    // Cancel the build from another thread, as a GUI or batch job would.
+    let cancel_thread = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(cancel_after_msec));
+        cancellation_token.cancel();
+    });
+
+    match builder.build() {
+        Ok(grid) => grid.print_simple(""),
+        Err(message) => println!("{}", message),
+    }
+    println!("try_progress_and_cancel(): size = {}, node_count = {}, {}", size, builder.build_runs[0].runner.node_count, builder.build_runs[0].runner.times_as_string());
+    cancel_thread.join().unwrap();
+}
+
+fn try_restarts() {
+    // Compare 25x25 builds without restarts and with each restart policy.
+    let size = 25;
+    let limit_msec = 5_000;
+    let repeat_count = 10;
+    let restart_policies = [
+        RestartPolicy::Never,
+        RestartPolicy::FixedNodes { node_count: 5_000 },
+        RestartPolicy::Luby { unit_node_count: 1_000 },
+        RestartPolicy::Geometric { first_node_count: 1_000, factor: 1.5 },
+    ];
+
+    let mut report = BuildReport::new();
+    for restart_policy in restart_policies.iter() {
+        let mut builder = Builder::with_size(size).limit_milliseconds(limit_msec).restart_policy(restart_policy.clone());
+        builder.strategy = BuildStrategy::NextCell;
+        for _ in 0..repeat_count {
+            let _ = builder.build();
+        }
+        report.add(&format!("{:?}", restart_policy), builder.build_runs());
+    }
+    println!("try_restarts(): size = {}\n{}", size, report.to_table());
+}
+
+fn try_build_pattern() {
+    // The pattern for each size, then a 9x9 with the knight rule, which has to fall back to search.
+    for size in [9, 16, 25, 36].iter() {
+        let mut builder = Builder::with_size(*size);
+        builder.strategy = BuildStrategy::Pattern;
+        let grid = builder.build().unwrap();
+        grid.print_simple(&format!("try_build_pattern(): size = {}, {}", size, builder.build_runs[0].runner.times_as_string()));
+    }
+    let mut builder = Builder::with_size(9).rule(RULE_KNIGHT);
+    builder.strategy = BuildStrategy::Pattern;
+    let grid = builder.build().unwrap();
+    grid.print_simple(&format!("try_build_pattern(): knight, node_count = {}", builder.build_runs[0].runner.node_count));
+}
+
+fn profile_build_fixed() {
     let grid_size = 9;
     let mut builder = Builder::with_size(grid_size);
-    builder.strategy = BuildStrategy::Flat9;
+    builder.strategy = BuildStrategy::Fixed;
     let _result = builder.build();
 }
 
 #[derive(Debug, Clone)]
 pub enum BuildStrategy {
+    // Pattern when the dimensions and rules allow it, otherwise NextCell. This is the default.
+    // Pattern only reaches grids that are equivalent to its base grid, so set NextCell to draw
+    // from every grid.
+    Auto,
     NextCell,
     Flat,
     FlatUsize,
-    Flat9,
-    Flat16,
-    Flat25,
-    Flat36,
-    Flat49,
+    // Like FlatUsize but with fixed-size arrays, for grids of up to 4,096 cells and 64 values.
+    // Larger grids fall back to FlatUsize.
+    Fixed,
+    // Start from a grid laid out in a fixed pattern and shuffle it with random transformations that
+    // keep it valid: relabelling the values, reordering the rows within each band and the bands
+    // themselves, the same for columns and stacks, and transposing if the blocks are square. This
+    // takes no search at all, but it only works for a square grid with just the row, column, and
+    // block rules and no cell limit. For anything else build() uses NextCell instead, as Auto
+    // does.
+    // The transformations can only reach grids that are equivalent to the pattern, so for example
+    // 192 of the 288 possible 4x4 grids.
+    Pattern,
+}
+
+// When to give up on an attempt and start the build again from an empty grid. A search that fills
+// the first cells badly can spend a very long time under them before going back far enough to fix
+// the mistake, and a fresh start with different random choices is usually quicker. Each attempt
+// gets a budget of nodes and is abandoned when it runs out. The time limit and node limit still
+// apply to the build as a whole.
+// With BuildStrategy::NextCell the values for a cell are tried in random order instead of
+// ascending order, and partial grids that were searched all the way without success are remembered
+// in BuildRun::tried_grids so that later attempts skip them. The other strategies only start over.
+#[derive(Debug, Clone)]
+pub enum RestartPolicy {
+    Never,
+    // The same budget for every attempt. Unlike the others this never gives an attempt more room,
+    // so a budget too small for the grid can restart until the time limit.
+    FixedNodes { node_count: usize },
+    // Budgets of unit_node_count times 1, 1, 2, 1, 1, 2, 4, 1, 1, 2, ... This is within a
+    // logarithmic factor of the best fixed budget without having to know what that is.
+    Luby { unit_node_count: usize },
+    // Budgets of first_node_count, then multiplied by factor for each restart.
+    Geometric { first_node_count: usize, factor: f64 },
 }
 
 #[derive(Derivative)]
@@ -237,11 +324,18 @@ pub struct Builder {
     pub block_width: u8,
     pub block_height: u8,
     pub max_tried_grid_count: Option<usize>,
+    pub restart_policy: RestartPolicy,
     pub symbols: Vec<char>,
     pub time_limit: Option<Duration>,
     pub cell_limit: Option<u16>,
+    pub control: RunControl,
+    // Add rules with rule() rather than changing this directly, so that standard_rules_only stays
+    // right.
     #[derivative(Debug = "ignore")]
     pub related_cell_predicates: Vec<Rule>,
+    // True while the rules are the row, column, and block rules that new() starts with, so build()
+    // can lay out the related cells directly and use the pattern.
+    standard_rules_only: bool,
     #[derivative(Debug="ignore")]
     related_cell_indexes: Vec<u16>,
     #[derivative(Debug="ignore")]
@@ -262,10 +356,21 @@ pub struct BuildRun {
     pub tried_grid_registered_count: usize,
     pub tried_grid_skipped_count: usize,
     pub tried_grid_found_count: usize,
+    // The number of attempts abandoned under the restart policy.
+    pub restart_count: usize,
+    attempt_start_node_count: usize,
+    attempt_node_limit: Option<usize>,
+    restart_pending: bool,
+    // The number of dead ends, where the build had to go back, by how many cells were filled at
+    // the time. The index is the filled cell count.
     #[derivative(Debug="ignore")]
-    pub filled_cell_counts: Vec<u16>,
+    pub filled_cell_counts: Vec<usize>,
+    // Emptied at the end of the build, leaving only tried_grid_count.
     #[derivative(Debug="ignore")]
     pub tried_grids: HashSet<u64>,
+    pub tried_grid_count: usize,
+    // Only BuildStrategy::NextCell counts these. branch_sizes[n - 1] is the number of times a cell
+    // got as far as trying its nth value.
     pub branch_sizes: Vec<u32>,
     #[derivative(Debug="ignore")]
     pub grid: Option<Grid>,
@@ -293,6 +398,11 @@ struct FailedGrid {
 impl Builder {
 
     pub fn with_size(size: u8) -> Self {
+        // Like new(), the builder starts with the row, column, and block rules and
+        // BuildStrategy::Auto, so until a rule is added build() makes grids from the pattern. They
+        // are all equivalent to one base grid under relabelling, line swaps, and transposing, which
+        // is 192 of the 288 4x4 grids and a tiny fraction of 9x9 ones. Set the strategy to
+        // BuildStrategy::NextCell to search instead and reach every grid.
         let (block_width, block_height) = match size {
             1 | 2 | 3 => (1, 1),
             4 | 5 => (2, 2),
@@ -313,7 +423,8 @@ impl Builder {
             32 | 33 | 34 => (8, 4),
             35 => (7, 5),
             36 => (6, 6),
-            _ => panic!("Unexpected grid_size = {}", size)
+            // There's no block shape for this size, so build() will report invalid dimensions.
+            _ => (0, 0),
         };
         Self::new(size, size, block_width, block_height)
     }
@@ -329,6 +440,8 @@ impl Builder {
     }
 
     pub fn new(width: u8, height: u8, block_width: u8, block_height: u8) -> Self {
+        // Starts with the row, column, and block rules and BuildStrategy::Auto. See with_size()
+        // for what that means for the grids build() makes.
         /*
         let related_cell_predicates: Vec<Box<dyn Fn(&Cell, &Cell) -> bool>> = vec![
             Box::new(|cell_1, cell_2| cell_1.row == cell_2.row),
@@ -339,21 +452,24 @@ impl Builder {
         let related_cell_predicates= vec![RULE_ROW, RULE_COLUMN, RULE_BLOCK];
 
         let builder = Self {
-            strategy: BuildStrategy::NextCell,
+            strategy: BuildStrategy::Auto,
             width,
             height,
             block_width,
             block_height,
             max_tried_grid_count: None,
+            restart_policy: RestartPolicy::Never,
             symbols: vec![],
             time_limit: None,
             cell_limit: None,
+            control: RunControl::default(),
             related_cell_predicates,
+            standard_rules_only: true,
             related_cell_indexes: vec![],
             fixed_related_cell_indexes: vec![],
             build_runs: vec![],
         };
-        if VERBOSE >= 1 { dbg!(&builder); }
+        diagnostic!(Level::Trace, event = "builder_new"; "{:?}", builder);
         builder
     }
 
@@ -362,6 +478,11 @@ impl Builder {
         self
     }
 
+    pub fn restart_policy(mut self, restart_policy: RestartPolicy) -> Self {
+        self.restart_policy = restart_policy;
+        self
+    }
+
     pub fn symbols(mut self, symbols: &str) -> Self {
         self.symbols = gen_char_array(symbols);
         self
@@ -382,23 +503,51 @@ impl Builder {
         self
     }
 
+    pub fn cancellation_token(mut self, cancellation_token: CancellationToken) -> Self {
+        self.control.cancellation_token = Some(cancellation_token);
+        self
+    }
+
+    pub fn node_limit(mut self, node_limit: usize) -> Self {
+        self.control.node_limit = Some(node_limit);
+        self
+    }
+
+    pub fn progress_callback<F>(mut self, progress_interval: usize, callback: F) -> Self
+        where F: Fn(&Progress) + Send + Sync + 'static
+    {
+        self.control.set_progress_callback(progress_interval, callback);
+        self
+    }
+
+    pub fn diagnostics(mut self, diagnostics: Diagnostics) -> Self {
+        self.control.diagnostics = diagnostics;
+        self
+    }
+
     pub fn clear_rules(mut self) -> Self {
         self.related_cell_predicates.clear();
+        self.standard_rules_only = false;
         self
     }
 
     pub fn rule(mut self, rule: Rule) -> Self {
         self.related_cell_predicates.push(rule);
+        self.standard_rules_only = false;
         self
     }
 
-    pub fn build(&mut self) -> Result<Grid, String> {
+    pub fn build(&mut self) -> Result<Grid, SudokuError> {
+
+        let _diagnostics = self.control.diagnostics.enter();
+        self.validate()?;
+        diagnostic!(Level::Debug, event = "build_start", width = self.width, height = self.height, strategy:? = self.strategy, rule_count = self.related_cell_predicates.len(); "Starting a build.");
 
         let mut grid = Grid::new(self.width, self.height, self.block_width, self.block_height);
         self.set_up_related_cells(&mut grid);
 
         match self.strategy {
-            BuildStrategy::FlatUsize | BuildStrategy::Flat9 | BuildStrategy::Flat16 | BuildStrategy::Flat25 | BuildStrategy::Flat36 | BuildStrategy::Flat49 => {
+            BuildStrategy::FlatUsize | BuildStrategy::Fixed => {
                 if self.fixed_related_cell_indexes.is_empty() {
                     self.set_up_fixed_related_cell_indexes();
                 }
@@ -406,7 +555,9 @@ impl Builder {
             _ => {},
         }
 
-        let mut build_run = BuildRun::new(self.time_limit, grid.max_value);
+        let mut build_run = BuildRun::new(self.time_limit, grid.cell_count, grid.max_value);
+        build_run.runner.control = self.control.clone();
+        build_run.start_attempt(&self.restart_policy);
 
         if self.symbols.is_empty() {
             self.symbols = gen_char_array(if grid.max_value <= 9 {
@@ -418,7 +569,7 @@ impl Builder {
 
         match self.strategy {
             BuildStrategy::NextCell => {
-                self.build_next_cell(&mut build_run, &grid);
+                self.build_next_cell_with_restarts(&mut build_run, &grid);
             },
             BuildStrategy::Flat => {
                 self.build_flat(&mut build_run, &grid);
@@ -426,61 +577,137 @@ impl Builder {
             BuildStrategy::FlatUsize => {
                 self.build_flat_usize(&mut build_run, &grid);
             },
-            BuildStrategy::Flat9 => {
-                self.build_flat_9(&mut build_run, &grid);
+            BuildStrategy::Fixed => {
+                self.build_fixed_any(&mut build_run, &grid);
             },
-            BuildStrategy::Flat16 => {
-                self.build_flat_16(&mut build_run, &grid);
-            },
-            BuildStrategy::Flat25 => {
-                self.build_flat_25(&mut build_run, &grid);
-            },
-            BuildStrategy::Flat36 => {
-                self.build_flat_36(&mut build_run, &grid);
+            BuildStrategy::Auto => {
+                if self.pattern_fits(&grid) {
+                    self.build_pattern(&mut build_run, &grid);
+                } else {
+                    self.build_next_cell_with_restarts(&mut build_run, &grid);
+                }
             },
-            BuildStrategy::Flat49 => {
-                self.build_flat_49(&mut build_run, &grid);
+            BuildStrategy::Pattern => {
+                if self.pattern_fits(&grid) {
+                    self.build_pattern(&mut build_run, &grid);
+                } else {
+                    diagnostic!(Level::Debug, event = "build_pattern_fallback", width = self.width, height = self.height, rule_count = self.related_cell_predicates.len();
+                        "The pattern doesn't fit these dimensions and rules so searching with NextCell.");
+                    self.build_next_cell_with_restarts(&mut build_run, &grid);
+                }
             },
         }
 
         build_run.runner.mark_end();
-        self.build_runs.push(build_run.clone());
-        if build_run.runner.success.unwrap() {
-            let grid = build_run.grid.unwrap().clone();
+        // The hashes are only needed while building. Keeping them in build_runs would make a
+        // Builder that's used for many builds hold every build's set.
+        build_run.tried_grid_count = build_run.tried_grids.len();
+        build_run.tried_grids = HashSet::new();
+        diagnostic!(Level::Info, event = "build_end", width = self.width, height = self.height, strategy:? = self.strategy, success = build_run.runner.success == Some(true),
+            node_count = build_run.runner.node_count, elapsed_ms = build_run.runner.elapsed().as_millis() as u64; "Finished a build.");
+        let result = if build_run.runner.success == Some(true) {
+            let grid = build_run.grid.clone().unwrap();
             if SHOW_ELAPSED_TIME { dbg!(build_run.runner.time); }
-            grid.invariant();
+            if diagnostics::checks(Checkpoint::BuildEnd) { grid.invariant(); }
             Ok(grid)
         } else {
-            Err(build_run.runner.failure_message.unwrap().clone())
+            Err(build_run.runner.failure_clone())
+        };
+        self.build_runs.push(build_run);
+        result
+
+    }
+
+    fn validate(&self) -> Result<(), SudokuError> {
+        // Check the dimensions and symbols before building anything so that a bad configuration
+        // comes back as an error rather than a panic somewhere inside the build.
+        let invalid_dimensions = |reason: &str| SudokuError::InvalidDimensions {
+            width: self.width,
+            height: self.height,
+            block_width: self.block_width,
+            block_height: self.block_height,
+            reason: reason.to_string(),
+        };
+        if self.width == 0 || self.height == 0 {
+            return Err(invalid_dimensions("The grid width and height must be at least 1."));
+        }
+        if self.block_width == 0 || self.block_height == 0 {
+            return Err(invalid_dimensions("The block width and height must be at least 1."));
+        }
+        if !self.width.is_multiple_of(self.block_width) || !self.height.is_multiple_of(self.block_height) {
+            return Err(invalid_dimensions("The blocks must divide the grid evenly."));
+        }
+        let block_cell_count = self.block_width as usize * self.block_height as usize;
+        if block_cell_count > u8::MAX as usize {
+            return Err(invalid_dimensions("The blocks have too many cells."));
+        }
+        let required_count = *[self.width as usize, self.height as usize, block_cell_count].iter().max().unwrap();
+
+        // If no symbols were given, build() will pick the standard or extended set.
+        let symbols = if self.symbols.is_empty() {
+            gen_char_array(if required_count <= 9 { SYMBOLS_STANDARD } else { SYMBOLS_EXTENDED })
+        } else {
+            self.symbols.clone()
+        };
+        if symbols.len() < required_count {
+            return Err(SudokuError::InvalidSymbols { symbol_count: symbols.len(), required_count, duplicate: None });
+        }
+        let mut seen = HashSet::new();
+        for symbol in symbols.iter() {
+            if !seen.insert(*symbol) {
+                return Err(SudokuError::InvalidSymbols { symbol_count: symbols.len(), required_count, duplicate: Some(*symbol) });
+            }
         }
+        Ok(())
+    }
 
+    fn build_next_cell_with_restarts(&self, build_run: &mut BuildRun, grid: &Grid) {
+        loop {
+            self.build_next_cell(build_run, grid, 0);
+            if !build_run.restart_pending {
+                break;
+            }
+            build_run.restart(&self.restart_policy);
+        }
     }
 
-    fn build_next_cell(&self, build_run: &mut BuildRun, grid_to_now: &Grid) {
+    fn build_next_cell(&self, build_run: &mut BuildRun, grid_to_now: &Grid, depth: u16) {
         build_run.fill_next_cell_count += 1;
-        if !build_run.runner.check_continue() {
+        let filled_cell_count = grid_to_now.solved_cell_count();
+        build_run.runner.record_best(filled_cell_count as usize, false);
+        if !build_run.runner.count_node(depth, filled_cell_count) {
+            return;
+        }
+        if !build_run.check_continue() {
             //rintln!("build_next_cell: return a");
             return;
         }
-
-        if VERBOSE >= 1 {
-            let label = "build_next_cell() top";
-            grid_to_now.print_simple_and_remaining(label);
+        let learn = !matches!(self.restart_policy, RestartPolicy::Never);
+        if learn && build_run.tried_grids.contains(&Self::hash_values(&grid_to_now.values)) {
+            // An earlier attempt searched everything under this partial grid and found nothing.
+            build_run.tried_grid_found_count += 1;
+            return;
         }
 
+        diagnostic!(Level::Trace, event = "build_next_cell", depth = depth, unsolved_cell_count = grid_to_now.unsolved_cell_count,
+            remaining_value_count = grid_to_now.remaining_value_count; "build_next_cell() top");
+
         let try_cell_index = Self::choose_try_cell_index(grid_to_now);
 
         let mut try_values = grid_to_now.remaining_values(try_cell_index);
         //bg!(try_cell_index, &try_values);
         let mut branch_size = 0u8;
         while !try_values.is_empty() {
-            // let try_value = try_values.remove(thread_rng().gen_range(0, try_values.len()));
-            let try_value = try_values.remove(0);
+            let try_value = if learn {
+                try_values.remove(rng().gen_range(0, try_values.len()))
+            } else {
+                try_values.remove(0)
+            };
             branch_size += 1;
             build_run.branch_sizes[branch_size as usize - 1] += 1;
 
             build_run.try_value_count += 1;
-            if !build_run.runner.check_continue() {
+            if !build_run.check_continue() {
                 //rintln!("build_next_cell: return b");
                 return;
             }
@@ -491,7 +718,7 @@ impl Builder {
             let mut try_grid = grid_to_now.clone();
             //rintln!("\tOption is {} with value {}", &grid_to_now.cell(option.0), option.1);
             let set_value_ok = self.set_value(build_run, &mut try_grid, try_cell_index, try_value);
-            if !build_run.runner.check_continue() {
+            if !build_run.check_continue() {
                 // Most likely the last cell was filled in during the call to set_value().
                 //rintln!("build_next_cell: return c");
                 return;
@@ -499,8 +726,8 @@ impl Builder {
             if set_value_ok {
                 // This option worked so keep going.
                 //rintln!("\t\tOption worked, continuing.");
-                self.build_next_cell(build_run, &try_grid);
-                if !build_run.runner.check_continue() {
+                self.build_next_cell(build_run, &try_grid, depth + 1);
+                if !build_run.check_continue() {
                     // Most likely the last cell was filled in during the call to build_next_cell().
                     //rintln!("build_next_cell: return d");
                     return;
@@ -511,6 +738,30 @@ impl Builder {
         // Given grid_to_now as the starting point, we tried all possible values in all remaining
         // cells and nothing worked, so fall back to an earlier version of the grid.
         //rintln!("build_next_cell: return e");
+        build_run.record_dead_end(filled_cell_count);
+        if learn {
+            self.register_failed_grid(build_run, grid_to_now);
+        }
+    }
+
+    fn register_failed_grid(&self, build_run: &mut BuildRun, grid: &Grid) {
+        // Remember a partial grid with nothing under it so that a later attempt can skip it. Filling
+        // in the cells with one remaining value depends only on the grid, so the same partial grid
+        // always leads to the same dead end.
+        if let Some(max_tried_grid_count) = self.max_tried_grid_count {
+            if build_run.tried_grids.len() >= max_tried_grid_count {
+                build_run.tried_grid_skipped_count += 1;
+                return;
+            }
+        }
+        build_run.tried_grid_registered_count += 1;
+        build_run.tried_grids.insert(Self::hash_values(&grid.values));
+    }
+
+    fn hash_values(values: &[u8]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        values.hash(&mut hasher);
+        hasher.finish()
     }
 
     fn register_attempt(&self, build_run: &mut BuildRun, grid: &Grid, try_cell_index: u16, try_value: u8) -> bool {
@@ -563,10 +814,7 @@ impl Builder {
             return false;
         }
 
-        if VERBOSE >= 1 {
-            let label = format!("\nset_value() top: index = {}, value = {}, {}", index, value, grid.cell_display(index));
-            grid.debug_cell_and_related(&label, index);
-        }
+        diagnostic!(Level::Trace, event = "set_value", index = index, value = value; "set_value() top: {}", grid.cell_display(index));
 
         assert!(grid.values[index as usize] == NO_VALUE);
         assert!(value > 0);
@@ -576,12 +824,7 @@ impl Builder {
         // let label = format!("\nset_value() after setting value: index = {}, value = {}, {}", index, value, self.cell_display(index));
         // self.debug_cell_and_related(&label, index);
 
-        for remaining_value_index in grid.remaining_value_range(index) {
-            if grid.remaining_values[remaining_value_index] {
-                grid.remaining_values.set(remaining_value_index, false);
-                grid.remaining_value_count -= 1;
-            }
-        }
+        grid.remaining_value_count -= grid.remaining_values.clear_cell(index);
         grid.remaining_value_counts[index as usize] = 0;
         grid.unsolved_cell_count -= 1;
 
@@ -628,7 +871,7 @@ impl Builder {
                 }
             }
 
-            if RUN_INVARIANT { grid.invariant_for_builder(&self.symbols, &self.related_cell_indexes); }
+            if diagnostics::checks(Checkpoint::BuildStep) { grid.invariant_for_builder(&self.symbols, &self.related_cell_indexes); }
 
             for related_cell_index in one_value_indexes {
                 // It's possible that between the code a few lines above and now this cell has been
@@ -647,7 +890,7 @@ impl Builder {
             }
         }
 
-        if RUN_INVARIANT { grid.invariant_for_builder(&self.symbols, &self.related_cell_indexes); }
+        if diagnostics::checks(Checkpoint::BuildStep) { grid.invariant_for_builder(&self.symbols, &self.related_cell_indexes); }
 
         if grid.unsolved_cell_count == 0 || reached_cell_limit {
             //rintln!("\t\tOption worked, grid is completed.");
@@ -672,10 +915,7 @@ impl Builder {
         // value.
         debug_assert_eq!(1, grid.remaining_value_counts[index as usize]);
 
-        if VERBOSE >= 2 {
-            let label = format!("set_one_remaining_value(): index = {}, {}", index, grid.cell_display(index));
-            println!("{}", label);
-        }
+        diagnostic!(Level::Trace, event = "set_one_remaining_value", index = index; "set_one_remaining_value(): {}", grid.cell_display(index));
         // self.debug_cell_and_related(&label, index);
 
         let value = grid.one_remaining_value(index);
@@ -686,7 +926,7 @@ impl Builder {
     fn choose_try_cell_index(grid: &Grid) -> u16 {
         let min = *grid.remaining_value_counts.iter().filter(|count| **count > 0).min().unwrap();
         let found_count = grid.remaining_value_counts.iter().filter(|count| **count == min).count();
-        let random_index = thread_rng().gen_range(0, found_count);
+        let random_index = rng().gen_range(0, found_count);
         //rintln!("choose_try_cell_index(): min = {}, found_count = {}, random_index = {}", min, found_count, random_index);
 
         let mut i = 0;
@@ -709,6 +949,10 @@ impl Builder {
     }
 
     fn set_up_related_cells(&mut self, grid: &mut Grid) {
+        if self.uses_standard_rules() {
+            self.set_up_standard_related_cells(grid);
+            return;
+        }
         let mut cells = Vec::with_capacity(grid.cell_count as usize);
         for index in 0..grid.cell_count {
             cells.push(Self::index_to_cell(grid, index));
@@ -766,11 +1010,49 @@ impl Builder {
                 // done.
                 self.related_cell_indexes.push(index as u16);
             }
-            if VERBOSE >= 2 { println!("Grid::new() bottom of related cell loop: index = {}, related_cell_indexes = [{}]", index, self.related_cell_indexes.iter().join(", ")); }
+            diagnostic!(Level::Trace, event = "related_cells", index = index; "related_cell_indexes = [{}]", self.related_cell_indexes.iter().join(", "));
         }
         debug_assert_eq!(related_cell_total, self.related_cell_indexes.len());
     }
 
+    fn uses_standard_rules(&self) -> bool {
+        // True if the rules are still the three that new() set up. Checking the count as well
+        // catches a rule pushed onto related_cell_predicates directly.
+        self.standard_rules_only && self.related_cell_predicates.len() == 3
+    }
+
+    fn set_up_standard_related_cells(&mut self, grid: &mut Grid) {
+        // The same result as set_up_related_cells() for the standard rules, but listing each cell's
+        // row, column, and block directly rather than testing it against every other cell. The
+        // blocks divide the grid evenly so every cell has the same number of related cells.
+        let width = grid.width as u16;
+        let height = grid.height as u16;
+        let block_width = grid.block_width as u16;
+        let block_height = grid.block_height as u16;
+        let related_cell_count = (width - 1) + (height - 1) + (block_width * block_height - 1) - (block_width - 1) - (block_height - 1);
+        grid.max_related_cell_count = related_cell_count as u8;
+
+        self.related_cell_indexes = Vec::with_capacity(grid.cell_count as usize * related_cell_count as usize);
+        for index in 0..grid.cell_count {
+            // Go down the rows in order so the list comes out sorted, as it does from the general
+            // setup. The cell's own row gives the whole row, the rest of its band gives the block,
+            // and every other row gives just the cell in this column.
+            let (row, column) = (index / width, index % width);
+            let (band_top, block_left) = (row - row % block_height, column - column % block_width);
+            for other_row in 0..height {
+                let row_start = other_row * width;
+                if other_row == row {
+                    self.related_cell_indexes.extend((row_start..row_start + width).filter(|related_index| *related_index != index));
+                } else if other_row >= band_top && other_row < band_top + block_height {
+                    self.related_cell_indexes.extend(row_start + block_left..row_start + block_left + block_width);
+                } else {
+                    self.related_cell_indexes.push(row_start + column);
+                }
+            }
+        }
+        debug_assert_eq!(grid.cell_count as usize * related_cell_count as usize, self.related_cell_indexes.len());
+    }
+
     fn complete_grid_post_build(&self, grid: &Grid) -> Grid {
         // Don't run the invariant because we might be calling this function so we can print out the
         // grid and find a problem.
@@ -860,12 +1142,28 @@ impl Builder {
         // we tried by removing one remaining value (unless we removed it while choosing it) and try
         // another one. If there are no choices remaining, decrement current_cell_index.
 
+        if cell_count == 1 {
+            // The only cell already has its value.
+            build_run.runner.success = Some(true);
+            build_run.grid = Some(self.complete_grid_post_build_with_values_u8(grid, &values));
+            return;
+        }
+
         let loop_start_time = Instant::now();
 
         let mut prev_cell_index = 0;
         let mut current_cell_index = 1;
         'main_loop: loop {
 
+            if !build_run.runner.count_node(current_cell_index as u16, current_cell_index as u16) {
+                // The build was cancelled or ran out of nodes.
+                return;
+            }
+            if build_run.attempt_over() {
+                (prev_cell_index, current_cell_index) = build_run.restart_flat(&self.restart_policy);
+                continue;
+            }
+
             /*
             {
                 // Everything in this block is only for debugging.
@@ -894,6 +1192,7 @@ impl Builder {
                     // There are no remaining values for this cell, so this branch is not going to work.
                     //rintln!("build_flat(): There are no remaining values for this cell, so this branch is not going to work.");
                     prev_cell_index = current_cell_index;
+                    build_run.record_dead_end(current_cell_index as u16);
                     current_cell_index -= 1;
                 } else {
                     remaining_values[current_cell_index] = (1..=value_count as u8)
@@ -911,7 +1210,7 @@ impl Builder {
                     let remaining_value_index = if remaining_value_count == 1 {
                         0
                     } else {
-                        thread_rng().gen_range(0, remaining_value_count)
+                        rng().gen_range(0, remaining_value_count)
                     };
                     let value = remaining_values[current_cell_index].remove(remaining_value_index);
                     //rintln!("build_flat(): Setting value {} at index {}.", value, current_cell_index);
@@ -935,9 +1234,14 @@ impl Builder {
                     continue 'main_loop;
                 }
                 // We've reached the end of the possible values and nothing has worked so go down
-                // a level.
+                // a level. The first cell is always 1, so if we get back to it there's no grid
+                // with these rules.
                 //rintln!("build_flat(): We've reached the end of the possible values and nothing has worked so go down a level.");
+                if current_cell_index == 0 {
+                    return;
+                }
                 prev_cell_index = current_cell_index;
+                build_run.record_dead_end(current_cell_index as u16);
                 current_cell_index -= 1;
             }
         }
@@ -958,7 +1262,7 @@ impl Builder {
     }
 
     fn build_flat_usize(&self, build_run: &mut BuildRun, grid: &Grid) {
-        // This is similar to build_flat_9 in that it avoids allocating memory and converting
+        // This is similar to build_fixed in that it avoids allocating memory and converting
         // number types, but it allaws grids af any size.
 
         let value_count= grid.max_value as usize;
@@ -995,12 +1299,28 @@ impl Builder {
         // we tried by removing one remaining value (unless we removed it while choosing it) and try
         // another one. If there are no choices remaining, decrement current_cell_index.
 
+        if cell_count == 1 {
+            // The only cell already has its value.
+            build_run.runner.success = Some(true);
+            build_run.grid = Some(self.complete_grid_post_build_with_values_usize(grid, &values));
+            return;
+        }
+
         let loop_start_time = Instant::now();
 
         let mut prev_cell_index = 0;
         let mut current_cell_index = 1;
         'main_loop: loop {
 
+            if !build_run.runner.count_node(current_cell_index as u16, current_cell_index as u16) {
+                // The build was cancelled or ran out of nodes.
+                return;
+            }
+            if build_run.attempt_over() {
+                (prev_cell_index, current_cell_index) = build_run.restart_flat(&self.restart_policy);
+                continue;
+            }
+
             /*
             {
                 // Everything in this block is only for debugging.
@@ -1048,6 +1368,7 @@ impl Builder {
                     // There are no remaining values for this cell, so this branch is not going to work.
                     //rintln!("build_flat_9(): There are no remaining values for this cell, so this branch is not going to work.");
                     prev_cell_index = current_cell_index;
+                    build_run.record_dead_end(current_cell_index as u16);
                     current_cell_index -= 1;
                 } else {
                     //rintln!("build_flat_9(): There are remaining values so stay at this level.");
@@ -1061,15 +1382,20 @@ impl Builder {
                 let remaining_value_count = remaining_value_counts[current_cell_index];
                 if remaining_value_count == 0 {
                     // There are no remaining values for this cell, so this branch is not going to work.
+                    // As in build_flat(), getting back to the first cell means there's no grid.
                     //rintln!("build_flat_9(): There are no remaining values for this cell, so this branch is not going to work.");
+                    if current_cell_index == 0 {
+                        return;
+                    }
                     prev_cell_index = current_cell_index;
+                    build_run.record_dead_end(current_cell_index as u16);
                     current_cell_index -= 1;
                     continue 'main_loop;
                 }
                 let remaining_value_offset = if remaining_value_count == 1 {
                     0
                 } else {
-                    thread_rng().gen_range(0, remaining_value_count)
+                    rng().gen_range(0, remaining_value_count)
                 };
                 let mut value = NO_VALUE_USIZE;
                 let remaining_values_start_index = current_cell_index * value_count;
@@ -1115,756 +1441,204 @@ impl Builder {
         }
     }
 
-    fn build_flat_9(&self, build_run: &mut BuildRun, grid: &Grid) {
-        // This is a specialized version of build_flat() that's only for 9x9 grids and that uses
-        // fixed-size arrays as much as possible and avoids number conversions. It also takes in
-        // some of the setup data rather than working it out each time, so it's better for repeated
-        // calls that involve a 9x9 grid with the same rules.
+    fn build_fixed_any(&self, build_run: &mut BuildRun, grid: &Grid) {
+        // Pick the smallest cell capacity that fits the grid. Only these capacities are compiled,
+        // so a new grid size needs nothing new as long as it fits in one of them. Grids that are
+        // too big for the engine go to build_flat_usize() instead.
+        let cell_count = grid.cell_count as usize;
+        if grid.max_value as usize > FIXED_MAX_VALUE_COUNT || cell_count > FIXED_CELL_CAPACITY_MAX {
+            self.build_flat_usize(build_run, grid);
+        } else if cell_count <= FIXED_CELL_CAPACITY_SMALL {
+            self.build_fixed::<FIXED_CELL_CAPACITY_SMALL>(build_run, grid);
+        } else if cell_count <= FIXED_CELL_CAPACITY_MEDIUM {
+            self.build_fixed::<FIXED_CELL_CAPACITY_MEDIUM>(build_run, grid);
+        } else if cell_count <= FIXED_CELL_CAPACITY_LARGE {
+            self.build_fixed::<FIXED_CELL_CAPACITY_LARGE>(build_run, grid);
+        } else {
+            self.build_fixed::<FIXED_CELL_CAPACITY_MAX>(build_run, grid);
+        }
+    }
+
+    fn build_fixed<const CELL_CAPACITY: usize>(&self, build_run: &mut BuildRun, grid: &Grid) {
+        // This replaces the old build_flat_9() through build_flat_49(), which were copies of the
+        // same loop with different array sizes. It works the same way as build_flat_usize() but
+        // keeps everything in fixed-size arrays on the stack, and each cell's remaining values are
+        // a bitmask with bit v - 1 set if value v is still possible. The arrays are sized for
+        // CELL_CAPACITY cells and only the first cell_count are used, so one engine covers any
+        // grid size, block shape, and set of rules.
 
+        let value_count = grid.max_value as usize;
+        let cell_count = grid.cell_count as usize;
         let max_related_cell_count = grid.max_related_cell_count as usize;
-        // debug_assert!(max_related_cell_count <= GRID_9_MAX_MAX_RELATED_CELL_COUNT);
-
-        // related_cell_indexes = self.fixed_9_related_cell_indexes.unwrap();
-        //bg!(&related_cell_indexes);
+        debug_assert!(cell_count <= CELL_CAPACITY);
+        debug_assert!(value_count <= FIXED_MAX_VALUE_COUNT);
 
         let setup_start_time = Instant::now();
 
-        let mut values = [NO_VALUE_USIZE; GRID_9_CELL_COUNT];
+        let mut values = [NO_VALUE_USIZE; CELL_CAPACITY];
         values[0] = 1;
-
-        let mut remaining_values = [false; GRID_9_REMANING_VALUE_LIST_SIZE];
-        let mut remaining_value_counts = [0; GRID_9_CELL_COUNT];
+        let mut remaining_values = [0u64; CELL_CAPACITY];
+        let all_values = if value_count == FIXED_MAX_VALUE_COUNT { u64::MAX } else { (1u64 << value_count) - 1 };
 
         build_run.runner.setup_time = Some(Instant::now() - setup_start_time);
 
-        // Hold a collection of remaining values for each cell throughout the loop. When going up to
-        // some current_cell_index, recalculate the remaining values for that new cell. When going
-        // down, don't recalculate since the previous cells haven't changed. Instead mark the path
-        // we tried by removing one remaining value (unless we removed it while choosing it) and try
-        // another one. If there are no choices remaining, decrement current_cell_index.
-
         let loop_start_time = Instant::now();
 
+        // As in build_flat_usize(), when going up to a new current_cell_index work out the remaining
+        // values for that cell from the earlier cells it's related to. When going down, take the
+        // value we just tried out of the remaining values and try another. If there's nothing left
+        // to try, go down again.
         let mut prev_cell_index = 0;
         let mut current_cell_index = 1;
-        'main_loop: loop {
+        while current_cell_index < cell_count {
 
-            /*
-            {
-                // Everything in this block is only for debugging.
-                let direction = if current_cell_index > prev_cell_index { "UP" } else if current_cell_index == prev_cell_index { "SAME" } else { "DOWN" };
-                println!("\nprint_flat_9() top of loop: {}: current = {}, prev = {}", direction, current_cell_index, prev_cell_index);
-                let mut debug_values = values.clone();
-                for i in current_cell_index..GRID_9_CELL_COUNT {
-                    debug_values[i] = NO_VALUE_USIZE;
-                }
-                let debug_grid = self.complete_grid_post_build_with_values_usize(grid, &debug_values);
-                debug_grid.print_simple_and_remaining_counts("");
+            if !build_run.runner.count_node(current_cell_index as u16, current_cell_index as u16) {
+                // The build was cancelled or ran out of nodes.
+                return;
+            }
+            if build_run.attempt_over() {
+                (prev_cell_index, current_cell_index) = build_run.restart_flat(&self.restart_policy);
+                continue;
+            }
+            // Unlike count_node() this looks at the clock, so only call it now and then.
+            if build_run.runner.node_count.is_multiple_of(FIXED_CLOCK_CHECK_INTERVAL) && !build_run.runner.check_continue() {
+                return;
             }
-            */
 
             if current_cell_index > prev_cell_index {
-                //rintln!("build_flat(): The current cell index is higher so we need to determine the available values.");
-                // Find the available values for the current cell.
-                let remaining_values_start_index = current_cell_index * GRID_9_VALUE_COUNT;
-                for i in remaining_values_start_index..(remaining_values_start_index + GRID_9_VALUE_COUNT) {
-
-                    if i >= remaining_values.len() {
-                        //bg!(current_cell_index, GRID_9_VALUE_COUNT, remaining_values_start_index, i);
-                    }
-
-                    remaining_values[i] = true;
-                }
-                let mut remaining_value_count = GRID_9_VALUE_COUNT;
+                // Every earlier cell has a value, and the related cell indexes for each cell are in
+                // ascending order so we can stop at the first one that isn't earlier.
+                let mut remaining = all_values;
                 let related_cell_lookup_start_index = current_cell_index * max_related_cell_count;
-                'related_indexes: for i in related_cell_lookup_start_index..(related_cell_lookup_start_index + max_related_cell_count) {
-                    let related_cell_index = self.fixed_related_cell_indexes[i];
-                    if related_cell_index < current_cell_index {
-                        let found_value = values[related_cell_index];
-                        if found_value != NO_VALUE_USIZE {
-                            let remaining_values_index = (remaining_values_start_index + found_value) - 1;
-                            if remaining_values[remaining_values_index] {
-                                remaining_values[remaining_values_index] = false;
-                                remaining_value_count -= 1;
-                            }
-                        }
-                    } else {
-                        break 'related_indexes;
+                for related_cell_index in self.fixed_related_cell_indexes[related_cell_lookup_start_index..related_cell_lookup_start_index + max_related_cell_count].iter() {
+                    if *related_cell_index >= current_cell_index {
+                        break;
                     }
+                    remaining &= !(1u64 << (values[*related_cell_index] - 1));
                 }
-                if remaining_value_count == 0 {
-                    // There are no remaining values for this cell, so this branch is not going to work.
-                    //rintln!("build_flat_9(): There are no remaining values for this cell, so this branch is not going to work.");
-                    prev_cell_index = current_cell_index;
-                    current_cell_index -= 1;
-                } else {
-                    //rintln!("build_flat_9(): There are remaining values so stay at this level.");
-                    remaining_value_counts[current_cell_index] = remaining_value_count;
-                    prev_cell_index = current_cell_index;
-                }
-                continue 'main_loop;
-            } else {
-                //rintln!("build_flat_9(): The current cell index is the same or lower so we need to keep going at the current level.");
-                //bg!(&remaining_values[current_cell_index]);
-                let remaining_value_count = remaining_value_counts[current_cell_index];
-                if remaining_value_count == 0 {
-                    // There are no remaining values for this cell, so this branch is not going to work.
-                    //rintln!("build_flat_9(): There are no remaining values for this cell, so this branch is not going to work.");
-                    prev_cell_index = current_cell_index;
+                remaining_values[current_cell_index] = remaining;
+                prev_cell_index = current_cell_index;
+                if remaining == 0 {
+                    build_run.record_dead_end(current_cell_index as u16);
                     current_cell_index -= 1;
-                    continue 'main_loop;
-                }
-                let remaining_value_offset = if remaining_value_count == 1 {
-                    0
-                } else {
-                    thread_rng().gen_range(0, remaining_value_count)
-                };
-                let mut value = NO_VALUE_USIZE;
-                let remaining_values_start_index = current_cell_index * GRID_9_VALUE_COUNT;
-                let mut remaining_values_used_index = std::usize::MAX;
-                let mut found_count = 0;
-                'find_value: for i in remaining_values_start_index..(remaining_values_start_index + GRID_9_VALUE_COUNT) {
-                    if remaining_values[i] {
-                        // This is a legal remaining value entry for the current cell.
-                        if found_count == remaining_value_offset {
-                            value = (i - remaining_values_start_index) + 1;
-                            remaining_values_used_index = i;
-                            break 'find_value;
-                        } else {
-                            found_count += 1;
-                        }
-                    }
                 }
-                debug_assert!(value != NO_VALUE_USIZE);
-                debug_assert!(value <= GRID_9_VALUE_COUNT);
-                values[current_cell_index] = value;
-                if current_cell_index == GRID_9_CELL_COUNT - 1 {
-                    // We've set the last value. The grid is complete. For now just show the
-                    // values but really we need to create a real grid.
-
-                    build_run.runner.loop_time = Some(Instant::now() - loop_start_time);
-
-                    build_run.runner.success = Some(true);
-
-                    let start_time = Instant::now();
-                    build_run.grid = Some(self.complete_grid_post_build_with_values_usize(grid, &values));
-                    build_run.runner.return_object_time = Some(Instant::now() - start_time);
+                continue;
+            }
 
+            let remaining = remaining_values[current_cell_index];
+            if remaining == 0 {
+                // Nothing left to try in this cell, so this branch is not going to work. The first
+                // cell is always 1, so if we get back to it there's no grid with these rules.
+                if current_cell_index == 0 {
                     return;
                 }
-                // Mark off the value we just set for the current cell so that we don't try it
-                // again.
-                remaining_values[remaining_values_used_index] = false;
-                remaining_value_counts[current_cell_index] -= 1;
                 prev_cell_index = current_cell_index;
-                current_cell_index += 1;
-                continue 'main_loop;
+                build_run.record_dead_end(current_cell_index as u16);
+                current_cell_index -= 1;
+                continue;
+            }
+            let remaining_value_count = remaining.count_ones();
+            let remaining_value_offset = if remaining_value_count == 1 {
+                0
+            } else {
+                rng().gen_range(0, remaining_value_count)
+            };
+            // Clear the lowest set bits until the one we want is the lowest.
+            let mut bits = remaining;
+            for _ in 0..remaining_value_offset {
+                bits &= bits - 1;
             }
+            let bit = bits.trailing_zeros();
+            values[current_cell_index] = bit as usize + 1;
+            // Mark off the value we just set so that we don't try it again.
+            remaining_values[current_cell_index] = remaining & !(1u64 << bit);
+            prev_cell_index = current_cell_index;
+            current_cell_index += 1;
         }
+
+        // Every cell has a value so the grid is complete.
+        build_run.runner.loop_time = Some(Instant::now() - loop_start_time);
+        build_run.runner.success = Some(true);
+        let start_time = Instant::now();
+        build_run.grid = Some(self.complete_grid_post_build_with_values_usize(grid, &values[..cell_count]));
+        build_run.runner.return_object_time = Some(Instant::now() - start_time);
     }
 
-    fn build_flat_16(&self, build_run: &mut BuildRun, grid: &Grid) {
-        // This is a specialized version of build_flat() that's only for 9x9 grids and that uses
-        // fixed-size arrays as much as possible and avoids number conversions. It also takes in
-        // some of the setup data rather than working it out each time, so it's better for repeated
-        // calls that involve a 9x9 grid with the same rules.
+    fn pattern_fits(&self, grid: &Grid) -> bool {
+        // True if the grid is square with one value per row and the related cells worked out from
+        // the rules are exactly those in the same row, column, or block. The standard rules are
+        // recognised directly. Otherwise compare what the rules do rather than which rules were
+        // given, so an extra rule that relates nothing new is fine.
+        if self.cell_limit.is_some() || grid.width != grid.height || grid.max_value != grid.width
+            || grid.width as usize != grid.block_width as usize * grid.block_height as usize {
+            return false;
+        }
+        if self.uses_standard_rules() {
+            return true;
+        }
+        let standard_related_cell_count = (grid.width as usize - 1) * 2 + (grid.block_width as usize - 1) * (grid.block_height as usize - 1);
+        if grid.max_related_cell_count as usize != standard_related_cell_count {
+            return false;
+        }
+        (0..grid.cell_count).all(|index| {
+            let (row, column, block) = grid.row_col_block(index);
+            let related_cell_indexes = self.index_to_related_cell_indexes(grid, index);
+            related_cell_indexes.len() == standard_related_cell_count
+                && related_cell_indexes.iter().all(|related_cell_index| {
+                    let (related_row, related_column, related_block) = grid.row_col_block(*related_cell_index);
+                    related_row == row || related_column == column || related_block == block
+                })
+        })
+    }
 
-        let max_related_cell_count = grid.max_related_cell_count as usize;
-        // debug_assert!(max_related_cell_count <= GRID_16_MAX_MAX_RELATED_CELL_COUNT);
+    fn build_pattern(&self, build_run: &mut BuildRun, grid: &Grid) {
+        // In the pattern, row r is the first row shifted left by (r % block_height) * block_width +
+        // r / block_height. Within a band the shifts step by a block width so the blocks get every
+        // value, and down a column they cover every shift once. Then apply a random transform,
+        // reading each cell's value from the pattern at its source cell.
+        let loop_start_time = Instant::now();
 
-        // related_cell_indexes = self.fixed_16_related_cell_indexes.unwrap();
-        //bg!(&related_cell_indexes);
+        let size = grid.width as u16;
+        let block_width = grid.block_width as u16;
+        let block_height = grid.block_height as u16;
+        let pattern_value = |index: u16| {
+            let (row, column) = (index / size, index % size);
+            (((row % block_height) * block_width + row / block_height + column) % size) as u8 + 1
+        };
 
-        let setup_start_time = Instant::now();
+        let mut relabel = (1..=grid.max_value).collect::<Vec<_>>();
+        relabel.shuffle(&mut rng());
+        relabel.insert(0, NO_VALUE);
+        let transform = GridTransform {
+            transpose: block_width == block_height && rng().gen(),
+            row_order: transform::random_band_preserving_order(grid.height, grid.block_height),
+            column_order: transform::random_band_preserving_order(grid.width, grid.block_width),
+            relabel,
+        };
+        let values = (0..grid.cell_count)
+            .map(|index| transform.relabel[pattern_value(transform.source_index(grid, index)) as usize])
+            .collect::<Vec<_>>();
+        build_run.runner.count_node(0, grid.cell_count);
+        build_run.runner.loop_time = Some(Instant::now() - loop_start_time);
+
+        build_run.runner.success = Some(true);
+        let start_time = Instant::now();
+        build_run.grid = Some(self.complete_grid_post_build_with_values_u8(grid, &values));
+        build_run.runner.return_object_time = Some(Instant::now() - start_time);
+    }
 
-        let mut values = [NO_VALUE_USIZE; GRID_16_CELL_COUNT];
-        values[0] = 1;
+    fn debug_print_related_cell_indexes_flat(related_cell_indexes: &Vec<Vec<usize>>) {
+        println!("debug_print_related_cell_indexes_flat():");
+        for (index, v) in related_cell_indexes.iter().enumerate() {
+            let related_indexes = v.iter().map(|index| index.to_string()).join(", ");
+            println!("\t{}: {}", index, related_indexes);
+        }
 
-        let mut remaining_values = [false; GRID_16_REMANING_VALUE_LIST_SIZE];
-        let mut remaining_value_counts = [0; GRID_16_CELL_COUNT];
+    }
 
-        build_run.runner.setup_time = Some(Instant::now() - setup_start_time);
-
-        // Hold a collection of remaining values for each cell throughout the loop. When going up to
-        // some current_cell_index, recalculate the remaining values for that new cell. When going
-        // down, don't recalculate since the previous cells haven't changed. Instead mark the path
-        // we tried by removing one remaining value (unless we removed it while choosing it) and try
-        // another one. If there are no choices remaining, decrement current_cell_index.
-
-        let loop_start_time = Instant::now();
-
-        let mut prev_cell_index = 0;
-        let mut current_cell_index = 1;
-        'main_loop: loop {
-
-            /*
-            {
-                // Everything in this block is only for debugging.
-                let direction = if current_cell_index > prev_cell_index { "UP" } else if current_cell_index == prev_cell_index { "SAME" } else { "DOWN" };
-                println!("\nprint_flat_16() top of loop: {}: current = {}, prev = {}", direction, current_cell_index, prev_cell_index);
-                let mut debug_values = values.clone();
-                for i in current_cell_index..GRID_16_CELL_COUNT {
-                    debug_values[i] = NO_VALUE_USIZE;
-                }
-                let debug_grid = self.complete_grid_post_build_with_values_usize(grid, &debug_values);
-                debug_grid.print_simple_and_remaining_counts("");
-            }
-            */
-
-            if current_cell_index > prev_cell_index {
-                //rintln!("build_flat(): The current cell index is higher so we need to determine the available values.");
-                // Find the available values for the current cell.
-                let remaining_values_start_index = current_cell_index * GRID_16_VALUE_COUNT;
-                for i in remaining_values_start_index..(remaining_values_start_index + GRID_16_VALUE_COUNT) {
-
-                    if i >= remaining_values.len() {
-                        //bg!(current_cell_index, GRID_16_VALUE_COUNT, remaining_values_start_index, i);
-                    }
-
-                    remaining_values[i] = true;
-                }
-                let mut remaining_value_count = GRID_16_VALUE_COUNT;
-                let related_cell_lookup_start_index = current_cell_index * max_related_cell_count;
-                'related_indexes: for i in related_cell_lookup_start_index..(related_cell_lookup_start_index + max_related_cell_count) {
-                    let related_cell_index = self.fixed_related_cell_indexes[i];
-                    if related_cell_index < current_cell_index {
-                        let found_value = values[related_cell_index];
-                        if found_value != NO_VALUE_USIZE {
-                            let remaining_values_index = (remaining_values_start_index + found_value) - 1;
-                            if remaining_values[remaining_values_index] {
-                                remaining_values[remaining_values_index] = false;
-                                remaining_value_count -= 1;
-                            }
-                        }
-                    } else {
-                        break 'related_indexes;
-                    }
-                }
-                if remaining_value_count == 0 {
-                    // There are no remaining values for this cell, so this branch is not going to work.
-                    //rintln!("build_flat_16(): There are no remaining values for this cell, so this branch is not going to work.");
-                    prev_cell_index = current_cell_index;
-                    current_cell_index -= 1;
-                } else {
-                    //rintln!("build_flat_16(): There are remaining values so stay at this level.");
-                    remaining_value_counts[current_cell_index] = remaining_value_count;
-                    prev_cell_index = current_cell_index;
-                }
-                continue 'main_loop;
-            } else {
-                //rintln!("build_flat_16(): The current cell index is the same or lower so we need to keep going at the current level.");
-                //bg!(&remaining_values[current_cell_index]);
-                let remaining_value_count = remaining_value_counts[current_cell_index];
-                if remaining_value_count == 0 {
-                    // There are no remaining values for this cell, so this branch is not going to work.
-                    //rintln!("build_flat_16(): There are no remaining values for this cell, so this branch is not going to work.");
-                    prev_cell_index = current_cell_index;
-                    current_cell_index -= 1;
-                    continue 'main_loop;
-                }
-                let remaining_value_offset = if remaining_value_count == 1 {
-                    0
-                } else {
-                    thread_rng().gen_range(0, remaining_value_count)
-                };
-                let mut value = NO_VALUE_USIZE;
-                let remaining_values_start_index = current_cell_index * GRID_16_VALUE_COUNT;
-                let mut remaining_values_used_index = std::usize::MAX;
-                let mut found_count = 0;
-                'find_value: for i in remaining_values_start_index..(remaining_values_start_index + GRID_16_VALUE_COUNT) {
-                    if remaining_values[i] {
-                        // This is a legal remaining value entry for the current cell.
-                        if found_count == remaining_value_offset {
-                            value = (i - remaining_values_start_index) + 1;
-                            remaining_values_used_index = i;
-                            break 'find_value;
-                        } else {
-                            found_count += 1;
-                        }
-                    }
-                }
-                debug_assert!(value != NO_VALUE_USIZE);
-                debug_assert!(value <= GRID_16_VALUE_COUNT);
-                values[current_cell_index] = value;
-                if current_cell_index == GRID_16_CELL_COUNT - 1 {
-                    // We've set the last value. The grid is complete. For now just show the
-                    // values but really we need to create a real grid.
-
-                    build_run.runner.loop_time = Some(Instant::now() - loop_start_time);
-
-                    build_run.runner.success = Some(true);
-
-                    let start_time = Instant::now();
-                    build_run.grid = Some(self.complete_grid_post_build_with_values_usize(grid, &values));
-                    build_run.runner.return_object_time = Some(Instant::now() - start_time);
-
-                    return;
-                }
-                // Mark off the value we just set for the current cell so that we don't try it
-                // again.
-                remaining_values[remaining_values_used_index] = false;
-                remaining_value_counts[current_cell_index] -= 1;
-                prev_cell_index = current_cell_index;
-                current_cell_index += 1;
-                continue 'main_loop;
-            }
-        }
-    }
-
-    fn build_flat_25(&self, build_run: &mut BuildRun, grid: &Grid) {
-        // This is a specialized version of build_flat() that's only for 9x9 grids and that uses
-        // fixed-size arrays as much as possible and avoids number conversions. It also takes in
-        // some of the setup data rather than working it out each time, so it's better for repeated
-        // calls that involve a 9x9 grid with the same rules.
-
-        let max_related_cell_count = grid.max_related_cell_count as usize;
-        // debug_assert!(max_related_cell_count <= GRID_25_MAX_MAX_RELATED_CELL_COUNT);
-
-        // related_cell_indexes = self.fixed_25_related_cell_indexes.unwrap();
-        //bg!(&related_cell_indexes);
-
-        let setup_start_time = Instant::now();
-
-        let mut values = [NO_VALUE_USIZE; GRID_25_CELL_COUNT];
-        values[0] = 1;
-
-        let mut remaining_values = [false; GRID_25_REMANING_VALUE_LIST_SIZE];
-        let mut remaining_value_counts = [0; GRID_25_CELL_COUNT];
-
-        build_run.runner.setup_time = Some(Instant::now() - setup_start_time);
-
-        // Hold a collection of remaining values for each cell throughout the loop. When going up to
-        // some current_cell_index, recalculate the remaining values for that new cell. When going
-        // down, don't recalculate since the previous cells haven't changed. Instead mark the path
-        // we tried by removing one remaining value (unless we removed it while choosing it) and try
-        // another one. If there are no choices remaining, decrement current_cell_index.
-
-        let loop_start_time = Instant::now();
-
-        let mut prev_cell_index = 0;
-        let mut current_cell_index = 1;
-        'main_loop: loop {
-
-            {
-                // Everything in this block is only for debugging.
-                let direction = if current_cell_index > prev_cell_index { "UP" } else if current_cell_index == prev_cell_index { "SAME" } else { "DOWN" };
-                println!("\nprint_flat_25() top of loop: {}: current = {}, prev = {}", direction, current_cell_index, prev_cell_index);
-                let mut debug_values = values.clone();
-                for i in current_cell_index..GRID_25_CELL_COUNT {
-                    debug_values[i] = NO_VALUE_USIZE;
-                }
-                let debug_grid = self.complete_grid_post_build_with_values_usize(grid, &debug_values);
-                debug_grid.print_simple_and_remaining_counts("");
-            }
-
-            if current_cell_index > prev_cell_index {
-                println!("build_flat(): The current cell index is higher so we need to determine the available values.");
-                // Find the available values for the current cell.
-                let remaining_values_start_index = current_cell_index * GRID_25_VALUE_COUNT;
-                for i in remaining_values_start_index..(remaining_values_start_index + GRID_25_VALUE_COUNT) {
-
-                    if i >= remaining_values.len() {
-                        //bg!(current_cell_index, GRID_25_VALUE_COUNT, remaining_values_start_index, i);
-                    }
-
-                    remaining_values[i] = true;
-                }
-                let mut remaining_value_count = GRID_25_VALUE_COUNT;
-                let related_cell_lookup_start_index = current_cell_index * max_related_cell_count;
-                'related_indexes: for i in related_cell_lookup_start_index..(related_cell_lookup_start_index + max_related_cell_count) {
-                    let related_cell_index = self.fixed_related_cell_indexes[i];
-                    if related_cell_index < current_cell_index {
-                        let found_value = values[related_cell_index];
-                        if found_value != NO_VALUE_USIZE {
-                            let remaining_values_index = (remaining_values_start_index + found_value) - 1;
-                            if remaining_values[remaining_values_index] {
-                                remaining_values[remaining_values_index] = false;
-                                remaining_value_count -= 1;
-                            }
-                        }
-                    } else {
-                        break 'related_indexes;
-                    }
-                }
-                if remaining_value_count == 0 {
-                    // There are no remaining values for this cell, so this branch is not going to work.
-                    println!("build_flat_25(): There are no remaining values for this cell, so this branch is not going to work.");
-                    prev_cell_index = current_cell_index;
-                    current_cell_index -= 1;
-                } else {
-                    //rintln!("build_flat_25(): There are remaining values so stay at this level.");
-                    remaining_value_counts[current_cell_index] = remaining_value_count;
-                    prev_cell_index = current_cell_index;
-                }
-                continue 'main_loop;
-            } else {
-                println!("build_flat_25(): The current cell index is the same or lower so we need to keep going at the current level.");
-                //bg!(&remaining_values[current_cell_index]);
-                let remaining_value_count = remaining_value_counts[current_cell_index];
-                if remaining_value_count == 0 {
-                    // There are no remaining values for this cell, so this branch is not going to work.
-                    //rintln!("build_flat_25(): There are no remaining values for this cell, so this branch is not going to work.");
-                    prev_cell_index = current_cell_index;
-                    current_cell_index -= 1;
-                    continue 'main_loop;
-                }
-                let remaining_value_offset = if remaining_value_count == 1 {
-                    0
-                } else {
-                    thread_rng().gen_range(0, remaining_value_count)
-                };
-                let mut value = NO_VALUE_USIZE;
-                let remaining_values_start_index = current_cell_index * GRID_25_VALUE_COUNT;
-                let mut remaining_values_used_index = std::usize::MAX;
-                let mut found_count = 0;
-                'find_value: for i in remaining_values_start_index..(remaining_values_start_index + GRID_25_VALUE_COUNT) {
-                    if remaining_values[i] {
-                        // This is a legal remaining value entry for the current cell.
-                        if found_count == remaining_value_offset {
-                            value = (i - remaining_values_start_index) + 1;
-                            remaining_values_used_index = i;
-                            break 'find_value;
-                        } else {
-                            found_count += 1;
-                        }
-                    }
-                }
-                debug_assert!(value != NO_VALUE_USIZE);
-                debug_assert!(value <= GRID_25_VALUE_COUNT);
-                values[current_cell_index] = value;
-                if current_cell_index == GRID_25_CELL_COUNT - 1 {
-                    // We've set the last value. The grid is complete. For now just show the
-                    // values but really we need to create a real grid.
-
-                    build_run.runner.loop_time = Some(Instant::now() - loop_start_time);
-
-                    build_run.runner.success = Some(true);
-
-                    let start_time = Instant::now();
-                    build_run.grid = Some(self.complete_grid_post_build_with_values_usize(grid, &values));
-                    build_run.runner.return_object_time = Some(Instant::now() - start_time);
-
-                    return;
-                }
-                // Mark off the value we just set for the current cell so that we don't try it
-                // again.
-                remaining_values[remaining_values_used_index] = false;
-                remaining_value_counts[current_cell_index] -= 1;
-                prev_cell_index = current_cell_index;
-                current_cell_index += 1;
-                continue 'main_loop;
-            }
-        }
-    }
-    
-    fn build_flat_36(&self, build_run: &mut BuildRun, grid: &Grid) {
-        // This is a specialized version of build_flat() that's only for 9x9 grids and that uses
-        // fixed-size arrays as much as possible and avoids number conversions. It also takes in
-        // some of the setup data rather than working it out each time, so it's better for repeated
-        // calls that involve a 9x9 grid with the same rules.
-
-        let max_related_cell_count = grid.max_related_cell_count as usize;
-        // debug_assert!(max_related_cell_count <= GRID_36_MAX_MAX_RELATED_CELL_COUNT);
-
-        // related_cell_indexes = self.fixed_36_related_cell_indexes.unwrap();
-        //bg!(&related_cell_indexes);
-
-        let setup_start_time = Instant::now();
-
-        let mut values = [NO_VALUE_USIZE; GRID_36_CELL_COUNT];
-        values[0] = 1;
-
-        let mut remaining_values = [false; GRID_36_REMANING_VALUE_LIST_SIZE];
-        let mut remaining_value_counts = [0; GRID_36_CELL_COUNT];
-
-        build_run.runner.setup_time = Some(Instant::now() - setup_start_time);
-
-        // Hold a collection of remaining values for each cell throughout the loop. When going up to
-        // some current_cell_index, recalculate the remaining values for that new cell. When going
-        // down, don't recalculate since the previous cells haven't changed. Instead mark the path
-        // we tried by removing one remaining value (unless we removed it while choosing it) and try
-        // another one. If there are no choices remaining, decrement current_cell_index.
-
-        let loop_start_time = Instant::now();
-
-        let mut prev_cell_index = 0;
-        let mut current_cell_index = 1;
-        'main_loop: loop {
-
-            /*
-            {
-                // Everything in this block is only for debugging.
-                let direction = if current_cell_index > prev_cell_index { "UP" } else if current_cell_index == prev_cell_index { "SAME" } else { "DOWN" };
-                println!("\nprint_flat_36() top of loop: {}: current = {}, prev = {}", direction, current_cell_index, prev_cell_index);
-                let mut debug_values = values.clone();
-                for i in current_cell_index..GRID_36_CELL_COUNT {
-                    debug_values[i] = NO_VALUE_USIZE;
-                }
-                let debug_grid = self.complete_grid_post_build_with_values_usize(grid, &debug_values);
-                debug_grid.print_simple_and_remaining_counts("");
-            }
-            */
-
-            if current_cell_index > prev_cell_index {
-                //rintln!("build_flat(): The current cell index is higher so we need to determine the available values.");
-                // Find the available values for the current cell.
-                let remaining_values_start_index = current_cell_index * GRID_36_VALUE_COUNT;
-                for i in remaining_values_start_index..(remaining_values_start_index + GRID_36_VALUE_COUNT) {
-
-                    if i >= remaining_values.len() {
-                        //bg!(current_cell_index, GRID_36_VALUE_COUNT, remaining_values_start_index, i);
-                    }
-
-                    remaining_values[i] = true;
-                }
-                let mut remaining_value_count = GRID_36_VALUE_COUNT;
-                let related_cell_lookup_start_index = current_cell_index * max_related_cell_count;
-                'related_indexes: for i in related_cell_lookup_start_index..(related_cell_lookup_start_index + max_related_cell_count) {
-                    let related_cell_index = self.fixed_related_cell_indexes[i];
-                    if related_cell_index < current_cell_index {
-                        let found_value = values[related_cell_index];
-                        if found_value != NO_VALUE_USIZE {
-                            let remaining_values_index = (remaining_values_start_index + found_value) - 1;
-                            if remaining_values[remaining_values_index] {
-                                remaining_values[remaining_values_index] = false;
-                                remaining_value_count -= 1;
-                            }
-                        }
-                    } else {
-                        break 'related_indexes;
-                    }
-                }
-                if remaining_value_count == 0 {
-                    // There are no remaining values for this cell, so this branch is not going to work.
-                    //rintln!("build_flat_36(): There are no remaining values for this cell, so this branch is not going to work.");
-                    prev_cell_index = current_cell_index;
-                    current_cell_index -= 1;
-                } else {
-                    //rintln!("build_flat_36(): There are remaining values so stay at this level.");
-                    remaining_value_counts[current_cell_index] = remaining_value_count;
-                    prev_cell_index = current_cell_index;
-                }
-                continue 'main_loop;
-            } else {
-                //rintln!("build_flat_36(): The current cell index is the same or lower so we need to keep going at the current level.");
-                //bg!(&remaining_values[current_cell_index]);
-                let remaining_value_count = remaining_value_counts[current_cell_index];
-                if remaining_value_count == 0 {
-                    // There are no remaining values for this cell, so this branch is not going to work.
-                    //rintln!("build_flat_36(): There are no remaining values for this cell, so this branch is not going to work.");
-                    prev_cell_index = current_cell_index;
-                    current_cell_index -= 1;
-                    continue 'main_loop;
-                }
-                let remaining_value_offset = if remaining_value_count == 1 {
-                    0
-                } else {
-                    thread_rng().gen_range(0, remaining_value_count)
-                };
-                let mut value = NO_VALUE_USIZE;
-                let remaining_values_start_index = current_cell_index * GRID_36_VALUE_COUNT;
-                let mut remaining_values_used_index = std::usize::MAX;
-                let mut found_count = 0;
-                'find_value: for i in remaining_values_start_index..(remaining_values_start_index + GRID_36_VALUE_COUNT) {
-                    if remaining_values[i] {
-                        // This is a legal remaining value entry for the current cell.
-                        if found_count == remaining_value_offset {
-                            value = (i - remaining_values_start_index) + 1;
-                            remaining_values_used_index = i;
-                            break 'find_value;
-                        } else {
-                            found_count += 1;
-                        }
-                    }
-                }
-                debug_assert!(value != NO_VALUE_USIZE);
-                debug_assert!(value <= GRID_36_VALUE_COUNT);
-                values[current_cell_index] = value;
-                if current_cell_index == GRID_36_CELL_COUNT - 1 {
-                    // We've set the last value. The grid is complete. For now just show the
-                    // values but really we need to create a real grid.
-
-                    build_run.runner.loop_time = Some(Instant::now() - loop_start_time);
-
-                    build_run.runner.success = Some(true);
-
-                    let start_time = Instant::now();
-                    build_run.grid = Some(self.complete_grid_post_build_with_values_usize(grid, &values));
-                    build_run.runner.return_object_time = Some(Instant::now() - start_time);
-
-                    return;
-                }
-                // Mark off the value we just set for the current cell so that we don't try it
-                // again.
-                remaining_values[remaining_values_used_index] = false;
-                remaining_value_counts[current_cell_index] -= 1;
-                prev_cell_index = current_cell_index;
-                current_cell_index += 1;
-                continue 'main_loop;
-            }
-        }
-    }
-    
-    fn build_flat_49(&self, build_run: &mut BuildRun, grid: &Grid) {
-        // This is a specialized version of build_flat() that's only for 9x9 grids and that uses
-        // fixed-size arrays as much as possible and avoids number conversions. It also takes in
-        // some of the setup data rather than working it out each time, so it's better for repeated
-        // calls that involve a 9x9 grid with the same rules.
-
-        let max_related_cell_count = grid.max_related_cell_count as usize;
-        // debug_assert!(max_related_cell_count <= GRID_49_MAX_MAX_RELATED_CELL_COUNT);
-
-        // related_cell_indexes = self.fixed_49_related_cell_indexes.unwrap();
-        //bg!(&related_cell_indexes);
-
-        let setup_start_time = Instant::now();
-
-        let mut values = [NO_VALUE_USIZE; GRID_49_CELL_COUNT];
-        values[0] = 1;
-
-        let mut remaining_values = [false; GRID_49_REMANING_VALUE_LIST_SIZE];
-        let mut remaining_value_counts = [0; GRID_49_CELL_COUNT];
-
-        build_run.runner.setup_time = Some(Instant::now() - setup_start_time);
-
-        // Hold a collection of remaining values for each cell throughout the loop. When going up to
-        // some current_cell_index, recalculate the remaining values for that new cell. When going
-        // down, don't recalculate since the previous cells haven't changed. Instead mark the path
-        // we tried by removing one remaining value (unless we removed it while choosing it) and try
-        // another one. If there are no choices remaining, decrement current_cell_index.
-
-        let loop_start_time = Instant::now();
-
-        let mut prev_cell_index = 0;
-        let mut current_cell_index = 1;
-        'main_loop: loop {
-
-            /*
-            {
-                // Everything in this block is only for debugging.
-                let direction = if current_cell_index > prev_cell_index { "UP" } else if current_cell_index == prev_cell_index { "SAME" } else { "DOWN" };
-                println!("\nprint_flat_49() top of loop: {}: current = {}, prev = {}", direction, current_cell_index, prev_cell_index);
-                let mut debug_values = values.clone();
-                for i in current_cell_index..GRID_49_CELL_COUNT {
-                    debug_values[i] = NO_VALUE_USIZE;
-                }
-                let debug_grid = self.complete_grid_post_build_with_values_usize(grid, &debug_values);
-                debug_grid.print_simple_and_remaining_counts("");
-            }
-            */
-
-            if current_cell_index > prev_cell_index {
-                //rintln!("build_flat(): The current cell index is higher so we need to determine the available values.");
-                // Find the available values for the current cell.
-                let remaining_values_start_index = current_cell_index * GRID_49_VALUE_COUNT;
-                for i in remaining_values_start_index..(remaining_values_start_index + GRID_49_VALUE_COUNT) {
-
-                    if i >= remaining_values.len() {
-                        //bg!(current_cell_index, GRID_49_VALUE_COUNT, remaining_values_start_index, i);
-                    }
-
-                    remaining_values[i] = true;
-                }
-                let mut remaining_value_count = GRID_49_VALUE_COUNT;
-                let related_cell_lookup_start_index = current_cell_index * max_related_cell_count;
-                'related_indexes: for i in related_cell_lookup_start_index..(related_cell_lookup_start_index + max_related_cell_count) {
-                    let related_cell_index = self.fixed_related_cell_indexes[i];
-                    if related_cell_index < current_cell_index {
-                        let found_value = values[related_cell_index];
-                        if found_value != NO_VALUE_USIZE {
-                            let remaining_values_index = (remaining_values_start_index + found_value) - 1;
-                            if remaining_values[remaining_values_index] {
-                                remaining_values[remaining_values_index] = false;
-                                remaining_value_count -= 1;
-                            }
-                        }
-                    } else {
-                        break 'related_indexes;
-                    }
-                }
-                if remaining_value_count == 0 {
-                    // There are no remaining values for this cell, so this branch is not going to work.
-                    //rintln!("build_flat_49(): There are no remaining values for this cell, so this branch is not going to work.");
-                    prev_cell_index = current_cell_index;
-                    current_cell_index -= 1;
-                } else {
-                    //rintln!("build_flat_49(): There are remaining values so stay at this level.");
-                    remaining_value_counts[current_cell_index] = remaining_value_count;
-                    prev_cell_index = current_cell_index;
-                }
-                continue 'main_loop;
-            } else {
-                //rintln!("build_flat_49(): The current cell index is the same or lower so we need to keep going at the current level.");
-                //bg!(&remaining_values[current_cell_index]);
-                let remaining_value_count = remaining_value_counts[current_cell_index];
-                if remaining_value_count == 0 {
-                    // There are no remaining values for this cell, so this branch is not going to work.
-                    //rintln!("build_flat_49(): There are no remaining values for this cell, so this branch is not going to work.");
-                    prev_cell_index = current_cell_index;
-                    current_cell_index -= 1;
-                    continue 'main_loop;
-                }
-                let remaining_value_offset = if remaining_value_count == 1 {
-                    0
-                } else {
-                    thread_rng().gen_range(0, remaining_value_count)
-                };
-                let mut value = NO_VALUE_USIZE;
-                let remaining_values_start_index = current_cell_index * GRID_49_VALUE_COUNT;
-                let mut remaining_values_used_index = std::usize::MAX;
-                let mut found_count = 0;
-                'find_value: for i in remaining_values_start_index..(remaining_values_start_index + GRID_49_VALUE_COUNT) {
-                    if remaining_values[i] {
-                        // This is a legal remaining value entry for the current cell.
-                        if found_count == remaining_value_offset {
-                            value = (i - remaining_values_start_index) + 1;
-                            remaining_values_used_index = i;
-                            break 'find_value;
-                        } else {
-                            found_count += 1;
-                        }
-                    }
-                }
-                debug_assert!(value != NO_VALUE_USIZE);
-                debug_assert!(value <= GRID_49_VALUE_COUNT);
-                values[current_cell_index] = value;
-                if current_cell_index == GRID_49_CELL_COUNT - 1 {
-                    // We've set the last value. The grid is complete. For now just show the
-                    // values but really we need to create a real grid.
-
-                    build_run.runner.loop_time = Some(Instant::now() - loop_start_time);
-
-                    build_run.runner.success = Some(true);
-
-                    let start_time = Instant::now();
-                    build_run.grid = Some(self.complete_grid_post_build_with_values_usize(grid, &values));
-                    build_run.runner.return_object_time = Some(Instant::now() - start_time);
-
-                    return;
-                }
-                // Mark off the value we just set for the current cell so that we don't try it
-                // again.
-                remaining_values[remaining_values_used_index] = false;
-                remaining_value_counts[current_cell_index] -= 1;
-                prev_cell_index = current_cell_index;
-                current_cell_index += 1;
-                continue 'main_loop;
-            }
-        }
-    }
-    
-    fn debug_print_related_cell_indexes_flat(related_cell_indexes: &Vec<Vec<usize>>) {
-        println!("debug_print_related_cell_indexes_flat():");
-        for (index, v) in related_cell_indexes.iter().enumerate() {
-            let related_indexes = v.iter().map(|index| index.to_string()).join(", ");
-            println!("\t{}: {}", index, related_indexes);
-        }
-
-    }
-
-    fn print_grid_simple(&self, grid: &Grid, label: &str) {
-        self.complete_grid_post_build(grid).print_simple(label);
-    }
+    fn print_grid_simple(&self, grid: &Grid, label: &str) {
+        self.complete_grid_post_build(grid).print_simple(label);
+    }
 
     fn print_grid_simple_and_remaining_counts(&self, grid: &Grid, label: &str) {
         self.complete_grid_post_build(grid).print_simple_and_remaining_counts(label);
@@ -1890,6 +1664,11 @@ impl Builder {
         indexes
     }
 
+    pub fn build_runs(&self) -> &[BuildRun] {
+        // One for each call to build(), oldest first.
+        &self.build_runs
+    }
+
     pub fn get_duration(&self) -> Duration {
         // This works only if there is a single build run and it has a duration.
         debug_assert!(self.build_runs.len() == 1);
@@ -1899,7 +1678,7 @@ impl Builder {
 }
 
 impl BuildRun {
-    pub fn new(time_limit: Option<Duration>, max_values: u8) -> Self {
+    pub fn new(time_limit: Option<Duration>, cell_count: u16, max_values: u8) -> Self {
         let mut branch_sizes = Vec::with_capacity(max_values as usize);
         for _ in 0..max_values {
             branch_sizes.push(0);
@@ -1913,13 +1692,97 @@ impl BuildRun {
             tried_grid_registered_count: 0,
             tried_grid_skipped_count: 0,
             tried_grid_found_count: 0,
-            filled_cell_counts: vec![],
+            restart_count: 0,
+            attempt_start_node_count: 0,
+            attempt_node_limit: None,
+            restart_pending: false,
+            filled_cell_counts: vec![0; cell_count as usize + 1],
             tried_grids: Default::default(),
+            tried_grid_count: 0,
             branch_sizes,
             grid: None
         }
     }
 
+    #[inline]
+    pub fn record_dead_end(&mut self, filled_cell_count: u16) {
+        self.filled_cell_counts[filled_cell_count as usize] += 1;
+    }
+
+    pub fn dead_end_count(&self) -> usize {
+        self.filled_cell_counts.iter().sum()
+    }
+
+    fn start_attempt(&mut self, restart_policy: &RestartPolicy) {
+        self.attempt_start_node_count = self.runner.node_count;
+        self.attempt_node_limit = restart_policy.attempt_node_limit(self.restart_count);
+        self.restart_pending = false;
+    }
+
+    fn restart(&mut self, restart_policy: &RestartPolicy) {
+        self.restart_count += 1;
+        diagnostic!(Level::Debug, event = "build_restart", restart_count = self.restart_count, node_count = self.runner.node_count,
+            attempt_node_count = self.runner.node_count - self.attempt_start_node_count; "Restarting the build.");
+        self.start_attempt(restart_policy);
+    }
+
+    fn restart_flat(&mut self, restart_policy: &RestartPolicy) -> (usize, usize) {
+        // Restarts a build by one of the flat strategies and returns the previous and current cell
+        // indexes to carry on from. The build starts again from the second cell. The values after
+        // the current cell are set again on the way up so there's no need to clear them.
+        self.restart(restart_policy);
+        (0, 1)
+    }
+
+    #[inline]
+    fn attempt_over(&mut self) -> bool {
+        // True once this attempt has used up its node budget, after which it stays true until the
+        // next attempt starts.
+        if let Some(attempt_node_limit) = self.attempt_node_limit {
+            if self.runner.node_count - self.attempt_start_node_count >= attempt_node_limit {
+                self.restart_pending = true;
+            }
+        }
+        self.restart_pending
+    }
+
+    #[inline]
+    fn check_continue(&mut self) -> bool {
+        // Like Runner::check_continue() but also false when the attempt should be abandoned.
+        !self.attempt_over() && self.runner.check_continue()
+    }
+
+}
+
+impl RestartPolicy {
+    pub fn attempt_node_limit(&self, restart_count: usize) -> Option<usize> {
+        // The node budget for the attempt after restart_count restarts, or None for no limit.
+        match self {
+            RestartPolicy::Never => None,
+            RestartPolicy::FixedNodes { node_count } => Some((*node_count).max(1)),
+            RestartPolicy::Luby { unit_node_count } => Some(unit_node_count.saturating_mul(luby(restart_count + 1)).max(1)),
+            RestartPolicy::Geometric { first_node_count, factor } => {
+                let node_count = *first_node_count as f64 * factor.powi(restart_count.min(i32::MAX as usize) as i32);
+                Some((node_count.min(usize::MAX as f64) as usize).max(1))
+            },
+        }
+    }
+}
+
+fn luby(i: usize) -> usize {
+    // The ith term of the Luby sequence, counting from 1: 1, 1, 2, 1, 1, 2, 4, 1, 1, 2, 1, 1, 2,
+    // 4, 8, ... Each run of terms ending in 2^(k - 1) takes up the first 2^k - 1 places.
+    let mut i = i;
+    loop {
+        let mut k = 1;
+        while (1usize << k) - 1 < i {
+            k += 1;
+        }
+        if (1usize << k) - 1 == i {
+            return 1 << (k - 1);
+        }
+        i -= (1 << (k - 1)) - 1;
+    }
 }
 
 impl Cell {
@@ -1959,6 +1822,58 @@ impl Cell {
     }
 }
 
+// The grid_constraint_solve engine for engine::GridBuilder, using the standard row, column, and
+// block rules and the given strategy. Flat and FlatUsize check the node limit but not the clock,
+// so they don't stop at the time limit.
+pub struct Engine {
+    pub strategy: BuildStrategy,
+}
+
+impl Engine {
+    pub fn new(strategy: BuildStrategy) -> Self {
+        Self {
+            strategy,
+        }
+    }
+}
+
+impl GridBuilder for Engine {
+    fn name(&self) -> &'static str {
+        match self.strategy {
+            BuildStrategy::Auto => "grid_constraint_solve Auto",
+            BuildStrategy::NextCell => "grid_constraint_solve NextCell",
+            BuildStrategy::Flat => "grid_constraint_solve Flat",
+            BuildStrategy::FlatUsize => "grid_constraint_solve FlatUsize",
+            BuildStrategy::Fixed => "grid_constraint_solve Fixed",
+            BuildStrategy::Pattern => "grid_constraint_solve Pattern",
+        }
+    }
+
+    fn max_cell_count(&self) -> usize {
+        u8::MAX as usize * u8::MAX as usize
+    }
+
+    fn honors_time_limit(&self) -> bool {
+        match self.strategy {
+            BuildStrategy::Auto | BuildStrategy::NextCell | BuildStrategy::Fixed | BuildStrategy::Pattern => true,
+            BuildStrategy::Flat | BuildStrategy::FlatUsize => false,
+        }
+    }
+
+    fn build_values(&self, config: &BuildConfig, stats: &mut BuildStats) -> Result<Vec<u8>, SudokuError> {
+        let mut builder = Builder::new(config.width, config.height, config.block_width, config.block_height);
+        builder.strategy = self.strategy.clone();
+        builder.time_limit = config.time_limit;
+        builder.max_tried_grid_count = config.max_tried_grid_count;
+        let result = builder.build();
+        if let Some(build_run) = builder.build_runs.last() {
+            stats.node_count = Some(build_run.runner.node_count);
+            stats.tried_grid_count = Some(build_run.tried_grid_count);
+        }
+        result.map(|grid| grid.values)
+    }
+}
+
 /*
 impl FailedGridSet {
     fn new() -> Self {
@@ -0,0 +1,155 @@
+#![allow(dead_code)]
+
+// Reading and editing the candidates (pencil marks) of a grid, that is, the remaining values of
+// each empty cell, plus queries on rows, columns, and blocks such as where a value can go in a
+// row. For the text form of a whole candidate grid see Grid::remaining_values_string() and
+// Grid::set_remaining_values_from_string().
+// Candidates removed by hand come back whenever the remaining values are recalculated, which
+// happens for a cell and its related cells when a value is set, and for every cell in
+// replace_values() and reset_candidates().
+// This goes with grid::Grid.
+
+use crate::*;
+use super::grid::Grid;
+use super::builder::Builder;
+use super::SudokuError;
+
+pub fn main() {
+    try_candidates();
+}
+
+fn try_candidates() {
+    let mut grid = Builder::with_size(9).build().unwrap();
+    grid.remove_cells(50);
+    let house = House::Row(2);
+    let value = 5;
+    println!("try_candidates(): {:?} has cells {:?}, value {} can go in {:?}",
+             house, grid.house_indexes(house).unwrap(), value, grid.candidate_positions(house, value).unwrap());
+    let text = grid.remaining_values_string().unwrap();
+    println!("{}", text);
+    let mut copy = grid.clone_empty();
+    copy.set_remaining_values_from_string(&text).unwrap();
+    println!("try_candidates(): round trip ok = {}", copy.remaining_values_string().unwrap() == text);
+}
+
+// Rows, columns, and blocks are numbered from zero, as in Grid::row_col_block().
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum House {
+    Row(u8),
+    Column(u8),
+    Block(u8),
+}
+
+impl Grid {
+
+    pub fn candidates(&self, index: u16) -> Result<Vec<u8>, SudokuError> {
+        // A solved cell has no candidates.
+        self.check_candidate_index(index)?;
+        Ok(self.remaining_values(index))
+    }
+
+    pub fn set_candidates(&mut self, index: u16, values: &[u8]) -> Result<(), SudokuError> {
+        // Replace the cell's candidates with exactly these values.
+        self.check_candidate_index(index)?;
+        for value in values.iter() {
+            self.check_candidate_value(index, *value)?;
+        }
+        for value in 1..=self.max_value {
+            if values.contains(&value) {
+                self.set_remaining_value(index, value);
+            } else {
+                self.clear_remaining_value(index, value);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn add_candidate(&mut self, index: u16, value: u8) -> Result<bool, SudokuError> {
+        // Returns true if the value wasn't already a candidate.
+        self.check_candidate_index(index)?;
+        self.check_candidate_value(index, value)?;
+        Ok(self.set_remaining_value(index, value))
+    }
+
+    pub fn remove_candidate(&mut self, index: u16, value: u8) -> Result<bool, SudokuError> {
+        // Returns true if the value was a candidate.
+        self.check_candidate_index(index)?;
+        if value == NO_VALUE || value > self.max_value {
+            return Err(SudokuError::InvalidCandidate { index, value, reason: format!("Values go from 1 to {}.", self.max_value) });
+        }
+        Ok(self.clear_remaining_value(index, value))
+    }
+
+    pub fn reset_candidates(&mut self) {
+        // Undo any editing of the candidates so that each empty cell's candidates are just the
+        // values not found in its related cells.
+        self.recalc_remaining_values();
+    }
+
+    pub fn all_houses(&self) -> Vec<House> {
+        let mut houses = vec![];
+        houses.extend((0..self.height).map(House::Row));
+        houses.extend((0..self.width).map(House::Column));
+        houses.extend((0..self.block_count).map(House::Block));
+        houses
+    }
+
+    pub fn cell_houses(&self, index: u16) -> [House; 3] {
+        let (row, column, block) = self.row_col_block(index);
+        [House::Row(row), House::Column(column), House::Block(block)]
+    }
+
+    pub fn house_indexes(&self, house: House) -> Result<Vec<u16>, SudokuError> {
+        let width = self.width as u16;
+        match house {
+            House::Row(row) if row < self.height => Ok((0..width).map(|column| (row as u16 * width) + column).collect()),
+            House::Column(column) if column < self.width => Ok((0..self.height as u16).map(|row| (row * width) + column as u16).collect()),
+            House::Block(block) if block < self.block_count => Ok((0..self.cell_count)
+                .filter(|index| self.row_col_block(*index).2 == block)
+                .collect()),
+            _ => Err(SudokuError::InvalidDimensions {
+                width: self.width,
+                height: self.height,
+                block_width: self.block_width,
+                block_height: self.block_height,
+                reason: format!("There is no {:?} in the grid.", house),
+            }),
+        }
+    }
+
+    pub fn candidate_positions(&self, house: House, value: u8) -> Result<Vec<u16>, SudokuError> {
+        // The empty cells in the house that have the value as a candidate. If the value is already
+        // in the house this is usually empty.
+        if value == NO_VALUE || value > self.max_value {
+            return Err(SudokuError::InvalidCandidate { index: 0, value, reason: format!("Values go from 1 to {}.", self.max_value) });
+        }
+        Ok(self.house_indexes(house)?
+            .into_iter()
+            .filter(|index| self.values[*index as usize] == NO_VALUE && self.has_remaining_value(*index, value))
+            .collect())
+    }
+
+    fn check_candidate_index(&self, index: u16) -> Result<(), SudokuError> {
+        if index >= self.cell_count {
+            return Err(SudokuError::InvalidCandidate { index, value: NO_VALUE, reason: format!("The grid has {} cells.", self.cell_count) });
+        }
+        if self.values[index as usize] != NO_VALUE {
+            return Err(SudokuError::InvalidCandidate { index, value: self.values[index as usize], reason: "The cell already has a value.".to_string() });
+        }
+        Ok(())
+    }
+
+    fn check_candidate_value(&self, index: u16, value: u8) -> Result<(), SudokuError> {
+        // A value that's already in a related cell can't be a candidate.
+        if value == NO_VALUE || value > self.max_value {
+            return Err(SudokuError::InvalidCandidate { index, value, reason: format!("Values go from 1 to {}.", self.max_value) });
+        }
+        if let Some(related_index) = self.index_to_related_cell_indexes(index)
+                .into_iter()
+                .find(|related_index| self.values[*related_index as usize] == value) {
+            return Err(SudokuError::InvalidCandidate { index, value, reason: format!("Related cell index {} has the same value.", related_index) });
+        }
+        Ok(())
+    }
+
+}
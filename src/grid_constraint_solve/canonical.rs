@@ -0,0 +1,188 @@
+#![allow(dead_code)]
+
+// Canonical forms for grids and puzzles. Two grids that differ only by a transformation that
+// preserves the standard rules (see transform::GridTransform) have the same canonical form, so the
+// canonical string can be used to find duplicates in a puzzle collection.
+// This goes with grid::Grid.
+
+use crate::*;
+use super::grid::Grid;
+use super::builder::Builder;
+use super::transform::{GridTransform, band_preserving_orders, band_preserving_order_count};
+use super::SudokuError;
+use std::time::Instant;
+
+// The search below tries every transformation, so it's only practical for grids up to about 9x9.
+// A 9x9 grid has 2 * 1,296 * 1,296 = 3,359,232 candidates and most of them are rejected after a
+// handful of cells.
+const MAX_CANDIDATE_COUNT: u64 = 50_000_000;
+
+const SYMBOL_CANONICAL_NO_VALUE: char = '.';
+
+pub fn main() {
+    try_canonical_form();
+}
+
+fn try_canonical_form() {
+    let grid_size = 9;
+    let remove_cell_count = 50;
+    let repeat_count = 5;
+
+    for _ in 0..repeat_count {
+        let mut grid = Builder::with_size(grid_size).build().unwrap();
+        grid.remove_cells(remove_cell_count);
+        let start_time = Instant::now();
+        match grid.canonical_form() {
+            Ok(canonical_form) => {
+                // Flip the grid upside down and reverse the values, then make sure the two grids are
+                // seen as equivalent.
+                let mut transform = GridTransform::identity(&grid);
+                transform.row_order.reverse();
+                transform.relabel[1..].reverse();
+                let scrambled_grid = transform.apply(&grid);
+                println!("try_canonical_form(): time = {:?}, canonical = {}, equivalent = {:?}",
+                         Instant::now() - start_time, canonical_form.string, grid.is_equivalent(&scrambled_grid));
+            },
+            Err(error) => println!("{}", error),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CanonicalForm {
+    pub string: String,
+    pub values: Vec<u8>,
+    pub transform: GridTransform,
+}
+
+impl Grid {
+
+    pub fn canonical_form(&self) -> Result<CanonicalForm, SudokuError> {
+        // The canonical form is the lexicographically smallest list of values over every
+        // transformation, with empty cells counting as zero and the values in each candidate
+        // relabelled in order of first appearance. Relabelling this way means we never have to try
+        // the value permutations separately.
+        if !self.has_standard_rules() {
+            return Err(SudokuError::UnsupportedRules { reason: "Canonical forms are only defined for the standard row, column, and block rules.".to_string() });
+        }
+
+        let can_transpose = self.width == self.height && self.block_width == self.block_height;
+        let transpose_options = if can_transpose { vec![false, true] } else { vec![false] };
+        let candidate_count = band_preserving_order_count(self.height, self.block_height)
+            .saturating_mul(band_preserving_order_count(self.width, self.block_width))
+            .saturating_mul(transpose_options.len() as u64);
+        if candidate_count > MAX_CANDIDATE_COUNT {
+            return Err(SudokuError::CanonicalSearchTooLarge { transform_count: candidate_count, max_transform_count: MAX_CANDIDATE_COUNT });
+        }
+
+        let row_orders = band_preserving_orders(self.height, self.block_height);
+        let column_orders = band_preserving_orders(self.width, self.block_width);
+
+        let cell_count = self.cell_count as usize;
+        let width = self.width as usize;
+        let mut best_values: Vec<u8> = vec![];
+        let mut best_transform = GridTransform::identity(self);
+        let mut candidate_values = vec![NO_VALUE; cell_count];
+        let mut relabel = vec![NO_VALUE; self.max_value as usize + 1];
+
+        for transpose in transpose_options.iter() {
+            for row_order in row_orders.iter() {
+                for column_order in column_orders.iter() {
+                    for value in relabel.iter_mut() {
+                        *value = NO_VALUE;
+                    }
+                    let mut next_label = 1;
+                    // Until a candidate is known to be smaller than the best so far we compare it
+                    // cell by cell and drop it as soon as it's larger.
+                    let mut is_smaller = best_values.is_empty();
+                    let mut is_larger = false;
+                    for index in 0..cell_count {
+                        let (row, column) = if *transpose {
+                            (index % width, index / width)
+                        } else {
+                            (index / width, index % width)
+                        };
+                        let source_index = (row_order[row] as usize * width) + column_order[column] as usize;
+                        let value = self.values[source_index];
+                        let label = if value == NO_VALUE {
+                            NO_VALUE
+                        } else {
+                            if relabel[value as usize] == NO_VALUE {
+                                relabel[value as usize] = next_label;
+                                next_label += 1;
+                            }
+                            relabel[value as usize]
+                        };
+                        if !is_smaller {
+                            if label > best_values[index] {
+                                is_larger = true;
+                                break;
+                            }
+                            if label < best_values[index] {
+                                is_smaller = true;
+                            }
+                        }
+                        candidate_values[index] = label;
+                    }
+                    if is_smaller && !is_larger {
+                        // Values that don't appear in the grid at all still need a label so that the
+                        // transform is a complete relabelling.
+                        for value in relabel.iter_mut().skip(1) {
+                            if *value == NO_VALUE {
+                                *value = next_label;
+                                next_label += 1;
+                            }
+                        }
+                        best_values = candidate_values.clone();
+                        best_transform = GridTransform {
+                            transpose: *transpose,
+                            row_order: row_order.clone(),
+                            column_order: column_order.clone(),
+                            relabel: relabel.clone(),
+                        };
+                    }
+                }
+            }
+        }
+
+        let string = best_values.iter().map(|value| canonical_symbol(*value)).collect();
+        Ok(CanonicalForm {
+            string,
+            values: best_values,
+            transform: best_transform,
+        })
+    }
+
+    pub fn is_equivalent(&self, other: &Grid) -> Result<bool, SudokuError> {
+        // Returns true if some combination of transformations and relabelling turns one grid into
+        // the other.
+        if self.width != other.width || self.height != other.height
+            || self.block_width != other.block_width || self.block_height != other.block_height {
+            return Ok(false);
+        }
+        Ok(self.canonical_form()?.string == other.canonical_form()?.string)
+    }
+
+    pub(crate) fn has_standard_rules(&self) -> bool {
+        // A grid with no related cells hasn't been through a builder yet. Otherwise each cell has
+        // to be related to exactly the other cells in its row, column, and block.
+        if self.related_cell_indexes.is_empty() {
+            return true;
+        }
+        (0..self.cell_count).all(|index| {
+            let mut related_cell_indexes = self.index_to_related_cell_indexes(index);
+            related_cell_indexes.sort();
+            related_cell_indexes.dedup();
+            related_cell_indexes == self.standard_related_cell_indexes(index)
+        })
+    }
+
+}
+
+fn canonical_symbol(value: u8) -> char {
+    if value == NO_VALUE {
+        SYMBOL_CANONICAL_NO_VALUE
+    } else {
+        SYMBOLS_EXTENDED.chars().nth(value as usize).unwrap_or('?')
+    }
+}
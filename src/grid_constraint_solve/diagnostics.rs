@@ -0,0 +1,199 @@
+#![allow(dead_code)]
+
+// Runtime switches for invariant checks and logging, replacing the RUN_INVARIANT and VERBOSE
+// constants. A Diagnostics value says which checkpoints run Grid::invariant() and the most detailed
+// log level to emit. Give one to Builder::diagnostics() or Solver::diagnostics() and it applies for
+// the length of each build or solve, including the Grid methods they call. Code that works on a
+// Grid directly can use set_current() instead.
+// Like the seeded random number generator, the current diagnostics belong to the thread, so two
+// builds on different threads can use different settings. Checking whether a checkpoint is on is
+// a thread-local read, cheap enough for the inner loops.
+// Log events go through the log facade, so nothing is printed unless the program installs a
+// logger, and the logger's own level applies as well as this one. Events have an event key and
+// other fields as structured key-values, and the target is the module they come from.
+
+use std::cell::Cell;
+use std::fmt::{self, Display, Formatter};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+use super::builder::Builder;
+
+pub fn main() {
+    try_diagnostics();
+}
+
+fn try_diagnostics() {
+    // Check the grid at every step of a small build and print the debug events.
+    log::set_logger(&PRINT_LOGGER).unwrap();
+    log::set_max_level(LevelFilter::Debug);
+    let diagnostics = Diagnostics::new().check_all().log_level(LevelFilter::Debug);
+    println!("try_diagnostics(): {}", diagnostics);
+    let grid = Builder::with_size(4).diagnostics(diagnostics).build().unwrap();
+    grid.print_simple("try_diagnostics()");
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Checkpoint {
+    // After each Grid method that changes values: setting and removing values, replacing them,
+    // making partial grids, and transforming.
+    GridChange,
+    // In the builder after each value is set and the cells it leaves with one value are filled.
+    BuildStep,
+    // The completed grid at the end of a build.
+    BuildEnd,
+    // In the solver after each value is set.
+    SolveStep,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Diagnostics {
+    // One bit for each Checkpoint.
+    checkpoints: u8,
+    pub log_level: LevelFilter,
+}
+
+thread_local! {
+    static CURRENT_DIAGNOSTICS: Cell<Diagnostics> = const { Cell::new(Diagnostics::DEFAULT) };
+}
+
+// Puts the previous diagnostics back when dropped.
+#[must_use]
+pub struct DiagnosticsGuard {
+    previous: Diagnostics,
+}
+
+impl Checkpoint {
+    pub const ALL: [Checkpoint; 4] = [Checkpoint::GridChange, Checkpoint::BuildStep, Checkpoint::BuildEnd, Checkpoint::SolveStep];
+
+    const fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+impl Diagnostics {
+
+    // The settings before anything changes them: the completed grid is checked at the end of each
+    // build, and events up to Info are logged.
+    pub const DEFAULT: Diagnostics = Diagnostics {
+        checkpoints: Checkpoint::BuildEnd.bit(),
+        log_level: LevelFilter::Info,
+    };
+
+    pub fn new() -> Self {
+        Self::DEFAULT
+    }
+
+    pub fn check(mut self, checkpoint: Checkpoint) -> Self {
+        self.checkpoints |= checkpoint.bit();
+        self
+    }
+
+    pub fn check_all(mut self) -> Self {
+        for checkpoint in Checkpoint::ALL.iter() {
+            self.checkpoints |= checkpoint.bit();
+        }
+        self
+    }
+
+    pub fn no_checks(mut self) -> Self {
+        self.checkpoints = 0;
+        self
+    }
+
+    pub fn log_level(mut self, log_level: LevelFilter) -> Self {
+        self.log_level = log_level;
+        self
+    }
+
+    pub fn checks(&self, checkpoint: Checkpoint) -> bool {
+        self.checkpoints & checkpoint.bit() != 0
+    }
+
+    pub fn enter(&self) -> DiagnosticsGuard {
+        // Make these the current diagnostics until the guard is dropped.
+        let previous = CURRENT_DIAGNOSTICS.with(|current| current.replace(*self));
+        DiagnosticsGuard { previous }
+    }
+
+}
+
+impl Default for Diagnostics {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl Drop for DiagnosticsGuard {
+    fn drop(&mut self) {
+        CURRENT_DIAGNOSTICS.with(|current| current.set(self.previous));
+    }
+}
+
+pub fn current() -> Diagnostics {
+    CURRENT_DIAGNOSTICS.with(|current| current.get())
+}
+
+pub fn set_current(diagnostics: Diagnostics) {
+    CURRENT_DIAGNOSTICS.with(|current| current.set(diagnostics));
+}
+
+#[inline]
+pub fn checks(checkpoint: Checkpoint) -> bool {
+    CURRENT_DIAGNOSTICS.with(|current| current.get().checks(checkpoint))
+}
+
+#[inline]
+pub fn log_enabled(level: Level) -> bool {
+    level <= log::max_level() && level <= CURRENT_DIAGNOSTICS.with(|current| current.get().log_level)
+}
+
+// Logs through the log facade if both the current diagnostics and the logger allow the level. The
+// arguments after the level are the same as for log::log!(), so structured fields go before a
+// semicolon and the message after it. The message isn't formatted if the event is skipped.
+macro_rules! diagnostic {
+    ($level:expr, $($arg:tt)+) => {
+        if $crate::grid_constraint_solve::diagnostics::log_enabled($level) {
+            log::log!($level, $($arg)+);
+        }
+    };
+}
+pub(crate) use diagnostic;
+
+// A logger that prints every event to standard output, for the try_ functions.
+pub struct PrintLogger;
+
+pub static PRINT_LOGGER: PrintLogger = PrintLogger;
+
+impl Log for PrintLogger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        let mut fields = KeyValues(vec![]);
+        let _ = record.key_values().visit(&mut fields);
+        println!("{} {}: {}{}", record.level(), record.target(), record.args(), fields.0.iter().map(|(key, value)| format!(" {}={}", key, value)).collect::<String>());
+    }
+
+    fn flush(&self) {}
+}
+
+struct KeyValues(Vec<(String, String)>);
+
+impl<'kvs> log::kv::VisitSource<'kvs> for KeyValues {
+    fn visit_pair(&mut self, key: log::kv::Key<'kvs>, value: log::kv::Value<'kvs>) -> Result<(), log::kv::Error> {
+        self.0.push((key.to_string(), value.to_string()));
+        Ok(())
+    }
+}
+
+impl Display for Diagnostics {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let checkpoints = Checkpoint::ALL
+            .iter()
+            .filter(|checkpoint| self.checks(**checkpoint))
+            .map(|checkpoint| format!("{:?}", checkpoint))
+            .collect::<Vec<_>>();
+        write!(f, "checks = [{}], log_level = {}", checkpoints.join(", "), self.log_level)
+    }
+}
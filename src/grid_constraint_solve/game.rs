@@ -0,0 +1,430 @@
+#![allow(dead_code)]
+
+// A game session for playing a puzzle by hand: the givens, the player's entries and pencil marks,
+// an undo/redo history, conflicts between related cells, hints, and a timer. The terminal front
+// end is in play.rs and saving and loading are in session_file.rs.
+// This goes with grid::Grid.
+
+use crate::*;
+use super::grid::Grid;
+use super::builder::Builder;
+use super::solver::Solver;
+use super::candidates::House;
+use super::backdoor::{hidden_single, SingleSearch};
+use super::hint::{labelled_houses, HintLevel, HintOutcome, StepAction};
+use super::unique::{Uniqueness, UniquenessChecker};
+use super::validate::Violation;
+use super::SudokuError;
+use itertools::Itertools;
+use std::time::{Duration, Instant};
+
+const STYLE_GIVEN: &str = "\x1b[1m";
+const STYLE_ENTRY: &str = "\x1b[36m";
+const STYLE_CONFLICT: &str = "\x1b[31m";
+const STYLE_HINT: &str = "\x1b[32m";
+
+pub fn main() {
+    try_game_session();
+}
+
+fn try_game_session() {
+    // Play a puzzle to the end using only hints, with an undo and redo along the way.
+    let grid = Builder::with_size(9).build().unwrap();
+    let puzzle = Solver::new(&grid).reduce_minimal().unwrap().grid;
+    let mut session = GameSession::new(&puzzle).unwrap();
+    let mut reason_counts = [0; 4];
+    while let Some(hint) = session.hint() {
+        reason_counts[match hint.reason {
+            HintReason::Mistake => 0,
+            HintReason::NakedSingle => 1,
+            HintReason::HiddenSingle(_) => 2,
+            HintReason::FromSolution => 3,
+        }] += 1;
+        session.apply_hint(&hint).unwrap();
+        if session.hint_count == 1 {
+            session.undo();
+            session.redo();
+        }
+    }
+    println!("{}", session.render(false).unwrap());
+    println!("try_game_session(): solved = {}, hints = {}, [mistake, naked single, hidden single, from solution] = {:?}, time = {:?}",
+             session.is_solved(), session.hint_count, reason_counts, session.elapsed());
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CellState {
+    pub value: u8,
+    // Sorted, and empty whenever the cell has a value.
+    pub pencil_marks: Vec<u8>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Edit {
+    pub index: u16,
+    pub before: CellState,
+    pub after: CellState,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HintReason {
+    // The player's value in this cell doesn't match the solution.
+    Mistake,
+    // The cell has only one value that isn't already in a related cell.
+    NakedSingle,
+    // The value has only one possible cell in this house.
+    HiddenSingle(House),
+    // Nothing simple applies, so the hint just gives the value from the solution.
+    FromSolution,
+}
+
+#[derive(Clone, Debug)]
+pub struct Hint {
+    pub index: u16,
+    pub value: u8,
+    pub reason: HintReason,
+}
+
+#[derive(Clone, Debug)]
+pub struct GameSession {
+    // The givens only.
+    pub puzzle: Grid,
+    pub solution: Grid,
+    pub(crate) values: Vec<u8>,
+    pub(crate) pencil_marks: Vec<Vec<u8>>,
+    pub(crate) undo_stack: Vec<Edit>,
+    pub(crate) redo_stack: Vec<Edit>,
+    pub hint_count: usize,
+    pub last_hint: Option<Hint>,
+    // Time played in earlier stretches, not counting the current one.
+    pub(crate) elapsed_before: Duration,
+    // When the current stretch of play started, or None while paused.
+    pub(crate) resumed_at: Option<Instant>,
+}
+
+impl GameSession {
+
+    pub fn new(puzzle: &Grid) -> Result<Self, SudokuError> {
+        // The puzzle has to have a unique solution so that hints and checking have an answer to
+        // go by.
+        let solution_values = match UniquenessChecker::new(puzzle)?.check_grid(puzzle)? {
+            Uniqueness::Zero => return Err(SudokuError::Unsatisfiable { elapsed: Duration::from_secs(0), node_count: 0 }),
+            Uniqueness::One { solution } => solution,
+            uniqueness @ Uniqueness::Many { .. } => return Err(SudokuError::MultipleSolutions { differing_indexes: uniqueness.differing_indexes() }),
+        };
+        let mut solution = puzzle.clone();
+        solution.replace_values(&solution_values);
+        Ok(Self {
+            puzzle: puzzle.clone(),
+            solution,
+            values: puzzle.values.clone(),
+            pencil_marks: vec![vec![]; puzzle.cell_count as usize],
+            undo_stack: vec![],
+            redo_stack: vec![],
+            hint_count: 0,
+            last_hint: None,
+            elapsed_before: Duration::from_secs(0),
+            resumed_at: Some(Instant::now()),
+        })
+    }
+
+    pub fn cell_count(&self) -> u16 {
+        self.puzzle.cell_count
+    }
+
+    pub fn value(&self, index: u16) -> u8 {
+        self.values[index as usize]
+    }
+
+    pub fn values(&self) -> &[u8] {
+        &self.values
+    }
+
+    pub fn pencil_marks(&self, index: u16) -> &[u8] {
+        &self.pencil_marks[index as usize]
+    }
+
+    pub fn is_given(&self, index: u16) -> bool {
+        self.puzzle.values[index as usize] != NO_VALUE
+    }
+
+    pub fn set_value(&mut self, index: u16, value: u8) -> Result<(), SudokuError> {
+        // Enter a value, or clear the cell if value is NO_VALUE. Entering a value clears the
+        // cell's pencil marks.
+        self.check_editable(index)?;
+        if value > self.puzzle.max_value {
+            return Err(SudokuError::InvalidMove { index, reason: format!("Values go from 1 to {}.", self.puzzle.max_value) });
+        }
+        let after = CellState {
+            value,
+            pencil_marks: if value == NO_VALUE { self.pencil_marks[index as usize].clone() } else { vec![] },
+        };
+        self.apply_edit(index, after);
+        Ok(())
+    }
+
+    pub fn clear_value(&mut self, index: u16) -> Result<(), SudokuError> {
+        self.set_value(index, NO_VALUE)
+    }
+
+    pub fn toggle_pencil_mark(&mut self, index: u16, value: u8) -> Result<(), SudokuError> {
+        self.check_editable(index)?;
+        if value == NO_VALUE || value > self.puzzle.max_value {
+            return Err(SudokuError::InvalidMove { index, reason: format!("Values go from 1 to {}.", self.puzzle.max_value) });
+        }
+        if self.values[index as usize] != NO_VALUE {
+            return Err(SudokuError::InvalidMove { index, reason: "Pencil marks only go in empty cells.".to_string() });
+        }
+        let mut pencil_marks = self.pencil_marks[index as usize].clone();
+        match pencil_marks.binary_search(&value) {
+            Ok(position) => { pencil_marks.remove(position); },
+            Err(position) => pencil_marks.insert(position, value),
+        }
+        self.apply_edit(index, CellState { value: NO_VALUE, pencil_marks });
+        Ok(())
+    }
+
+    pub fn undo(&mut self) -> bool {
+        // Returns false if there was nothing to undo.
+        match self.undo_stack.pop() {
+            Some(edit) => {
+                self.restore(edit.index, &edit.before);
+                self.redo_stack.push(edit);
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn redo(&mut self) -> bool {
+        // Returns false if there was nothing to redo.
+        match self.redo_stack.pop() {
+            Some(edit) => {
+                self.restore(edit.index, &edit.after);
+                self.undo_stack.push(edit);
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn conflicts(&self) -> Vec<u16> {
+        // The filled cells whose value is also in a related cell, whether given or entered.
+        (0..self.puzzle.cell_count)
+            .filter(|index| {
+                let value = self.values[*index as usize];
+                value != NO_VALUE && self.puzzle.index_to_related_cell_indexes(*index)
+                    .iter()
+                    .any(|related_index| self.values[*related_index as usize] == value)
+            })
+            .collect()
+    }
+
+    pub fn mistakes(&self) -> Vec<u16> {
+        // The entered cells whose value doesn't match the solution.
+        (0..self.puzzle.cell_count)
+            .filter(|index| {
+                let value = self.values[*index as usize];
+                value != NO_VALUE && value != self.solution.values[*index as usize]
+            })
+            .collect()
+    }
+
+    pub fn validate(&self) -> Vec<Violation> {
+        // Every problem with the current entries, including the ones that break no rule but don't
+        // match the solution. See Grid::validate().
+        self.current_grid().validate_against_solution(&self.solution.values)
+    }
+
+    pub fn is_solved(&self) -> bool {
+        self.values == self.solution.values
+    }
+
+    pub fn hint(&mut self) -> Option<Hint> {
+        // Point out a mistake if there is one, otherwise find a single, otherwise give away the
+        // value of the empty cell with the fewest candidates. Returns None if the puzzle is solved.
+        let hint = self.find_hint();
+        if hint.is_some() {
+            self.hint_count += 1;
+        }
+        self.last_hint = hint.clone();
+        hint
+    }
+
+    pub fn graded_hint(&mut self, level: HintLevel) -> Result<HintOutcome, SudokuError> {
+        // A hint about the next logical step, revealing as much as the level asks for. See
+        // Grid::hint(). The candidates come from candidate_grid(), so an elimination applied with
+        // apply_step_action() isn't suggested again.
+        let outcome = self.candidate_grid().hint(level, Some(&self.puzzle))?;
+        if let HintOutcome::Step(_) = outcome {
+            self.hint_count += 1;
+        }
+        Ok(outcome)
+    }
+
+    pub fn apply_hint(&mut self, hint: &Hint) -> Result<(), SudokuError> {
+        self.set_value(hint.index, hint.value)
+    }
+
+    pub fn apply_step_action(&mut self, action: &StepAction) -> Result<(), SudokuError> {
+        // Carry out the action from a level 4 graded hint. An elimination is made by setting the
+        // pencil marks of each cell to its candidates without the eliminated values, since the
+        // pencil marks are where the session keeps candidates.
+        match action {
+            StepAction::Place { index, value } => self.set_value(*index, *value),
+            StepAction::Eliminate { eliminations } => {
+                for (index, _value) in eliminations.iter() {
+                    self.check_editable(*index)?;
+                }
+                let grid = self.candidate_grid();
+                for index in eliminations.iter().map(|(index, _value)| *index).unique() {
+                    let pencil_marks = grid.remaining_values(index).into_iter()
+                        .filter(|value| !eliminations.contains(&(index, *value)))
+                        .collect();
+                    self.apply_edit(index, CellState { value: NO_VALUE, pencil_marks });
+                }
+                Ok(())
+            },
+        }
+    }
+
+    fn find_hint(&self) -> Option<Hint> {
+        if let Some(index) = self.mistakes().first() {
+            return Some(Hint { index: *index, value: self.solution.values[*index as usize], reason: HintReason::Mistake });
+        }
+        let grid = self.current_grid();
+        if grid.unsolved_cell_count == 0 {
+            return None;
+        }
+        if let Some(index) = (0..grid.cell_count)
+                .find(|index| grid.values[*index as usize] == NO_VALUE && grid.remaining_value_counts[*index as usize] == 1) {
+            return Some(Hint { index, value: grid.one_remaining_value(index), reason: HintReason::NakedSingle });
+        }
+        for (house, house_indexes) in labelled_houses(&grid) {
+            if let SingleSearch::Found(index, value) = hidden_single(&grid, std::slice::from_ref(&house_indexes)) {
+                return Some(Hint { index, value, reason: HintReason::HiddenSingle(house) });
+            }
+        }
+        (0..grid.cell_count)
+            .filter(|index| grid.values[*index as usize] == NO_VALUE)
+            .min_by_key(|index| grid.remaining_value_counts[*index as usize])
+            .map(|index| Hint { index, value: self.solution.values[index as usize], reason: HintReason::FromSolution })
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        match self.resumed_at {
+            Some(resumed_at) => self.elapsed_before + resumed_at.elapsed(),
+            None => self.elapsed_before,
+        }
+    }
+
+    pub fn pause(&mut self) {
+        if let Some(resumed_at) = self.resumed_at.take() {
+            self.elapsed_before += resumed_at.elapsed();
+        }
+    }
+
+    pub fn resume(&mut self) {
+        if self.resumed_at.is_none() {
+            self.resumed_at = Some(Instant::now());
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.resumed_at.is_none()
+    }
+
+    pub fn current_grid(&self) -> Grid {
+        // The givens and entries as a grid, with remaining values worked out from the related
+        // cells rather than from the pencil marks.
+        let mut grid = self.puzzle.clone();
+        grid.replace_values(&self.values);
+        grid
+    }
+
+    pub fn candidate_grid(&self) -> Grid {
+        // Like current_grid(), but in an empty cell that has pencil marks the candidates are only
+        // those that are also pencil marks. A cell without pencil marks keeps all of its
+        // candidates.
+        let mut grid = self.current_grid();
+        for index in 0..grid.cell_count {
+            let pencil_marks = &self.pencil_marks[index as usize];
+            if grid.values[index as usize] == NO_VALUE && !pencil_marks.is_empty() {
+                for value in 1..=grid.max_value {
+                    if !pencil_marks.contains(&value) {
+                        grid.clear_remaining_value(index, value);
+                    }
+                }
+            }
+        }
+        grid
+    }
+
+    pub fn render(&self, use_color: bool) -> Result<String, SudokuError> {
+        // Draw the grid in the same style as Grid::print_simple_and_remaining(), with filled cells
+        // boxed and each empty cell showing its pencil marks. With color on, givens are bold,
+        // entries are cyan, conflicts are red, and the cell from the last hint is green.
+        let mut grid = self.current_grid();
+        for index in 0..grid.cell_count {
+            if grid.values[index as usize] == NO_VALUE {
+                for value in 1..=grid.max_value {
+                    grid.clear_remaining_value(index, value);
+                }
+                for value in self.pencil_marks[index as usize].iter() {
+                    grid.set_remaining_value(index, *value);
+                }
+            }
+        }
+        if !use_color {
+            return grid.remaining_values_string();
+        }
+        let conflicts = self.conflicts();
+        let hint_index = self.last_hint.as_ref().map(|hint| hint.index);
+        grid.remaining_values_string_styled(|index| {
+            if conflicts.contains(&index) {
+                Some(STYLE_CONFLICT)
+            } else if hint_index == Some(index) {
+                Some(STYLE_HINT)
+            } else if self.is_given(index) {
+                Some(STYLE_GIVEN)
+            } else if self.values[index as usize] != NO_VALUE {
+                Some(STYLE_ENTRY)
+            } else {
+                None
+            }
+        })
+    }
+
+    fn check_editable(&self, index: u16) -> Result<(), SudokuError> {
+        if index >= self.puzzle.cell_count {
+            return Err(SudokuError::InvalidMove { index, reason: format!("The grid has {} cells.", self.puzzle.cell_count) });
+        }
+        if self.is_given(index) {
+            return Err(SudokuError::InvalidMove { index, reason: "The cell is a given.".to_string() });
+        }
+        Ok(())
+    }
+
+    fn cell_state(&self, index: u16) -> CellState {
+        CellState {
+            value: self.values[index as usize],
+            pencil_marks: self.pencil_marks[index as usize].clone(),
+        }
+    }
+
+    fn apply_edit(&mut self, index: u16, after: CellState) {
+        // Record the edit for undo. A new edit makes anything that was undone impossible to redo.
+        let before = self.cell_state(index);
+        if before == after {
+            return;
+        }
+        self.restore(index, &after);
+        self.undo_stack.push(Edit { index, before, after });
+        self.redo_stack.clear();
+    }
+
+    fn restore(&mut self, index: u16, cell_state: &CellState) {
+        self.values[index as usize] = cell_state.value;
+        self.pencil_marks[index as usize] = cell_state.pencil_marks.clone();
+    }
+
+}
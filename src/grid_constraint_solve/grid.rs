@@ -5,20 +5,22 @@
 // - Custom symbols.
 
 use itertools::Itertools;
-use bit_vec::BitVec;
 // use std::collections::HashSet;
 use array2d::Array2D;
 // use std::hash::{Hash, Hasher};
 // use std::collections::hash_map::DefaultHasher;
-use std::ops::Range;
 //use std::fmt::{Display, Formatter, Error};
 // use std::time::Instant;
-use rand::{thread_rng, Rng};
+use rand::Rng;
 
 use crate::*;
 use super::*;
+use super::value_mask::RemainingValues;
+use super::diagnostics::{self, Checkpoint, diagnostic};
+use log::Level;
+use std::ops::Range;
 
-const VERBOSE: u8 = 0;
+const STYLE_RESET: &str = "\x1b[0m";
 
 pub fn main() {
 }
@@ -48,7 +50,7 @@ pub struct Grid {
     pub remaining_value_counts: Vec<u8>,
     // remaining_value_counts_map: Vec<HashSet<u8>>,
     #[derivative(Debug="ignore")]
-    pub remaining_values: BitVec,
+    pub remaining_values: RemainingValues,
     #[derivative(Debug="ignore")]
     pub related_cell_indexes: Vec<u16>,
 }
@@ -74,7 +76,7 @@ impl Grid {
             remaining_value_counts.push(max_value);
         }
 
-        let remaining_values = BitVec::from_elem(max_value as usize * cell_count as usize, true);
+        let remaining_values = RemainingValues::new(cell_count, max_value);
 
         let symbols = gen_char_array(if max_value <= 9 {
             SYMBOLS_STANDARD
@@ -104,21 +106,21 @@ impl Grid {
             remaining_values,
             related_cell_indexes: vec![],
         };
-        if VERBOSE >= 1 { dbg!(&grid); }
-        if RUN_INVARIANT { grid.invariant(); }
+        diagnostic!(Level::Trace, event = "grid_new", width = grid.width, height = grid.height; "{:?}", grid);
+        if diagnostics::checks(Checkpoint::GridChange) { grid.invariant(); }
         grid
     }
 
     pub fn symbols(mut self, symbols: &str) -> Self {
-        if RUN_INVARIANT { self.invariant(); }
+        if diagnostics::checks(Checkpoint::GridChange) { self.invariant(); }
         self.symbols = gen_char_array(symbols);
-        if RUN_INVARIANT { self.invariant(); }
+        if diagnostics::checks(Checkpoint::GridChange) { self.invariant(); }
         self
     }
 
     #[inline]
     pub(crate) fn row_col_block(&self, index: u16) -> (u8, u8, u8) {
-        if RUN_INVARIANT { self.invariant(); }
+        if diagnostics::checks(Checkpoint::GridChange) { self.invariant(); }
         let row = index / self.width as u16;
         let col = index % self.width as u16;
         let block = ((row / self.block_height as u16) * self.block_col_count as u16) + (col / self.block_width as u16);
@@ -127,7 +129,7 @@ impl Grid {
 
     #[inline]
     pub(crate) fn set_value(&mut self, index: u16, value: u8) {
-        if RUN_INVARIANT { self.invariant(); }
+        if diagnostics::checks(Checkpoint::GridChange) { self.invariant(); }
         let current_value = self.values[index as usize];
         if value == current_value {
             return;
@@ -142,27 +144,27 @@ impl Grid {
         for related_cell_index in self.index_to_related_cell_indexes(index) {
             self.recalc_remaining_values_one_cell(related_cell_index);
         }
-        if RUN_INVARIANT { self.invariant(); }
+        if diagnostics::checks(Checkpoint::GridChange) { self.invariant(); }
     }
 
     #[inline]
     pub fn remove_cells(&mut self, remove_cell_count: u16) {
-        if RUN_INVARIANT { self.invariant(); }
+        if diagnostics::checks(Checkpoint::GridChange) { self.invariant(); }
         let mut indexes = (0..self.cell_count)
             .filter(|index| self.values[*index as usize] != NO_VALUE)
             .collect::<Vec<_>>();
         let remove_cell_count = remove_cell_count.min(indexes.len() as u16);
         for _ in 0..remove_cell_count.min(self.cell_count) {
-            let index = indexes.remove(thread_rng().gen_range(0, indexes.len()));
+            let index = indexes.remove(rng().gen_range(0, indexes.len()));
             self.values[index as usize] = NO_VALUE;
             self.unsolved_cell_count += 1;
         }
         self.recalc_remaining_values();
-        if RUN_INVARIANT { self.invariant(); }
+        if diagnostics::checks(Checkpoint::GridChange) { self.invariant(); }
     }
 
     #[inline]
-    fn recalc_remaining_values(&mut self) {
+    pub(crate) fn recalc_remaining_values(&mut self) {
         for index in 0..self.cell_count {
             self.recalc_remaining_values_one_cell(index);
         }
@@ -170,46 +172,47 @@ impl Grid {
 
     #[inline]
     fn recalc_remaining_values_one_cell(&mut self, index: u16) {
-        if self.values[index as usize] == NO_VALUE {
-            let related_cell_values = self.index_to_related_cell_indexes(index)
+        let old_count = self.remaining_value_counts[index as usize];
+        let new_count = if self.values[index as usize] == NO_VALUE {
+            // The related cell list is padded with the cell's own index, which has no value here.
+            let related_cell_indexes: &[u16] = if self.related_cell_indexes.is_empty() {
+                &[]
+            } else {
+                &self.related_cell_indexes[self.related_cell_range(index)]
+            };
+            let values = &self.values;
+            let related_cell_values = related_cell_indexes
                 .iter()
-                .map(|related_cell_index| self.values[*related_cell_index as usize])
-                .collect::<HashSet<_>>();
-            for value in 1..=self.max_value {
-                if related_cell_values.contains(&value) {
-                    self.clear_remaining_value(index, value);
-                } else {
-                    self.set_remaining_value(index, value);
-                }
-            }
+                .map(|related_cell_index| values[*related_cell_index as usize]);
+            self.remaining_values.recalc_cell(index, self.max_value, related_cell_values) as u8
         } else {
-            self.clear_remaining_values(index);
-        }
+            self.remaining_values.clear_cell(index);
+            0
+        };
+        self.remaining_value_counts[index as usize] = new_count;
+        self.remaining_value_count = self.remaining_value_count + new_count as u32 - old_count as u32;
     }
 
     #[inline]
     fn clear_remaining_values(&mut self, index: u16) {
-        // if RUN_INVARIANT { self.invariant(); }
-        for value in 1..=self.max_value {
-            self.clear_remaining_value(index, value);
-        }
+        // if diagnostics::checks(Checkpoint::GridChange) { self.invariant(); }
+        let cleared_count = self.remaining_values.clear_cell(index);
+        self.remaining_value_counts[index as usize] = 0;
+        self.remaining_value_count -= cleared_count;
     }
 
     #[inline]
     pub fn has_remaining_value(&self, index: u16, value: u8) -> bool {
-        let remaining_value_index = self.remaining_value_index(index, value);
-        self.remaining_values[remaining_value_index]
+        self.remaining_values.has(index, value)
     }
 
     #[inline]
     pub fn set_remaining_value(&mut self, index: u16, value: u8) -> bool {
         // Returns true if the remaining value was not already in the cell and thus was added.
-        let remaining_value_index = self.remaining_value_index(index, value);
-        if !self.remaining_values[remaining_value_index] {
-            self.remaining_values.set(remaining_value_index, true);
+        if self.remaining_values.set(index, value) {
             self.remaining_value_counts[index as usize] += 1;
             self.remaining_value_count += 1;
-            if RUN_INVARIANT { self.invariant(); }
+            if diagnostics::checks(Checkpoint::GridChange) { self.invariant(); }
             true
         } else {
             false
@@ -221,9 +224,7 @@ impl Grid {
         // Returns true if the remaining value was in the cell and thus was removed.
         // Don't run the invariant here because this function will be called after setting the
         // value for some other cell. Thus the current cell may have extra remaining values.
-        let remaining_value_index = self.remaining_value_index(index, value);
-        if self.remaining_values[remaining_value_index] {
-            self.remaining_values.set(remaining_value_index, false);
+        if self.remaining_values.clear(index, value) {
             self.remaining_value_counts[index as usize] -= 1;
             self.remaining_value_count -= 1;
             true
@@ -232,28 +233,12 @@ impl Grid {
         }
     }
 
-    #[inline]
-    fn remaining_value_index(&self, index: u16, value: u8) -> usize {
-        ((index as usize * self.max_value as usize) + value as usize) - 1
-    }
-
     #[inline]
     pub fn one_remaining_value(&self, index: u16) -> u8 {
-        if RUN_INVARIANT { self.invariant(); }
+        if diagnostics::checks(Checkpoint::GridChange) { self.invariant(); }
         debug_assert_eq!(1, self.remaining_value_counts[index as usize]);
 
-        // Within the overall self.remaining_values vector there is a range dedicated to this cell.
-        // It has one boolean entry for each possible value.
-        let remaining_value_range = self.remaining_value_range(index);
-        let range_start = remaining_value_range.start;
-        for remaining_value_index in remaining_value_range {
-            if self.remaining_values[remaining_value_index] {
-                // This cell has this value marked as being a possible value.
-                let value = (remaining_value_index - range_start) + 1;
-                return value as u8
-            }
-        }
-        unreachable!()
+        self.remaining_values.lowest_value(index).unwrap()
     }
 
     #[inline]
@@ -273,24 +258,25 @@ impl Grid {
 
     #[inline]
     pub(crate) fn remaining_values(&self, index: u16) -> Vec<u8> {
-        if RUN_INVARIANT { self.invariant(); }
-        let mut v = vec![];
-        let remaining_value_range = self.remaining_value_range(index);
-        let range_start = remaining_value_range.start;
-        for remaining_value_index in remaining_value_range {
-            if self.remaining_values[remaining_value_index] {
-                let value = (remaining_value_index - range_start) as u8 + 1;
-                v.push(value);
-            }
-        }
-        v
+        if diagnostics::checks(Checkpoint::GridChange) { self.invariant(); }
+        self.remaining_values.values(index)
     }
 
-    #[inline]
-    pub(crate) fn remaining_value_range(&self, index: u16) -> Range<usize> {
-        let start_index = index as usize * self.max_value as usize;
-        let end_index = start_index + self.max_value as usize;
-        Range { start: start_index, end: end_index }
+    pub(crate) fn houses(&self) -> Vec<Vec<u16>> {
+        // The rows, columns, and blocks whose cells are all related to each other, so that each
+        // value can appear at most once in them. A grid that hasn't been through a builder has
+        // no related cells and so no houses.
+        let mut houses = self.all_houses()
+            .into_iter()
+            .map(|house| self.house_indexes(house).unwrap())
+            .collect::<Vec<_>>();
+        houses.retain(|house| {
+            house.iter().all(|index| {
+                let related_cell_indexes = self.index_to_related_cell_indexes(*index);
+                house.iter().all(|other_index| other_index == index || related_cell_indexes.contains(other_index))
+            })
+        });
+        houses
     }
 
     #[inline]
@@ -308,7 +294,7 @@ impl Grid {
         // in the new grid taken from the old grid. If include is false, this is a list of indexes
         // to exclude meaning their corresponding cells will be left empty while the others will be
         // filled.
-        if RUN_INVARIANT { self.invariant(); }
+        if diagnostics::checks(Checkpoint::GridChange) { self.invariant(); }
         let mut grid = self.clone_empty();
         for index in 0..self.cell_count {
             let in_list = indexes.contains(&index);
@@ -323,18 +309,18 @@ impl Grid {
             indexes.len() as u16
         };
         grid.recalc_remaining_values();
-        if RUN_INVARIANT { grid.invariant(); }
+        if diagnostics::checks(Checkpoint::GridChange) { grid.invariant(); }
         grid
     }
 
     pub fn clone_empty(&self) -> Self {
-        if RUN_INVARIANT { self.invariant(); }
+        if diagnostics::checks(Checkpoint::GridChange) { self.invariant(); }
         let mut grid = Self::new(self.width, self.height, self.block_width, self.block_height);
         //bg!(&self.symbols, &self.related_cell_indexes);
         grid.symbols = self.symbols.clone();
         grid.related_cell_indexes = self.related_cell_indexes.clone();
         grid.max_related_cell_count = self.max_related_cell_count;
-        if RUN_INVARIANT { grid.invariant(); }
+        if diagnostics::checks(Checkpoint::GridChange) { grid.invariant(); }
         grid
     }
 
@@ -343,7 +329,7 @@ impl Grid {
         let mut grid = self.clone_empty();
         grid.values = values;
         grid.recalc_remaining_values();
-        if RUN_INVARIANT { grid.invariant(); }
+        if diagnostics::checks(Checkpoint::GridChange) { grid.invariant(); }
         grid
     }
 
@@ -356,7 +342,7 @@ impl Grid {
             .count() as u16;
         self.values = values.to_vec();
         self.recalc_remaining_values();
-        if RUN_INVARIANT { self.invariant(); }
+        if diagnostics::checks(Checkpoint::GridChange) { self.invariant(); }
     }
 
     #[inline]
@@ -372,6 +358,20 @@ impl Grid {
         indexes
     }
 
+    pub(crate) fn set_related_cell_indexes(&mut self, related_cell_index_lists: &[Vec<u16>]) {
+        // Lay out the lists the way Builder does, padding each cell's slots with its own index so
+        // that every cell has max_related_cell_count of them.
+        debug_assert_eq!(self.cell_count as usize, related_cell_index_lists.len());
+        self.max_related_cell_count = related_cell_index_lists.iter().map(|list| list.len()).max().unwrap_or(0) as u8;
+        self.related_cell_indexes = Vec::with_capacity(self.cell_count as usize * self.max_related_cell_count as usize);
+        for (index, related_index_list) in related_cell_index_lists.iter().enumerate() {
+            self.related_cell_indexes.extend(related_index_list.iter().copied());
+            for _ in related_index_list.len()..self.max_related_cell_count as usize {
+                self.related_cell_indexes.push(index as u16);
+            }
+        }
+    }
+
     pub fn print_simple(&self, label: &str) {
         // Don't run the invariant because we might be displaying the grid to illustrate something
         // that has broken the invariant.
@@ -432,44 +432,41 @@ impl Grid {
     }
 
     fn print_remaining_values(&self) {
-        let cell_row_padding: usize = 1;
-        let cell_col_padding: usize = 3;
-        let block_row_padding: usize = 3;
-        let block_col_padding: usize = 5;
-        let (cell_width, cell_height, completed_template) = if self.max_value <= 4 {
-            (2, 2, "#│─┘")
-        } else if self.max_value <= 6 {
-            (3, 2, "│#│└─┘")
-        } else if self.max_value <= 9 {
-            (3, 3, "┌─┐│#│└─┘")
-        } else if self.max_value <= 12 {
-            (4, 3, "┌──┐│# │└──┘")
-        } else if self.max_value <= 16 {
-            (4, 4, "┌──┐│# ││  │└──┘")
-        } else {
-            panic!()
-        };
-        let num_rows: usize = (cell_height * self.height as usize) + (cell_row_padding as usize * (self.height as usize - 1)) + (block_row_padding * (self.block_row_count as usize - 1));
-        let num_cols: usize = (cell_width * self.width as usize) + (cell_col_padding * (self.width as usize - 1)) + (block_col_padding * (self.block_col_count as usize - 1));
-        //bg!(self.grid_width, self.block_col_count, cell_width, cell_col_padding, block_col_padding, num_cols);
-        // let num_rows = num_rows * 2;
-        // let num_cols = num_cols * 2;
-        let mut ar = Array2D::filled_with(" ".to_string(), num_rows as usize, num_cols as usize);
+        match self.remaining_values_string() {
+            Ok(text) => {
+                println!();
+                println!("{}", text);
+                println!();
+            },
+            Err(error) => println!("{}", error),
+        }
+    }
+
+    pub fn remaining_values_string(&self) -> Result<String, SudokuError> {
+        // Each cell is a small block of characters. A solved cell is drawn as a box around its
+        // value and an unsolved cell shows each remaining value in its own position, with
+        // SYMBOL_NO_VALUE for values that have been ruled out. set_remaining_values_from_string()
+        // reads this back.
+        self.remaining_values_string_styled(|_index| None)
+    }
+
+    pub(crate) fn remaining_values_string_styled<F>(&self, style: F) -> Result<String, SudokuError>
+        where F: Fn(u16) -> Option<&'static str>
+    {
+        // Like remaining_values_string() but each character of a cell is wrapped in the terminal
+        // escape code that style() returns for the cell, if any.
+        let layout = self.remaining_values_layout()?;
+        let mut ar = Array2D::filled_with(" ".to_string(), layout.num_rows, layout.num_cols);
         for index in 0..self.cell_count as usize {
-            let (row, col, _block) = row_col_block(index as u16, self.width, self.block_width, self.block_height, self.block_col_count);
-            let cell_x: usize = (col as usize * (cell_width + cell_col_padding)) + (self.block_col_index(col) as usize * block_col_padding) as usize;
-            let cell_y: usize = (row as usize * (cell_height + cell_row_padding)) + (self.block_row_index(row) as usize * block_row_padding) as usize;
-            let range = self.remaining_value_range(index as u16);
-            let range_start = range.start;
+            let (cell_x, cell_y) = layout.cell_position(self, index as u16);
             let cell_value = self.values[index];
             let internal_values = if cell_value > 0 {
-                completed_template.replace("#", &self.get_symbol(cell_value).to_string())
+                layout.completed_template.replace("#", &self.get_symbol(cell_value).to_string())
             } else {
                 let mut s = "".to_string();
-                for remaining_value_index in range {
-                    let offset = remaining_value_index - range_start;
-                    let value = if self.remaining_values[remaining_value_index] {
-                        offset as u8 + 1
+                for value in 1..=self.max_value {
+                    let value = if self.has_remaining_value(index as u16, value) {
+                        value
                     } else {
                         0
                     };
@@ -478,19 +475,121 @@ impl Grid {
                 }
                 s
             };
-            //bg!(&internal_values, internal_values.len());
+            let cell_style = style(index as u16);
             for (offset, c) in internal_values.chars().enumerate() {
-                let x = cell_x + (offset % cell_width);
-                let y = cell_y + (offset / cell_width);
-                ar.set(y, x, c.to_string()).unwrap();
+                let x = cell_x + (offset % layout.cell_width);
+                let y = cell_y + (offset / layout.cell_width);
+                let c = match cell_style {
+                    Some(cell_style) => format!("{}{}{}", cell_style, c, STYLE_RESET),
+                    None => c.to_string(),
+                };
+                ar.set(y, x, c).unwrap();
             }
         }
-        println!();
-        for mut row in ar.rows_iter() {
-            let row_string = row.join("");
-            println!("{}", row_string);
+        let rows = ar.rows_iter()
+            .map(|row| row.map(|s| s.as_str()).collect::<String>())
+            .collect::<Vec<_>>();
+        Ok(rows.join("\n"))
+    }
+
+    pub fn set_remaining_values_from_string(&mut self, text: &str) -> Result<(), SudokuError> {
+        // Replace the values and remaining values with those in text, which should be in the form
+        // written by remaining_values_string() for a grid of this size with the same symbols.
+        // Trailing spaces may be missing and there may be blank lines before and after the grid.
+        let layout = self.remaining_values_layout()?;
+        let mut lines = text.lines().map(|line| line.chars().collect::<Vec<_>>()).collect::<Vec<_>>();
+        while lines.len() > layout.num_rows && lines[0].iter().all(|c| c.is_whitespace()) {
+            lines.remove(0);
+        }
+        while lines.len() > layout.num_rows && lines[lines.len() - 1].iter().all(|c| c.is_whitespace()) {
+            lines.pop();
+        }
+        if lines.len() != layout.num_rows {
+            return Err(self.parse_error(format!("Expected {} lines but found {}.", layout.num_rows, lines.len())));
+        }
+        let char_at = |x: usize, y: usize| *lines[y].get(x).unwrap_or(&' ');
+
+        let mut values = vec![NO_VALUE; self.cell_count as usize];
+        let mut remaining_values = vec![];
+        for index in 0..self.cell_count {
+            let (cell_x, cell_y) = layout.cell_position(self, index);
+            let cell_chars = (0..layout.cell_width * layout.cell_height)
+                .map(|offset| char_at(cell_x + (offset % layout.cell_width), cell_y + (offset / layout.cell_width)))
+                .collect::<Vec<_>>();
+            let is_solved = layout.completed_template
+                .chars()
+                .zip(cell_chars.iter())
+                .all(|(template_char, cell_char)| template_char == '#' || template_char == *cell_char);
+            if is_solved {
+                let symbol_offset = layout.completed_template.chars().position(|c| c == '#').unwrap();
+                values[index as usize] = self.symbol_to_value(cell_chars[symbol_offset])
+                    .ok_or_else(|| self.parse_error(format!("Cell index {} has unknown symbol '{}'.", index, cell_chars[symbol_offset])))?;
+            } else {
+                for value in 1..=self.max_value {
+                    let c = cell_chars[value as usize - 1];
+                    if c == self.get_symbol(value) {
+                        remaining_values.push((index, value));
+                    } else if c != SYMBOL_NO_VALUE && !c.is_whitespace() {
+                        return Err(self.parse_error(format!("Cell index {} has '{}' where value {} or '{}' should be.", index, c, self.get_symbol(value), SYMBOL_NO_VALUE)));
+                    }
+                }
+            }
+        }
+
+        self.replace_values(&values);
+        for index in 0..self.cell_count {
+            if self.values[index as usize] == NO_VALUE {
+                for value in 1..=self.max_value {
+                    self.clear_remaining_value(index, value);
+                }
+            }
+        }
+        for (index, value) in remaining_values {
+            self.set_remaining_value(index, value);
         }
-        println!();
+        if diagnostics::checks(Checkpoint::GridChange) { self.invariant(); }
+        Ok(())
+    }
+
+    fn remaining_values_layout(&self) -> Result<RemainingValuesLayout, SudokuError> {
+        let cell_row_padding: usize = 1;
+        let cell_col_padding: usize = 3;
+        let block_row_padding: usize = 3;
+        let block_col_padding: usize = 5;
+        let (cell_width, cell_height, completed_template) = if self.max_value <= 4 {
+            (2, 2, "#│─┘")
+        } else if self.max_value <= 6 {
+            (3, 2, "│#│└─┘")
+        } else if self.max_value <= 9 {
+            (3, 3, "┌─┐│#│└─┘")
+        } else if self.max_value <= 12 {
+            (4, 3, "┌──┐│# │└──┘")
+        } else if self.max_value <= 16 {
+            (4, 4, "┌──┐│# ││  │└──┘")
+        } else {
+            return Err(SudokuError::InvalidDimensions {
+                width: self.width,
+                height: self.height,
+                block_width: self.block_width,
+                block_height: self.block_height,
+                reason: "Remaining values can only be shown for grids with up to 16 values.".to_string(),
+            });
+        };
+        let num_rows: usize = (cell_height * self.height as usize) + (cell_row_padding * (self.height as usize - 1)) + (block_row_padding * (self.block_row_count as usize - 1));
+        let num_cols: usize = (cell_width * self.width as usize) + (cell_col_padding * (self.width as usize - 1)) + (block_col_padding * (self.block_col_count as usize - 1));
+        Ok(RemainingValuesLayout { cell_row_padding, cell_col_padding, block_row_padding, block_col_padding, cell_width, cell_height, completed_template, num_rows, num_cols })
+    }
+
+    pub(crate) fn symbol_to_value(&self, symbol: char) -> Option<u8> {
+        self.symbols
+            .iter()
+            .position(|c| *c == symbol)
+            .map(|position| position as u8 + 1)
+            .filter(|value| *value <= self.max_value)
+    }
+
+    fn parse_error(&self, reason: String) -> SudokuError {
+        SudokuError::InvalidText { reason }
     }
 
     pub fn debug_cell_and_related(&self, label: &str, index: u16) {
@@ -569,8 +668,7 @@ impl Grid {
         let calc_remaining_value_count: u32 = self.remaining_value_counts.iter().map(|x| *x as u32).sum::<u32>();
         assert_eq!(self.remaining_value_count, calc_remaining_value_count);
 
-        let calc_remaining_value_bit_count = self.cell_count as usize * self.max_value as usize;
-        assert_eq!(calc_remaining_value_bit_count, self.remaining_values.len());
+        assert_eq!(self.cell_count as usize, self.remaining_values.cell_count());
 
         if !related_cell_indexes.is_empty() {
             let calc_related_cells_total_count = self.cell_count as usize * self.max_related_cell_count as usize;
@@ -586,17 +684,9 @@ impl Grid {
             let remaining_value_count = self.remaining_value_counts[index];
             assert!(remaining_value_count <= self.max_value);
 
-            {
-                let mut calc_remaining_value_count = 0;
-                let start_index = index as usize * self.max_value as usize;
-                let end_index = start_index + self.max_value as usize;
-                for remaining_value_index in start_index..end_index {
-                    if self.remaining_values[remaining_value_index] {
-                        calc_remaining_value_count += 1;
-                    }
-                }
-                assert_eq!(calc_remaining_value_count, remaining_value_count);
-            }
+            assert_eq!(self.remaining_values.count(index as u16), remaining_value_count as u32);
+            // No value past max_value should ever be marked as possible.
+            assert!(self.remaining_values.values(index as u16).iter().all(|value| *value <= self.max_value));
 
             if value == NO_VALUE {
                 // The cell has not been filled so there should be at least one possible value.
@@ -624,8 +714,7 @@ impl Grid {
                                 self.print_simple_and_remaining(&error_message);
                                 panic!(error_message);
                             }
-                            let remaining_value_index = ((related_cell_index * self.max_value as usize) + value as usize) - 1;
-                            if self.remaining_values[remaining_value_index] {
+                            if self.remaining_values.has(related_cell_index as u16, value) {
                                 let error_message = format!("{} has a remaining value found in {}.", self.cell_display_for_invariant(related_cell_index as u16), self.cell_display_for_invariant(index as u16));
                                 self.print_simple_and_remaining(&error_message);
                                 panic!(error_message);
@@ -673,6 +762,28 @@ impl Grid {
 
 }
 
+struct RemainingValuesLayout {
+    cell_row_padding: usize,
+    cell_col_padding: usize,
+    block_row_padding: usize,
+    block_col_padding: usize,
+    cell_width: usize,
+    cell_height: usize,
+    completed_template: &'static str,
+    num_rows: usize,
+    num_cols: usize,
+}
+
+impl RemainingValuesLayout {
+    fn cell_position(&self, grid: &Grid, index: u16) -> (usize, usize) {
+        // The column and row of the top left character of the cell.
+        let (row, col, _block) = row_col_block(index, grid.width, grid.block_width, grid.block_height, grid.block_col_count);
+        let cell_x: usize = (col as usize * (self.cell_width + self.cell_col_padding)) + (grid.block_col_index(col) as usize * self.block_col_padding);
+        let cell_y: usize = (row as usize * (self.cell_height + self.cell_row_padding)) + (grid.block_row_index(row) as usize * self.block_row_padding);
+        (cell_x, cell_y)
+    }
+}
+
 fn row_col_block(index: u16, grid_width: u8, block_width: u8, block_height: u8, block_col_count: u8) -> (u8, u8, u8) {
     let row = index / grid_width as u16;
     let col = index % grid_width as u16;
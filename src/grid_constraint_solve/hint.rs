@@ -0,0 +1,379 @@
+#![allow(dead_code)]
+
+// Hints that point the player at the next logical step rather than giving away the solution. The
+// step comes from a few human solving techniques applied to the grid's candidates, and the hint
+// level decides how much of it to reveal:
+//   1. the row, column, or block to look at,
+//   2. plus the name of the technique,
+//   3. plus the cells involved,
+//   4. plus the value to place or the candidates to eliminate.
+// Before looking for a step the grid is checked against its unique solution, so a wrong entry or a
+// wrongly removed candidate is reported instead of a step built on top of it.
+// The techniques work from related_cell_indexes rather than assuming rows, columns, and blocks, so
+// they stay correct for grids with extra rules such as RULE_KNIGHT.
+// This goes with grid::Grid and game::GameSession.
+
+use crate::*;
+use super::grid::Grid;
+use super::builder::Builder;
+use super::solver::Solver;
+use super::candidates::House;
+use super::backdoor::{hidden_single, SingleSearch};
+use super::unique::{Uniqueness, UniquenessChecker};
+use super::SudokuError;
+use itertools::Itertools;
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+
+// Naked subsets are tried up to this size, so pairs and triples.
+const MAX_NAKED_SUBSET_SIZE: usize = 3;
+
+pub fn main() {
+    try_graded_hints();
+}
+
+fn try_graded_hints() {
+    // Solve a puzzle using nothing but level 4 hints, showing the first hint at each level along
+    // the way.
+    let grid = Builder::with_size(9).build().unwrap();
+    let puzzle = Solver::new(&grid).reduce_minimal().unwrap().grid;
+    for level in HintLevel::all().iter() {
+        println!("try_graded_hints(): level {}: {:?}", *level as u8, puzzle.hint(*level, None).unwrap());
+    }
+    let mut current = puzzle.clone();
+    let mut step_count = 0;
+    let mut technique_counts: HashMap<Technique, usize> = HashMap::new();
+    loop {
+        match current.hint(HintLevel::Action, Some(&puzzle)).unwrap() {
+            HintOutcome::Step(hint) => {
+                let step = current.next_step().unwrap();
+                *technique_counts.entry(step.technique).or_insert(0) += 1;
+                step.apply(&mut current);
+                step_count += 1;
+                if step_count == 1 {
+                    println!("try_graded_hints(): {}", hint);
+                }
+            },
+            outcome => {
+                println!("try_graded_hints(): stopped with {:?} after {} steps: {:?}", outcome, step_count, technique_counts);
+                break;
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum HintLevel {
+    Region = 1,
+    Technique = 2,
+    Cells = 3,
+    Action = 4,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Technique {
+    NakedSingle,
+    HiddenSingle,
+    // A value whose candidates in a house all see some cell outside the house, so the value can't
+    // go in that cell. In the standard rules this covers both pointing and claiming.
+    LockedCandidates,
+    // As many cells in a house as there are values among their candidates, so those values can't
+    // go in any cell that sees all of them. The number is the size: 2 for a pair, 3 for a triple.
+    NakedSubset(u8),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StepAction {
+    Place { index: u16, value: u8 },
+    // Pairs of cell index and the candidate to remove from it.
+    Eliminate { eliminations: Vec<(u16, u8)> },
+}
+
+#[derive(Clone, Debug)]
+pub struct LogicalStep {
+    pub technique: Technique,
+    pub region: House,
+    pub cells: Vec<u16>,
+    pub action: StepAction,
+}
+
+// The parts of a LogicalStep revealed at a given level. Anything above the level is None.
+#[derive(Clone, Debug)]
+pub struct GradedHint {
+    pub level: HintLevel,
+    pub region: House,
+    pub technique: Option<Technique>,
+    pub cells: Option<Vec<u16>>,
+    pub action: Option<StepAction>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Contradiction {
+    // Filled cells whose value isn't the one in the unique solution.
+    WrongValues { indexes: Vec<u16> },
+    // Empty cells whose candidates no longer include the value in the unique solution.
+    MissingCandidates { indexes: Vec<u16> },
+    // The grid has no solution at all. This is only reported when there's no puzzle to tell the
+    // givens from the entries, so the cells at fault aren't known.
+    NoSolution,
+}
+
+#[derive(Clone, Debug)]
+pub enum HintOutcome {
+    Solved,
+    Contradiction(Contradiction),
+    Step(GradedHint),
+    // None of the techniques apply, so the next step would take a guess or a harder technique.
+    Stuck,
+}
+
+impl HintLevel {
+    pub fn all() -> [HintLevel; 4] {
+        [HintLevel::Region, HintLevel::Technique, HintLevel::Cells, HintLevel::Action]
+    }
+
+    pub fn from_number(number: u8) -> Option<HintLevel> {
+        HintLevel::all().iter().copied().find(|level| *level as u8 == number)
+    }
+}
+
+impl Display for Technique {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Technique::NakedSingle => write!(f, "naked single"),
+            Technique::HiddenSingle => write!(f, "hidden single"),
+            Technique::LockedCandidates => write!(f, "locked candidates"),
+            Technique::NakedSubset(2) => write!(f, "naked pair"),
+            Technique::NakedSubset(3) => write!(f, "naked triple"),
+            Technique::NakedSubset(size) => write!(f, "naked subset of {}", size),
+        }
+    }
+}
+
+impl Display for GradedHint {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        // Rows, columns, and blocks are numbered from 1 here since this is for the player.
+        let (house_kind, house_number) = match self.region {
+            House::Row(row) => ("row", row),
+            House::Column(column) => ("column", column),
+            House::Block(block) => ("block", block),
+        };
+        write!(f, "Look at {} {}", house_kind, house_number + 1)?;
+        if let Some(technique) = self.technique {
+            write!(f, " for a {}", technique)?;
+        }
+        if let Some(cells) = &self.cells {
+            write!(f, " in cell indexes {:?}", cells)?;
+        }
+        match &self.action {
+            Some(StepAction::Place { index, value }) => write!(f, ": cell index {} is {}", index, value)?,
+            Some(StepAction::Eliminate { eliminations }) => write!(f, ": remove (cell index, value) {:?}", eliminations)?,
+            None => {},
+        }
+        write!(f, ".")
+    }
+}
+
+impl LogicalStep {
+    pub fn graded(&self, level: HintLevel) -> GradedHint {
+        GradedHint {
+            level,
+            region: self.region,
+            technique: if level >= HintLevel::Technique { Some(self.technique) } else { None },
+            cells: if level >= HintLevel::Cells { Some(self.cells.clone()) } else { None },
+            action: if level >= HintLevel::Action { Some(self.action.clone()) } else { None },
+        }
+    }
+
+    pub fn apply(&self, grid: &mut Grid) {
+        match &self.action {
+            StepAction::Place { index, value } => grid.set_value(*index, *value),
+            StepAction::Eliminate { eliminations } => {
+                for (index, value) in eliminations.iter() {
+                    grid.clear_remaining_value(*index, *value);
+                }
+            },
+        }
+    }
+}
+
+impl Grid {
+
+    pub fn hint(&self, level: HintLevel, puzzle: Option<&Grid>) -> Result<HintOutcome, SudokuError> {
+        // The puzzle, if there is one, holds just the givens. It's used to find the unique
+        // solution so that wrong entries can be pointed out one by one. Without it the grid itself
+        // is checked, which can only tell that something is wrong, not where.
+        let solution = match puzzle {
+            Some(puzzle) => match UniquenessChecker::new(puzzle)?.check_grid(puzzle)? {
+                Uniqueness::Zero => return Err(SudokuError::Unsatisfiable { elapsed: Default::default(), node_count: 0 }),
+                Uniqueness::One { solution } => Some(solution),
+                uniqueness @ Uniqueness::Many { .. } => return Err(SudokuError::MultipleSolutions { differing_indexes: uniqueness.differing_indexes() }),
+            },
+            None => match UniquenessChecker::new(self)?.check_grid(self)? {
+                Uniqueness::Zero => return Ok(HintOutcome::Contradiction(Contradiction::NoSolution)),
+                Uniqueness::One { solution } => Some(solution),
+                // The grid is short of givens, but the techniques can still find steps.
+                Uniqueness::Many { .. } => None,
+            },
+        };
+        if let Some(solution) = &solution {
+            if let Some(contradiction) = self.contradiction(solution) {
+                return Ok(HintOutcome::Contradiction(contradiction));
+            }
+        }
+        if self.unsolved_cell_count == 0 {
+            return Ok(HintOutcome::Solved);
+        }
+        Ok(match self.next_step() {
+            Some(step) => HintOutcome::Step(step.graded(level)),
+            None => HintOutcome::Stuck,
+        })
+    }
+
+    pub fn contradiction(&self, solution: &[u8]) -> Option<Contradiction> {
+        let wrong_indexes = (0..self.cell_count)
+            .filter(|index| {
+                let value = self.values[*index as usize];
+                value != NO_VALUE && value != solution[*index as usize]
+            })
+            .collect::<Vec<_>>();
+        if !wrong_indexes.is_empty() {
+            return Some(Contradiction::WrongValues { indexes: wrong_indexes });
+        }
+        let missing_indexes = (0..self.cell_count)
+            .filter(|index| self.values[*index as usize] == NO_VALUE && !self.has_remaining_value(*index, solution[*index as usize]))
+            .collect::<Vec<_>>();
+        if !missing_indexes.is_empty() {
+            return Some(Contradiction::MissingCandidates { indexes: missing_indexes });
+        }
+        None
+    }
+
+    pub fn next_step(&self) -> Option<LogicalStep> {
+        // Try the techniques from easiest to hardest and return the first step that places a value
+        // or removes at least one candidate.
+        if let Some(index) = (0..self.cell_count)
+                .find(|index| self.values[*index as usize] == NO_VALUE && self.remaining_value_counts[*index as usize] == 1) {
+            let (_, _, block) = self.row_col_block(index);
+            return Some(LogicalStep {
+                technique: Technique::NakedSingle,
+                region: House::Block(block),
+                cells: vec![index],
+                action: StepAction::Place { index, value: self.one_remaining_value(index) },
+            });
+        }
+        let houses = labelled_houses(self);
+        for (house, house_indexes) in houses.iter() {
+            if let SingleSearch::Found(index, value) = hidden_single(self, std::slice::from_ref(house_indexes)) {
+                return Some(LogicalStep {
+                    technique: Technique::HiddenSingle,
+                    region: *house,
+                    cells: vec![index],
+                    action: StepAction::Place { index, value },
+                });
+            }
+        }
+        for (house, house_indexes) in houses.iter() {
+            if let Some(step) = self.locked_candidates(*house, house_indexes) {
+                return Some(step);
+            }
+        }
+        for subset_size in 2..=MAX_NAKED_SUBSET_SIZE {
+            for (house, house_indexes) in houses.iter() {
+                if let Some(step) = self.naked_subset(*house, house_indexes, subset_size) {
+                    return Some(step);
+                }
+            }
+        }
+        None
+    }
+
+    fn locked_candidates(&self, house: House, house_indexes: &[u16]) -> Option<LogicalStep> {
+        // The value has to go somewhere in the house only if the house has a cell for every value.
+        if house_indexes.len() != self.max_value as usize {
+            return None;
+        }
+        for value in 1..=self.max_value {
+            if house_indexes.iter().any(|index| self.values[*index as usize] == value) {
+                continue;
+            }
+            let positions = self.empty_cells_with_candidate(house_indexes.iter().copied(), value);
+            if positions.len() < 2 {
+                continue;
+            }
+            let eliminations = self
+                .empty_cells_with_candidate((0..self.cell_count).filter(|index| !house_indexes.contains(index)), value)
+                .into_iter()
+                .filter(|index| positions.iter().all(|position| self.is_related(*index, *position)))
+                .map(|index| (index, value))
+                .collect::<Vec<_>>();
+            if !eliminations.is_empty() {
+                return Some(LogicalStep {
+                    technique: Technique::LockedCandidates,
+                    region: house,
+                    cells: positions,
+                    action: StepAction::Eliminate { eliminations },
+                });
+            }
+        }
+        None
+    }
+
+    fn naked_subset(&self, house: House, house_indexes: &[u16], subset_size: usize) -> Option<LogicalStep> {
+        let subset_candidates = house_indexes
+            .iter()
+            .copied()
+            .filter(|index| {
+                let remaining_value_count = self.remaining_value_counts[*index as usize] as usize;
+                self.values[*index as usize] == NO_VALUE && remaining_value_count >= 2 && remaining_value_count <= subset_size
+            })
+            .collect::<Vec<_>>();
+        for cells in subset_candidates.into_iter().combinations(subset_size) {
+            let values = cells
+                .iter()
+                .flat_map(|index| self.remaining_values(*index))
+                .unique()
+                .sorted()
+                .collect::<Vec<_>>();
+            if values.len() != subset_size {
+                continue;
+            }
+            let mut eliminations = vec![];
+            for index in (0..self.cell_count).filter(|index| !cells.contains(index) && self.values[*index as usize] == NO_VALUE) {
+                if cells.iter().all(|cell| self.is_related(index, *cell)) {
+                    eliminations.extend(values.iter().filter(|value| self.has_remaining_value(index, **value)).map(|value| (index, *value)));
+                }
+            }
+            if !eliminations.is_empty() {
+                return Some(LogicalStep {
+                    technique: Technique::NakedSubset(subset_size as u8),
+                    region: house,
+                    cells,
+                    action: StepAction::Eliminate { eliminations },
+                });
+            }
+        }
+        None
+    }
+
+    fn empty_cells_with_candidate<I: Iterator<Item=u16>>(&self, indexes: I, value: u8) -> Vec<u16> {
+        indexes
+            .filter(|index| self.values[*index as usize] == NO_VALUE && self.has_remaining_value(*index, value))
+            .collect()
+    }
+
+    fn is_related(&self, index: u16, other_index: u16) -> bool {
+        index != other_index && self.related_cell_indexes[self.related_cell_range(index)].contains(&other_index)
+    }
+
+}
+
+pub(crate) fn labelled_houses(grid: &Grid) -> Vec<(House, Vec<u16>)> {
+    // The same houses as Grid::houses(), each with its row, column, or block.
+    let houses = grid.houses();
+    grid.all_houses()
+        .into_iter()
+        .map(|house| (house, grid.house_indexes(house).unwrap()))
+        .filter(|(_, house_indexes)| houses.contains(house_indexes))
+        .collect()
+}
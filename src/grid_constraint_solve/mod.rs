@@ -1,20 +1,93 @@
+pub mod backdoor;
+pub mod build_report;
 pub mod builder;
+pub mod candidates;
+pub mod canonical;
+pub mod diagnostics;
+pub mod game;
 pub mod grid;
+pub mod hint;
+pub mod pattern;
+pub mod play;
+pub mod property;
+pub mod sampler;
+pub mod session_file;
 pub mod solver;
+pub mod symmetry;
+pub mod transform;
+pub mod unavoidable;
+pub mod unique;
+pub mod validate;
+pub mod value_mask;
 
 use std::time::{Duration, Instant};
+use std::fmt::{self, Display, Formatter};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use crate::*;
 
-pub const RUN_INVARIANT: bool = false;
+pub type ProgressCallback = Arc<dyn Fn(&Progress) + Send + Sync>;
+
+#[derive(Clone, Debug)]
+pub enum SudokuError {
+    TimeLimitExceeded { elapsed: Duration, node_count: usize },
+    AttemptBudgetExhausted { elapsed: Duration, node_count: usize },
+    Cancelled { elapsed: Duration, node_count: usize },
+    Unsatisfiable { elapsed: Duration, node_count: usize },
+    InvalidDimensions { width: u8, height: u8, block_width: u8, block_height: u8, reason: String },
+    InvalidSymbols { symbol_count: usize, required_count: usize, duplicate: Option<char> },
+    ContradictoryGivens { index: u16, row: u8, column: u8, value: u8, conflicting_index: Option<u16> },
+    UnsupportedRules { reason: String },
+    CanonicalSearchTooLarge { transform_count: u64, max_transform_count: u64 },
+    NoUniquePuzzle { clue_count_min: u16, clue_count_max: u16 },
+    InvalidTransform { reason: String },
+    TransformBreaksRules { index: u16, related_index: u16 },
+    PatternNotUnique { reason: String, stats: pattern::PatternStats },
+    IncompleteGrid { unsolved_cell_count: u16 },
+    MultipleSolutions { differing_indexes: Vec<u16> },
+    SampleNotListed { values: Vec<u8> },
+    InvalidText { reason: String },
+    InvalidCandidate { index: u16, value: u8, reason: String },
+    InvalidMove { index: u16, reason: String },
+    FileError { path: String, reason: String },
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Progress {
+    pub depth: u16,
+    pub filled_cell_count: u16,
+    pub node_count: usize,
+    pub best_so_far: Option<usize>,
+}
+
+#[derive(Derivative)]
+#[derivative(Debug)]
+#[derive(Clone, Default)]
+pub struct RunControl {
+    pub cancellation_token: Option<CancellationToken>,
+    pub node_limit: Option<usize>,
+    pub progress_interval: usize,
+    #[derivative(Debug = "ignore")]
+    pub progress_callback: Option<ProgressCallback>,
+    pub diagnostics: diagnostics::Diagnostics,
+}
 
 #[derive(Derivative)]
 #[derivative(Debug)]
 #[derive(Clone)]
 pub struct Runner {
     pub time_limit: Option<Duration>,
+    pub control: RunControl,
+    pub node_count: usize,
+    pub best_so_far: Option<usize>,
     pub success: Option<bool>,
-    pub failure_message: Option<String>,
+    pub failure: Option<SudokuError>,
     #[derivative(Debug = "ignore")]
     pub start_time: Instant,
     #[derivative(Debug = "ignore")]
@@ -31,8 +104,11 @@ impl Runner {
     pub fn new(time_limit: Option<Duration>) -> Self {
         Self {
             time_limit,
+            control: RunControl::default(),
+            node_count: 0,
+            best_so_far: None,
             success: None,
-            failure_message: None,
+            failure: None,
             start_time: Instant::now(),
             end_time: None,
             time: None,
@@ -43,17 +119,27 @@ impl Runner {
         }
     }
 
+    pub fn restart(&self) -> Self {
+        // Start a new run with the same limits, cancellation token, and progress callback as this
+        // one but with fresh counts and timings.
+        let mut runner = Self::new(self.time_limit);
+        runner.control = self.control.clone();
+        runner
+    }
+
     pub fn check_continue(&mut self) -> bool {
         if self.success.is_some() {
             // We already have a result, either success or failure.
             false
+        } else if !self.check_control() {
+            false
         } else {
             match self.time_limit {
                 Some(time_limit) => {
                     if Instant::now() - self.start_time >= time_limit {
                         // We've gone over the time limit so cancel the build.
                         self.success = Some(false);
-                        self.failure_message = Some("Exceeded time limit.".to_string());
+                        self.failure = Some(SudokuError::TimeLimitExceeded { elapsed: self.elapsed(), node_count: self.node_count });
                         false
                     } else {
                         // We haven't reached the time limit so continue the build.
@@ -68,6 +154,55 @@ impl Runner {
         }
     }
 
+    pub fn count_node(&mut self, depth: u16, filled_cell_count: u16) -> bool {
+        // Record one node of the search tree, report progress if it's time to, and return false if
+        // the run has been cancelled or has used up its node budget. This doesn't look at the clock
+        // so it's cheap enough to call from the tight loops in the flat builders.
+        self.node_count += 1;
+        if let Some(callback) = &self.control.progress_callback {
+            let progress_interval = self.control.progress_interval.max(1);
+            if self.node_count.is_multiple_of(progress_interval) {
+                callback(&Progress {
+                    depth,
+                    filled_cell_count,
+                    node_count: self.node_count,
+                    best_so_far: self.best_so_far,
+                });
+            }
+        }
+        self.success.is_none() && self.check_control()
+    }
+
+    fn check_control(&mut self) -> bool {
+        // Returns false and marks the run as failed if the run was cancelled from outside or if
+        // it has explored more nodes than allowed.
+        if let Some(cancellation_token) = &self.control.cancellation_token {
+            if cancellation_token.is_cancelled() {
+                self.success = Some(false);
+                self.failure = Some(SudokuError::Cancelled { elapsed: self.elapsed(), node_count: self.node_count });
+                return false;
+            }
+        }
+        if let Some(node_limit) = self.control.node_limit {
+            if self.node_count > node_limit {
+                self.success = Some(false);
+                self.failure = Some(SudokuError::AttemptBudgetExhausted { elapsed: self.elapsed(), node_count: self.node_count });
+                return false;
+            }
+        }
+        true
+    }
+
+    pub fn record_best(&mut self, value: usize, lower_is_better: bool) {
+        let is_better = match self.best_so_far {
+            Some(best_so_far) => if lower_is_better { value < best_so_far } else { value > best_so_far },
+            None => true,
+        };
+        if is_better {
+            self.best_so_far = Some(value);
+        }
+    }
+
     pub fn mark_end(&mut self) {
         let now = Instant::now();
         self.end_time = Some(now);
@@ -88,8 +223,22 @@ impl Runner {
         self.remaining_time = Some(remaining_time);
     }
 
-    pub fn failure_message_clone(&self) -> String {
-        return self.failure_message.as_ref().unwrap().clone();
+    pub fn elapsed(&self) -> Duration {
+        match self.time {
+            Some(time) => time,
+            None => Instant::now() - self.start_time,
+        }
+    }
+
+    pub fn failure_clone(&self) -> SudokuError {
+        match &self.failure {
+            Some(failure) => failure.clone(),
+            None => {
+                // The run ended without success and without hitting any limit, meaning the search
+                // ran out of possibilities.
+                SudokuError::Unsatisfiable { elapsed: self.elapsed(), node_count: self.node_count }
+            }
+        }
     }
 
     pub fn times_as_string(&self) -> String {
@@ -113,7 +262,60 @@ impl Runner {
     }
 }
 
+impl Display for SudokuError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            SudokuError::TimeLimitExceeded { elapsed, node_count } => write!(f, "Exceeded time limit after {:?} and {} nodes.", elapsed, node_count),
+            SudokuError::AttemptBudgetExhausted { elapsed, node_count } => write!(f, "Exhausted the attempt budget after {:?} and {} nodes.", elapsed, node_count),
+            SudokuError::Cancelled { elapsed, node_count } => write!(f, "Cancelled after {:?} and {} nodes.", elapsed, node_count),
+            SudokuError::Unsatisfiable { elapsed, node_count } => write!(f, "The rules can't be satisfied (searched for {:?} and {} nodes).", elapsed, node_count),
+            SudokuError::InvalidDimensions { width, height, block_width, block_height, reason } => write!(f, "Invalid dimensions: width = {}, height = {}, block_width = {}, block_height = {}: {}", width, height, block_width, block_height, reason),
+            SudokuError::InvalidSymbols { symbol_count, required_count, duplicate } => match duplicate {
+                Some(symbol) => write!(f, "Invalid symbols: '{}' appears more than once.", symbol),
+                None => write!(f, "Invalid symbols: {} symbols given but {} are required.", symbol_count, required_count),
+            },
+            SudokuError::ContradictoryGivens { index, row, column, value, conflicting_index } => match conflicting_index {
+                Some(conflicting_index) => write!(f, "Contradictory givens: cell index {} r {} c {} has value {} which is also in related cell index {}.", index, row + 1, column + 1, value, conflicting_index),
+                None => write!(f, "Contradictory givens: cell index {} r {} c {} has no remaining values.", index, row + 1, column + 1),
+            },
+            SudokuError::UnsupportedRules { reason } => write!(f, "Unsupported rules: {}", reason),
+            SudokuError::CanonicalSearchTooLarge { transform_count, max_transform_count } => write!(f, "The grid has {} transformations, too many to search for a canonical form. The most is {}.", transform_count, max_transform_count),
+            SudokuError::NoUniquePuzzle { clue_count_min, clue_count_max } => write!(f, "No puzzle with {} to {} clues has a unique solution.", clue_count_min, clue_count_max),
+            SudokuError::InvalidTransform { reason } => write!(f, "Invalid transform: {}", reason),
+            SudokuError::TransformBreaksRules { index, related_index } => write!(f, "The transform would break the grid's rules: it changes whether cell index {} and cell index {} are related.", index, related_index),
+            SudokuError::PatternNotUnique { reason, stats } => write!(f, "The clue pattern didn't give a unique solution: {} ({})", reason, stats),
+            SudokuError::IncompleteGrid { unsolved_cell_count } => write!(f, "The grid must be complete but {} cells are empty.", unsolved_cell_count),
+            SudokuError::InvalidText { reason } => write!(f, "Invalid text: {}", reason),
+            SudokuError::InvalidCandidate { index, value, reason } => write!(f, "Invalid candidate {} for cell index {}: {}", value, index, reason),
+            SudokuError::InvalidMove { index, reason } => write!(f, "Invalid move at cell index {}: {}", index, reason),
+            SudokuError::FileError { path, reason } => write!(f, "Error with file \"{}\": {}", path, reason),
+            SudokuError::MultipleSolutions { differing_indexes } => write!(f, "The puzzle has more than one solution. Two of them differ at cell indexes {:?}.", differing_indexes),
+            SudokuError::SampleNotListed { values } => write!(f, "The sampler gave a grid that isn't in the list of every grid: {:?}", values),
+        }
+    }
+}
 
+impl std::error::Error for SudokuError {}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
 
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
 
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
 
+impl RunControl {
+    pub fn set_progress_callback<F>(&mut self, progress_interval: usize, callback: F)
+        where F: Fn(&Progress) + Send + Sync + 'static
+    {
+        self.progress_interval = progress_interval;
+        self.progress_callback = Some(Arc::new(callback));
+    }
+}
@@ -0,0 +1,305 @@
+#![allow(dead_code)]
+
+// Puzzles whose givens occupy exactly the cells of a pattern, such as a heart or a letter sketched
+// by a designer. We can't choose which cells end up as clues when reducing a grid, so instead we
+// build filled grids one after another and keep only the masked cells until one of them happens to
+// have a unique solution.
+// This goes with builder::Builder and solver::Solver.
+
+use crate::*;
+use super::grid::Grid;
+use super::builder::Builder;
+use super::unique::{Uniqueness, UniquenessChecker};
+use super::{Runner, CancellationToken, SudokuError};
+use std::time::Duration;
+use std::fmt::{self, Display, Formatter};
+
+const SOLVE_LIMIT_MSEC_DEFAULT: u64 = 5_000;
+
+pub fn main() {
+    try_pattern();
+}
+
+fn try_pattern() {
+    let pattern = "
+        . . . . . . . . .
+        . X X . . . X X .
+        X X X X . X X X X
+        X X X X X X X X X
+        X . X X X X X . X
+        . X . X X X . X .
+        . . X . X . X . .
+        . . . X . X . . .
+        . . . . X . . . .";
+    let limit_msec = 60_000;
+
+    let mut generator = PatternGenerator::from_pattern(Builder::with_size(9), pattern).limit_milliseconds(limit_msec);
+    match generator.generate() {
+        Ok(puzzle) => {
+            puzzle.print_simple("try_pattern()");
+            println!("{}", generator.stats);
+        },
+        Err(error) => println!("{}", error),
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct PatternStats {
+    pub attempt_count: usize,
+    pub build_failure_count: usize,
+    pub many_solution_count: usize,
+    // The uniqueness check ran out of time so we don't know how many solutions there were.
+    pub inconclusive_count: usize,
+    pub elapsed: Duration,
+}
+
+impl Display for PatternStats {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "attempts = {}, build failures = {}, multiple solutions = {}, inconclusive = {}, time = {:?}",
+               self.attempt_count, self.build_failure_count, self.many_solution_count, self.inconclusive_count, self.elapsed)
+    }
+}
+
+pub struct PatternGenerator {
+    pub builder: Builder,
+    pub mask: Vec<bool>,
+    pub max_attempt_count: Option<usize>,
+    pub solve_limit: Duration,
+    pub runner: Runner,
+    pub stats: PatternStats,
+    pub solution_grid: Option<Grid>,
+    checker: Option<UniquenessChecker>,
+}
+
+impl PatternGenerator {
+
+    pub fn new(builder: Builder, mask: &[bool]) -> Self {
+        Self {
+            builder,
+            mask: mask.to_vec(),
+            max_attempt_count: None,
+            solve_limit: Duration::from_millis(SOLVE_LIMIT_MSEC_DEFAULT),
+            runner: Runner::new(None),
+            stats: PatternStats::default(),
+            solution_grid: None,
+            checker: None,
+        }
+    }
+
+    pub fn from_pattern(builder: Builder, pattern: &str) -> Self {
+        let mask = mask_from_pattern(pattern);
+        Self::new(builder, &mask)
+    }
+
+    pub fn limit_seconds(mut self, seconds: u64) -> Self {
+        self.runner.time_limit = Some(Duration::from_secs(seconds));
+        self
+    }
+
+    pub fn limit_milliseconds(mut self, msec: u64) -> Self {
+        self.runner.time_limit = Some(Duration::from_millis(msec));
+        self
+    }
+
+    pub fn max_attempt_count(mut self, max_attempt_count: usize) -> Self {
+        self.max_attempt_count = Some(max_attempt_count);
+        self
+    }
+
+    pub fn solve_limit_milliseconds(mut self, msec: u64) -> Self {
+        self.solve_limit = Duration::from_millis(msec);
+        self
+    }
+
+    pub fn cancellation_token(mut self, cancellation_token: CancellationToken) -> Self {
+        self.runner.control.cancellation_token = Some(cancellation_token);
+        self
+    }
+
+    pub fn generate(&mut self) -> Result<Grid, SudokuError> {
+        // Returns a puzzle whose givens are exactly the masked cells and which has a unique
+        // solution. The solution is left in self.solution_grid.
+        self.runner = self.runner.restart();
+        self.stats = PatternStats::default();
+        self.solution_grid = None;
+
+        let cell_count = self.builder.width as usize * self.builder.height as usize;
+        if self.mask.len() != cell_count {
+            return Err(SudokuError::InvalidDimensions {
+                width: self.builder.width,
+                height: self.builder.height,
+                block_width: self.builder.block_width,
+                block_height: self.builder.block_height,
+                reason: format!("The clue pattern has {} cells but the grid has {}.", self.mask.len(), cell_count),
+            });
+        }
+        let clue_count = self.mask.iter().filter(|is_clue| **is_clue).count();
+
+        loop {
+            if let Some(max_attempt_count) = self.max_attempt_count {
+                if self.stats.attempt_count >= max_attempt_count {
+                    return Err(self.not_unique(format!("None of the {} grids tried gave a unique solution.", self.stats.attempt_count)));
+                }
+            }
+            if !self.runner.count_node(0, clue_count as u16) || !self.runner.check_continue() {
+                return match self.runner.failure_clone() {
+                    SudokuError::TimeLimitExceeded { .. } =>
+                        Err(self.not_unique("Exceeded the time limit before finding a grid with a unique solution.".to_string())),
+                    error => Err(error),
+                };
+            }
+            self.stats.attempt_count += 1;
+
+            let solution = match self.build_solution() {
+                Ok(grid) => grid,
+                Err(SudokuError::TimeLimitExceeded { .. }) | Err(SudokuError::AttemptBudgetExhausted { .. }) => {
+                    // Another grid may build in time. If the generator's own time is up, the check
+                    // at the top of the loop says so.
+                    self.stats.build_failure_count += 1;
+                    continue;
+                },
+                // The check at the top of the loop returns the cancellation.
+                Err(SudokuError::Cancelled { .. }) => continue,
+                // The builder's settings are wrong or its rules have no complete grid, so every
+                // other build would fail the same way.
+                Err(error) => return Err(self.fail(error)),
+            };
+            if self.stats.attempt_count == 1 {
+                // Unless every value but one appears among the givens, the two missing values can be
+                // swapped to make a second solution.
+                if clue_count + 1 < solution.max_value as usize {
+                    return Err(self.not_unique(format!("The pattern has {} clues, so at least two values would be missing from every puzzle.", clue_count)));
+                }
+                if let Some(reason) = pattern_defect(&solution, &self.mask) {
+                    return Err(self.not_unique(reason));
+                }
+            }
+
+            let puzzle = self.apply_mask(&solution);
+            if self.checker.is_none() {
+                // Every grid from the builder has the same size and rules, so one checker does for
+                // all of them.
+                self.checker = Some(UniquenessChecker::new(&solution)?);
+            }
+            let checker = self.checker.as_mut().unwrap();
+            checker.time_limit = Some(match self.runner.time_limit {
+                Some(time_limit) => self.solve_limit.min(time_limit.saturating_sub(self.runner.elapsed())),
+                None => self.solve_limit,
+            });
+            checker.cancellation_token = self.runner.control.cancellation_token.clone();
+            match checker.check_grid(&puzzle) {
+                Ok(Uniqueness::One { .. }) => {
+                    self.runner.success = Some(true);
+                    self.runner.mark_end();
+                    self.stats.elapsed = self.runner.elapsed();
+                    self.solution_grid = Some(solution);
+                    return Ok(puzzle);
+                },
+                Ok(Uniqueness::Many { .. }) => self.stats.many_solution_count += 1,
+                Ok(Uniqueness::Zero) => {
+                    // The puzzle came from a complete grid so this means the checker and the
+                    // builder disagree about the rules.
+                    let error = SudokuError::Unsatisfiable { elapsed: self.runner.elapsed(), node_count: self.runner.node_count };
+                    return Err(self.fail(error));
+                },
+                Err(SudokuError::Cancelled { .. }) => continue,
+                Err(_) => self.stats.inconclusive_count += 1,
+            }
+        }
+    }
+
+    fn build_solution(&mut self) -> Result<Grid, SudokuError> {
+        // Build a grid with no more than the generator's remaining time and with its cancellation
+        // token, then put the builder's own settings back.
+        let time_limit = self.builder.time_limit;
+        let cancellation_token = self.builder.control.cancellation_token.clone();
+        if let Some(generator_time_limit) = self.runner.time_limit {
+            let remaining_time = generator_time_limit.saturating_sub(self.runner.elapsed());
+            self.builder.time_limit = Some(time_limit.map_or(remaining_time, |time_limit| time_limit.min(remaining_time)));
+        }
+        if self.runner.control.cancellation_token.is_some() {
+            self.builder.control.cancellation_token = self.runner.control.cancellation_token.clone();
+        }
+        let result = self.builder.build();
+        self.builder.time_limit = time_limit;
+        self.builder.control.cancellation_token = cancellation_token;
+        result
+    }
+
+    fn apply_mask(&self, solution: &Grid) -> Grid {
+        let values = solution.values
+            .iter()
+            .zip(self.mask.iter())
+            .map(|(value, is_clue)| if *is_clue { *value } else { NO_VALUE })
+            .collect::<Vec<_>>();
+        let mut puzzle = solution.clone();
+        puzzle.replace_values(&values);
+        puzzle
+    }
+
+    fn not_unique(&mut self, reason: String) -> SudokuError {
+        self.mark_failed();
+        SudokuError::PatternNotUnique { reason, stats: self.stats.clone() }
+    }
+
+    fn fail(&mut self, error: SudokuError) -> SudokuError {
+        self.mark_failed();
+        error
+    }
+
+    fn mark_failed(&mut self) {
+        self.runner.success = Some(false);
+        self.runner.mark_end();
+        self.stats.elapsed = self.runner.elapsed();
+    }
+
+}
+
+pub fn mask_from_pattern(pattern: &str) -> Vec<bool> {
+    // Whitespace is ignored so the pattern can be laid out as a grid. '.', '0', and '-' are empty
+    // cells and anything else is a clue.
+    pattern.chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| !matches!(c, '.' | '0' | '-'))
+        .collect()
+}
+
+fn pattern_defect(grid: &Grid, mask: &[bool]) -> Option<String> {
+    // With the standard rules, two rows in the same band can be swapped without breaking any rule,
+    // as can two whole bands, and the same goes for columns and stacks. If the pattern leaves both
+    // of them empty the swap gives a second solution for every grid, so there's no point searching.
+    if !grid.has_standard_rules() {
+        return None;
+    }
+    let width = grid.width as usize;
+    let height = grid.height as usize;
+    let row_is_empty = (0..height)
+        .map(|row| (0..width).all(|column| !mask[(row * width) + column]))
+        .collect::<Vec<_>>();
+    let column_is_empty = (0..width)
+        .map(|column| (0..height).all(|row| !mask[(row * width) + column]))
+        .collect::<Vec<_>>();
+    line_defect(&row_is_empty, grid.block_height as usize, "row")
+        .or_else(|| line_defect(&column_is_empty, grid.block_width as usize, "column"))
+}
+
+fn line_defect(line_is_empty: &[bool], lines_per_group: usize, line_name: &str) -> Option<String> {
+    let group_is_empty = line_is_empty
+        .chunks(lines_per_group)
+        .map(|group| group.iter().all(|is_empty| *is_empty))
+        .collect::<Vec<_>>();
+    if group_is_empty.iter().filter(|is_empty| **is_empty).count() > 1 && lines_per_group < line_is_empty.len() {
+        return Some(format!("The pattern leaves more than one group of {}s empty, so those groups can be swapped.", line_name));
+    }
+    for (group_index, group) in line_is_empty.chunks(lines_per_group).enumerate() {
+        let empty_lines = group.iter()
+            .enumerate()
+            .filter(|(_, is_empty)| **is_empty)
+            .map(|(line, _)| (group_index * lines_per_group) + line + 1)
+            .collect::<Vec<_>>();
+        if empty_lines.len() > 1 {
+            return Some(format!("The pattern leaves {}s {} and {} empty, so they can be swapped.", line_name, empty_lines[0], empty_lines[1]));
+        }
+    }
+    None
+}
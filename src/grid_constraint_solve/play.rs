@@ -0,0 +1,158 @@
+#![allow(dead_code)]
+
+// A terminal front end for game::GameSession. It's a simple read-eval-print loop: each command is
+// one line, and after each command the grid is drawn again.
+// This goes with game::GameSession.
+
+use super::builder::Builder;
+use super::solver::Solver;
+use super::candidates::House;
+use super::game::{GameSession, HintReason};
+use super::hint::{Contradiction, HintLevel, HintOutcome, StepAction};
+use super::SudokuError;
+use std::io::{self, BufRead, Write};
+
+const HELP: &str = "\
+Rows and columns are numbered from 1 and values are the grid's symbols.
+  r c v     enter value v at row r, column c (v = . clears the cell)
+  n r c v   toggle pencil mark v at row r, column c
+  u         undo
+  y         redo
+  h         hint
+  h 1-4     hint about the next logical step: 1 = where to look, 2 = technique, 3 = cells,
+            4 = what to do
+  a         apply the last hint
+  k         check for mistakes
+  p         pause or resume the timer
+  ?         show this help
+  q         quit";
+
+pub fn main() {
+    let grid_size = 9;
+    let grid = Builder::with_size(grid_size).build().unwrap();
+    let puzzle = Solver::new(&grid).reduce_minimal().unwrap().grid;
+    let mut session = GameSession::new(&puzzle).unwrap();
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    play(&mut session, stdin.lock(), stdout.lock(), true).unwrap();
+}
+
+pub fn play<R: BufRead, W: Write>(session: &mut GameSession, input: R, mut output: W, use_color: bool) -> io::Result<()> {
+    writeln!(output, "{}", HELP)?;
+    show(session, &mut output, use_color)?;
+    // The action from the last level 4 hint, which a takes ahead of the session's last plain hint.
+    let mut last_action: Option<StepAction> = None;
+    for line in input.lines() {
+        let line = line?;
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        let message = match words.as_slice() {
+            [] => continue,
+            ["q"] => break,
+            ["?"] => HELP.to_string(),
+            ["u"] => if session.undo() { "Undone.".to_string() } else { "Nothing to undo.".to_string() },
+            ["y"] => if session.redo() { "Redone.".to_string() } else { "Nothing to redo.".to_string() },
+            ["h"] => match session.hint() {
+                Some(hint) => {
+                    last_action = None;
+                    let (row, column, _block) = session.puzzle.row_col_block(hint.index);
+                    let symbol = session.puzzle.symbols[hint.value as usize - 1];
+                    match hint.reason {
+                        HintReason::Mistake => format!("Row {} column {} is wrong.", row + 1, column + 1),
+                        HintReason::NakedSingle => format!("Row {} column {} can only be {}.", row + 1, column + 1, symbol),
+                        HintReason::HiddenSingle(house) => format!("{} can only go in row {} column {} in {}.", symbol, row + 1, column + 1, house_name(house)),
+                        HintReason::FromSolution => format!("Row {} column {} is {}.", row + 1, column + 1, symbol),
+                    }
+                },
+                None => "The puzzle is already solved.".to_string(),
+            },
+            ["h", level] => match level.parse::<u8>().ok().and_then(HintLevel::from_number) {
+                Some(level) => match session.graded_hint(level) {
+                    Ok(HintOutcome::Step(hint)) => {
+                        if hint.action.is_some() {
+                            last_action = hint.action.clone();
+                        }
+                        hint.to_string()
+                    },
+                    Ok(HintOutcome::Contradiction(Contradiction::WrongValues { indexes })) => format!("{} cells are wrong.", indexes.len()),
+                    Ok(HintOutcome::Contradiction(contradiction)) => format!("{:?}", contradiction),
+                    Ok(HintOutcome::Solved) => "The puzzle is already solved.".to_string(),
+                    Ok(HintOutcome::Stuck) => "There's no simple step from here. Try h for a plain hint.".to_string(),
+                    Err(error) => error.to_string(),
+                },
+                None => "Hint levels go from 1 to 4.".to_string(),
+            },
+            ["a"] => match (last_action.take(), session.last_hint.clone()) {
+                (Some(action), _) => result_message(session.apply_step_action(&action), "Applied the hint."),
+                (None, Some(hint)) => result_message(session.apply_hint(&hint), "Applied the hint."),
+                (None, None) => "Ask for a hint first.".to_string(),
+            },
+            ["k"] => match session.mistakes().len() {
+                0 => "No mistakes so far.".to_string(),
+                1 => "1 cell is wrong.".to_string(),
+                mistake_count => format!("{} cells are wrong.", mistake_count),
+            },
+            ["p"] => if session.is_paused() {
+                session.resume();
+                "Resumed.".to_string()
+            } else {
+                session.pause();
+                "Paused.".to_string()
+            },
+            ["n", row, column, value] => match parse_cell_and_value(session, row, column, value) {
+                Some((index, value)) => result_message(session.toggle_pencil_mark(index, value), "Toggled the pencil mark."),
+                None => "Couldn't read the row, column, and value.".to_string(),
+            },
+            [row, column, value] => match parse_cell_and_value(session, row, column, value) {
+                Some((index, value)) => result_message(session.set_value(index, value), "OK."),
+                None => "Couldn't read the row, column, and value.".to_string(),
+            },
+            _ => "Unknown command. Type ? for help.".to_string(),
+        };
+        writeln!(output, "{}", message)?;
+        show(session, &mut output, use_color)?;
+        if session.is_solved() {
+            writeln!(output, "Solved in {:?} with {} hints.", session.elapsed(), session.hint_count)?;
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn show<W: Write>(session: &GameSession, output: &mut W, use_color: bool) -> io::Result<()> {
+    match session.render(use_color) {
+        Ok(text) => writeln!(output, "\n{}\n", text)?,
+        Err(error) => writeln!(output, "{}", error)?,
+    }
+    let conflict_count = session.conflicts().len();
+    writeln!(output, "Time: {}s   Hints: {}   Conflicts: {}{}", session.elapsed().as_secs(), session.hint_count, conflict_count,
+             if session.is_paused() { "   (paused)" } else { "" })
+}
+
+fn parse_cell_and_value(session: &GameSession, row: &str, column: &str, value: &str) -> Option<(u16, u8)> {
+    let puzzle = &session.puzzle;
+    let row = row.parse::<u16>().ok().filter(|row| *row >= 1 && *row <= puzzle.height as u16)?;
+    let column = column.parse::<u16>().ok().filter(|column| *column >= 1 && *column <= puzzle.width as u16)?;
+    let mut chars = value.chars();
+    let symbol = chars.next()?;
+    if chars.next().is_some() {
+        return None;
+    }
+    let value = if symbol == '.' { 0 } else { puzzle.symbol_to_value(symbol)? };
+    Some((((row - 1) * puzzle.width as u16) + column - 1, value))
+}
+
+fn house_name(house: House) -> String {
+    // Numbered from 1 like the rows and columns the player types.
+    match house {
+        House::Row(row) => format!("row {}", row + 1),
+        House::Column(column) => format!("column {}", column + 1),
+        House::Block(block) => format!("block {}", block + 1),
+    }
+}
+
+fn result_message(result: Result<(), SudokuError>, ok_message: &str) -> String {
+    match result {
+        Ok(()) => ok_message.to_string(),
+        Err(error) => error.to_string(),
+    }
+}
@@ -0,0 +1,540 @@
+#![allow(dead_code)]
+
+// Property checks for Grid. Each case comes from a seed and has grid dimensions, a set of rules,
+// and a partial grid to take from a complete grid built with those rules. Every property is
+// checked against many cases, and the first case that fails a property is shrunk to the simplest
+// case that still fails it: fewer rules, smaller blocks, fewer blocks, fewer given cells.
+// The dimensions include grids that are narrower or shorter than a full sudoku, such as 6x3 with
+// 3x1 blocks, as long as each row, column, and block fits in the values.
+// The properties call Grid::invariant() themselves, so they check it whatever checkpoints the
+// current Diagnostics turn on. A case is discarded rather than failed if a build or solve runs out
+// of time or the extra rules leave no complete grid.
+
+use std::fmt::{self, Display, Formatter};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use itertools::Itertools;
+
+use crate::*;
+use super::SudokuError;
+use super::builder::{Builder, Rule, EXTRA_RULES};
+use super::grid::Grid;
+use super::solver::Solver;
+use super::unique::{Uniqueness, UniquenessChecker};
+
+const BLOCK_SIDE_MAX: u8 = 3;
+const EXTRA_RULE_ODDS: u32 = 4;
+const BUILD_TIME_LIMIT_MILLISECONDS: u64 = 1_000;
+const SOLVE_TIME_LIMIT_MILLISECONDS: u64 = 2_000;
+// Solution counts are capped at this. Capping keeps the comparison in RemoveCellsNeverReduces
+// valid since a count that reaches the cap on the smaller puzzle has to reach it on the larger.
+const SOLUTION_COUNT_LIMIT: usize = 20;
+
+pub fn main() {
+    try_properties();
+}
+
+fn try_properties() {
+    let report = PropertyRunner::new().case_count(50).run();
+    println!("try_properties():\n{}", report);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Property {
+    // partial_grid_from_indexes() gives a grid that passes the invariant and validate() and holds
+    // exactly the chosen values from the complete grid.
+    PartialGridValid,
+    // replace_values() leaves the remaining values the same as working them out from scratch, the
+    // same as partial_grid_from_indexes() with the same values, and unchanged by another
+    // recalc_remaining_values().
+    ReplaceValuesConsistent,
+    // Removing one more cell from a partial grid never gives fewer solutions, and a partial grid
+    // always has at least one.
+    RemoveCellsNeverReduces,
+    // A puzzle reduced from the complete grid, and any partial grid with a single solution, solves
+    // back to the complete grid.
+    ReducedPuzzleSolves,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GridCase {
+    pub seed: u64,
+    pub block_width: u8,
+    pub block_height: u8,
+    // The number of blocks across and down. These are at most block_height and block_width so
+    // that every row and column fits in the values.
+    pub block_col_count: u8,
+    pub block_row_count: u8,
+    pub rule_names: Vec<&'static str>,
+    // The partial grid has about this percentage of the cells filled.
+    pub given_percent: u8,
+    // Whether the partial grid is made by listing the cells to keep or the cells to empty.
+    pub include: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CheckOutcome {
+    Passed,
+    Discarded(String),
+    Failed(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct PropertyFailure {
+    pub case: GridCase,
+    pub message: String,
+    pub shrunk_case: GridCase,
+    pub shrunk_message: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct PropertyResult {
+    pub property: Property,
+    pub passed_count: usize,
+    pub discarded_count: usize,
+    // Checking a property stops at its first failure.
+    pub failure: Option<PropertyFailure>,
+}
+
+#[derive(Clone, Debug)]
+pub struct PropertyReport {
+    pub results: Vec<PropertyResult>,
+}
+
+#[derive(Clone, Debug)]
+pub struct PropertyRunner {
+    pub first_seed: u64,
+    pub case_count: usize,
+    pub shrink: bool,
+    pub properties: Vec<Property>,
+}
+
+impl PropertyRunner {
+
+    pub fn new() -> Self {
+        Self {
+            first_seed: 0,
+            case_count: 100,
+            shrink: true,
+            properties: Property::ALL.to_vec(),
+        }
+    }
+
+    pub fn first_seed(mut self, first_seed: u64) -> Self {
+        self.first_seed = first_seed;
+        self
+    }
+
+    pub fn case_count(mut self, case_count: usize) -> Self {
+        self.case_count = case_count;
+        self
+    }
+
+    pub fn shrink(mut self, shrink: bool) -> Self {
+        self.shrink = shrink;
+        self
+    }
+
+    pub fn only(mut self, property: Property) -> Self {
+        self.properties = vec![property];
+        self
+    }
+
+    pub fn run(&self) -> PropertyReport {
+        // Panics are reported as failures, so keep the hook from printing them.
+        let _quiet_panics = quiet_panics();
+        let mut results = vec![];
+        for property in self.properties.iter() {
+            let mut result = PropertyResult { property: *property, passed_count: 0, discarded_count: 0, failure: None };
+            for seed in self.first_seed..self.first_seed + self.case_count as u64 {
+                let case = GridCase::from_seed(seed);
+                match property.check(&case) {
+                    CheckOutcome::Passed => result.passed_count += 1,
+                    CheckOutcome::Discarded(_) => result.discarded_count += 1,
+                    CheckOutcome::Failed(message) => {
+                        let (shrunk_case, shrunk_message) = if self.shrink {
+                            property.shrink(&case, &message)
+                        } else {
+                            (case.clone(), message.clone())
+                        };
+                        result.failure = Some(PropertyFailure { case, message, shrunk_case, shrunk_message });
+                        break;
+                    },
+                }
+            }
+            results.push(result);
+        }
+        PropertyReport { results }
+    }
+
+}
+
+impl Default for PropertyRunner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PropertyReport {
+    pub fn is_success(&self) -> bool {
+        self.results.iter().all(|result| result.failure.is_none())
+    }
+}
+
+// Generators. Each takes the random number generator for a case so that a case is completely
+// determined by its seed.
+
+pub fn gen_dimensions<R: Rng>(rng: &mut R) -> (u8, u8, u8, u8) {
+    // Returns block_width, block_height, block_col_count, and block_row_count.
+    let block_width = rng.gen_range(1, BLOCK_SIDE_MAX + 1);
+    let block_height = rng.gen_range(1, BLOCK_SIDE_MAX + 1);
+    let block_col_count = rng.gen_range(1, block_height + 1);
+    let block_row_count = rng.gen_range(1, block_width + 1);
+    (block_width, block_height, block_col_count, block_row_count)
+}
+
+pub fn gen_rule_names<R: Rng>(rng: &mut R) -> Vec<&'static str> {
+    EXTRA_RULES
+        .iter()
+        .filter(|_| rng.gen_range(0, EXTRA_RULE_ODDS) == 0)
+        .map(|(name, _)| *name)
+        .collect()
+}
+
+pub fn gen_partial_indexes<R: Rng>(rng: &mut R, cell_count: u16, given_percent: u8, include: bool) -> Vec<u16> {
+    // The indexes to pass to partial_grid_from_indexes() along with include.
+    let given_count = (cell_count as usize * given_percent as usize) / 100;
+    let mut indexes = (0..cell_count).collect::<Vec<_>>();
+    indexes.shuffle(rng);
+    if include {
+        indexes.truncate(given_count);
+    } else {
+        indexes.truncate(cell_count as usize - given_count);
+    }
+    indexes.sort_unstable();
+    indexes
+}
+
+impl GridCase {
+
+    pub fn from_seed(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let (block_width, block_height, block_col_count, block_row_count) = gen_dimensions(&mut rng);
+        let rule_names = gen_rule_names(&mut rng);
+        let given_percent = rng.gen_range(0, 101);
+        let include = rng.gen();
+        Self { seed, block_width, block_height, block_col_count, block_row_count, rule_names, given_percent, include }
+    }
+
+    pub fn width(&self) -> u8 {
+        self.block_width * self.block_col_count
+    }
+
+    pub fn height(&self) -> u8 {
+        self.block_height * self.block_row_count
+    }
+
+    pub fn cell_count(&self) -> u16 {
+        self.width() as u16 * self.height() as u16
+    }
+
+    fn rules(&self) -> Vec<Rule> {
+        EXTRA_RULES
+            .iter()
+            .filter(|(name, _)| self.rule_names.contains(name))
+            .map(|(_, rule)| *rule)
+            .collect()
+    }
+
+    fn partial_indexes(&self) -> Vec<u16> {
+        // Use a different stream from the one that picked the case so that shrinking the other
+        // fields doesn't change how the cells are chosen more than it has to.
+        let mut rng = StdRng::seed_from_u64(self.seed.wrapping_add(1));
+        gen_partial_indexes(&mut rng, self.cell_count(), self.given_percent, self.include)
+    }
+
+    fn build_solution(&self) -> Result<Grid, CheckOutcome> {
+        let mut builder = Builder::new(self.width(), self.height(), self.block_width, self.block_height)
+            .limit_milliseconds(BUILD_TIME_LIMIT_MILLISECONDS);
+        for rule in self.rules() {
+            builder = builder.rule(rule);
+        }
+        let result = {
+            let _seeded_rng = seeded_rng(Some(self.seed));
+            catch_panic(|| builder.build())
+        };
+        match result {
+            Ok(Ok(grid)) => Ok(grid),
+            Ok(Err(SudokuError::TimeLimitExceeded { .. })) | Ok(Err(SudokuError::AttemptBudgetExhausted { .. })) => Err(CheckOutcome::Discarded("The build ran out of time.".to_string())),
+            Ok(Err(SudokuError::Unsatisfiable { .. })) if !self.rule_names.is_empty() => Err(CheckOutcome::Discarded("There's no grid with these rules.".to_string())),
+            Ok(Err(error)) => Err(CheckOutcome::Failed(format!("The build failed: {}", error))),
+            Err(message) => Err(CheckOutcome::Failed(format!("The build panicked: {}", message))),
+        }
+    }
+
+    fn simpler_cases(&self) -> Vec<Self> {
+        // Each of these is simpler than this case in one way, so shrinking always finishes.
+        let mut cases = vec![];
+        for rule_name in self.rule_names.iter() {
+            let mut case = self.clone();
+            case.rule_names.retain(|name| name != rule_name);
+            cases.push(case);
+        }
+        if self.block_col_count > 1 {
+            let mut case = self.clone();
+            case.block_col_count -= 1;
+            cases.push(case);
+        }
+        if self.block_row_count > 1 {
+            let mut case = self.clone();
+            case.block_row_count -= 1;
+            cases.push(case);
+        }
+        if self.block_width > 1 {
+            let mut case = self.clone();
+            case.block_width -= 1;
+            case.block_row_count = case.block_row_count.min(case.block_width);
+            cases.push(case);
+        }
+        if self.block_height > 1 {
+            let mut case = self.clone();
+            case.block_height -= 1;
+            case.block_col_count = case.block_col_count.min(case.block_height);
+            cases.push(case);
+        }
+        for given_percent in [0, self.given_percent / 2, self.given_percent.saturating_sub(10)].iter().unique() {
+            if *given_percent < self.given_percent {
+                let mut case = self.clone();
+                case.given_percent = *given_percent;
+                cases.push(case);
+            }
+        }
+        cases
+    }
+
+}
+
+impl Property {
+
+    pub const ALL: [Property; 4] = [Property::PartialGridValid, Property::ReplaceValuesConsistent, Property::RemoveCellsNeverReduces, Property::ReducedPuzzleSolves];
+
+    pub fn check(&self, case: &GridCase) -> CheckOutcome {
+        let solution = match case.build_solution() {
+            Ok(solution) => solution,
+            Err(outcome) => return outcome,
+        };
+        let result = catch_panic(|| match self {
+            Property::PartialGridValid => check_partial_grid_valid(case, &solution),
+            Property::ReplaceValuesConsistent => check_replace_values_consistent(case, &solution),
+            Property::RemoveCellsNeverReduces => check_remove_cells_never_reduces(case, &solution),
+            Property::ReducedPuzzleSolves => check_reduced_puzzle_solves(case, &solution),
+        });
+        match result {
+            Ok(outcome) => outcome,
+            Err(message) => CheckOutcome::Failed(format!("Panicked: {}", message)),
+        }
+    }
+
+    pub fn shrink(&self, case: &GridCase, message: &str) -> (GridCase, String) {
+        // Any failure of the same property counts, even with a different message, since a smaller
+        // case often shows the same bug a different way.
+        let mut shrunk = (case.clone(), message.to_string());
+        'simplify: loop {
+            for simpler_case in shrunk.0.simpler_cases() {
+                if let CheckOutcome::Failed(message) = self.check(&simpler_case) {
+                    shrunk = (simpler_case, message);
+                    continue 'simplify;
+                }
+            }
+            break;
+        }
+        shrunk
+    }
+
+}
+
+fn check_partial_grid_valid(case: &GridCase, solution: &Grid) -> CheckOutcome {
+    let indexes = case.partial_indexes();
+    let partial = solution.partial_grid_from_indexes(&indexes, case.include);
+    partial.invariant();
+    if let Some(violation) = partial.validate().first() {
+        return CheckOutcome::Failed(violation.to_string());
+    }
+    for index in 0..case.cell_count() {
+        let expected_value = if indexes.contains(&index) == case.include {
+            solution.values[index as usize]
+        } else {
+            NO_VALUE
+        };
+        let value = partial.values[index as usize];
+        if value != expected_value {
+            return CheckOutcome::Failed(format!("Cell index {} has value {} but should have {}.", index, value, expected_value));
+        }
+    }
+    CheckOutcome::Passed
+}
+
+fn check_replace_values_consistent(case: &GridCase, solution: &Grid) -> CheckOutcome {
+    let partial = solution.partial_grid_from_indexes(&case.partial_indexes(), case.include);
+    let mut grid = solution.clone();
+    grid.replace_values(&partial.values);
+    grid.invariant();
+
+    // Work out the remaining values from scratch.
+    let mut remaining_value_count = 0;
+    for index in 0..case.cell_count() {
+        let expected_values = if grid.values[index as usize] == NO_VALUE {
+            let related_values = grid.index_to_related_cell_indexes(index)
+                .iter()
+                .map(|related_index| grid.values[*related_index as usize])
+                .collect::<Vec<_>>();
+            (1..=grid.max_value).filter(|value| !related_values.contains(value)).collect()
+        } else {
+            vec![]
+        };
+        let values = grid.remaining_values.values(index);
+        if values != expected_values {
+            return CheckOutcome::Failed(format!("Cell index {} has remaining values {:?} but should have {:?}.", index, values, expected_values));
+        }
+        if grid.remaining_value_counts[index as usize] as usize != expected_values.len() {
+            return CheckOutcome::Failed(format!("Cell index {} has a remaining value count of {} but should have {}.", index, grid.remaining_value_counts[index as usize], expected_values.len()));
+        }
+        remaining_value_count += expected_values.len() as u32;
+    }
+    if grid.remaining_value_count != remaining_value_count {
+        return CheckOutcome::Failed(format!("The remaining value count is {} but should be {}.", grid.remaining_value_count, remaining_value_count));
+    }
+
+    // The same values reached by partial_grid_from_indexes() and by another recalc.
+    if let Some(message) = remaining_values_difference(&grid, &partial) {
+        return CheckOutcome::Failed(format!("replace_values() and partial_grid_from_indexes() differ: {}", message));
+    }
+    let before_recalc = grid.clone();
+    grid.recalc_remaining_values();
+    if let Some(message) = remaining_values_difference(&grid, &before_recalc) {
+        return CheckOutcome::Failed(format!("recalc_remaining_values() changed the grid: {}", message));
+    }
+
+    // Putting the complete grid's values back gives the complete grid.
+    grid.replace_values(&solution.values);
+    if let Some(message) = remaining_values_difference(&grid, solution) {
+        return CheckOutcome::Failed(format!("Replacing the values with the complete grid's differs from the complete grid: {}", message));
+    }
+    CheckOutcome::Passed
+}
+
+fn remaining_values_difference(grid: &Grid, other_grid: &Grid) -> Option<String> {
+    if grid.unsolved_cell_count != other_grid.unsolved_cell_count {
+        return Some(format!("unsolved_cell_count is {} and {}", grid.unsolved_cell_count, other_grid.unsolved_cell_count));
+    }
+    if grid.remaining_value_count != other_grid.remaining_value_count {
+        return Some(format!("remaining_value_count is {} and {}", grid.remaining_value_count, other_grid.remaining_value_count));
+    }
+    (0..grid.cell_count)
+        .find(|index| grid.values[*index as usize] != other_grid.values[*index as usize]
+            || grid.remaining_values.values(*index) != other_grid.remaining_values.values(*index))
+        .map(|index| format!("cell index {} has value {} and {}, remaining values {:?} and {:?}", index,
+            grid.values[index as usize], other_grid.values[index as usize],
+            grid.remaining_values.values(index), other_grid.remaining_values.values(index)))
+}
+
+fn check_remove_cells_never_reduces(case: &GridCase, solution: &Grid) -> CheckOutcome {
+    let partial = solution.partial_grid_from_indexes(&case.partial_indexes(), case.include);
+    let given_indexes = (0..case.cell_count())
+        .filter(|index| partial.values[*index as usize] != NO_VALUE)
+        .collect::<Vec<_>>();
+    if given_indexes.is_empty() {
+        return CheckOutcome::Discarded("There are no cells to remove.".to_string());
+    }
+    let mut rng = StdRng::seed_from_u64(case.seed);
+    let remove_index = given_indexes[rng.gen_range(0, given_indexes.len())];
+    let mut values = partial.values.clone();
+    values[remove_index as usize] = NO_VALUE;
+    let mut reduced = partial.clone();
+    reduced.replace_values(&values);
+
+    let count = match capped_solution_count(&partial) {
+        Ok(count) => count,
+        Err(outcome) => return outcome,
+    };
+    let reduced_count = match capped_solution_count(&reduced) {
+        Ok(count) => count,
+        Err(outcome) => return outcome,
+    };
+    if count == 0 {
+        return CheckOutcome::Failed("The partial grid has no solutions even though it came from a complete grid.".to_string());
+    }
+    if reduced_count < count {
+        return CheckOutcome::Failed(format!("Removing cell index {} took the solution count from {} down to {}.", remove_index, count, reduced_count));
+    }
+    CheckOutcome::Passed
+}
+
+fn capped_solution_count(grid: &Grid) -> Result<usize, CheckOutcome> {
+    let result = UniquenessChecker::new(grid)
+        .and_then(|checker| checker.limit_milliseconds(SOLVE_TIME_LIMIT_MILLISECONDS).solutions(&grid.values, SOLUTION_COUNT_LIMIT));
+    match result {
+        Ok(solutions) => Ok(solutions.len()),
+        Err(SudokuError::TimeLimitExceeded { .. }) => Err(CheckOutcome::Discarded("Counting the solutions ran out of time.".to_string())),
+        Err(error) => Err(CheckOutcome::Failed(format!("Counting the solutions failed: {}", error))),
+    }
+}
+
+fn check_reduced_puzzle_solves(case: &GridCase, solution: &Grid) -> CheckOutcome {
+    let reduced = {
+        let _seeded_rng = seeded_rng(Some(case.seed));
+        Solver::new(solution).limit_milliseconds(SOLVE_TIME_LIMIT_MILLISECONDS).reduce_minimal()
+    };
+    let reduced = match reduced {
+        Ok(reduced) => reduced,
+        Err(SudokuError::TimeLimitExceeded { .. }) => return CheckOutcome::Discarded("Reducing the puzzle ran out of time.".to_string()),
+        Err(error) => return CheckOutcome::Failed(format!("Reducing the puzzle failed: {}", error)),
+    };
+    if reduced.solution.values != solution.values {
+        return CheckOutcome::Failed("reduce_minimal() gave a different solution from the grid it started with.".to_string());
+    }
+    let partial = solution.partial_grid_from_indexes(&case.partial_indexes(), case.include);
+    for (label, puzzle, must_be_unique) in [("The reduced puzzle", &reduced.grid, true), ("The partial grid", &partial, false)].iter() {
+        match Solver::new(puzzle).limit_milliseconds(SOLVE_TIME_LIMIT_MILLISECONDS).uniqueness() {
+            Ok(Uniqueness::One { solution: values }) => if values != solution.values {
+                return CheckOutcome::Failed(format!("{} has one solution but it isn't the grid it came from.", label));
+            },
+            Ok(Uniqueness::Zero) => return CheckOutcome::Failed(format!("{} has no solution.", label)),
+            Ok(Uniqueness::Many { .. }) => if *must_be_unique {
+                return CheckOutcome::Failed(format!("{} has more than one solution.", label));
+            },
+            Err(SudokuError::TimeLimitExceeded { .. }) => return CheckOutcome::Discarded("Solving ran out of time.".to_string()),
+            Err(error) => return CheckOutcome::Failed(format!("Solving {} failed: {}", label.to_lowercase(), error)),
+        }
+    }
+    CheckOutcome::Passed
+}
+
+impl Display for Property {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl Display for GridCase {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "seed {}: {}x{} with {}x{} blocks, extra rules [{}], {}% given ({})", self.seed, self.width(), self.height(), self.block_width, self.block_height,
+            self.rule_names.iter().join(", "), self.given_percent, if self.include { "include" } else { "exclude" })
+    }
+}
+
+impl Display for PropertyReport {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for (result_index, result) in self.results.iter().enumerate() {
+            if result_index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}: {} passed, {} discarded", result.property, result.passed_count, result.discarded_count)?;
+            if let Some(failure) = &result.failure {
+                write!(f, ", FAILED\n    at {}: {}\n    shrunk to {}: {}", failure.case, failure.message, failure.shrunk_case, failure.shrunk_message)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,404 @@
+#![allow(dead_code)]
+
+// Sampling complete grids so that every grid for a configuration is equally likely, for
+// statistical studies and fair puzzle collections. The builders aren't uniform: they fill cells in
+// a fixed or most-constrained order and break ties at random, so grids that are reached through
+// fewer choices come up more often. There are two methods.
+// - ExactCounting fills the cells in order and picks each value with probability proportional to
+//   the number of complete grids that follow from it. Every grid then has exactly the same chance,
+//   with no bias beyond that of the random number generator. Counting visits every grid, so this
+//   is only for small configurations, and the node limit stops it. A 4x4 sample takes well under a
+//   millisecond, but 6x6 with 3x2 blocks has 28,200,960 grids and a sample takes most of a minute.
+// - KempeChain builds a grid and then makes random Kempe chain swaps: pick a cell holding value a
+//   and another value b, find every cell holding a or b that's connected to the first through
+//   related cells, and swap a and b in all of them. This keeps the grid valid under any rules.
+//   The chance of proposing a swap is the same as the chance of proposing the swap that undoes it,
+//   and half of the steps do nothing, so the chain converges to the uniform distribution over the
+//   grids that swaps can reach from the starting grid.
+// Bias bounds: the bias of KempeChain after a number of steps is the total variation distance from
+// uniform, the largest difference between the chance of any set of grids and its share of all
+// grids. chain_total_variation() works it out exactly from the worst starting grid for any
+// configuration small enough to list every grid. For 4x4 swaps reach all 288 grids and the
+// distance is below 1% after 64 steps and below 0.01% after 150. Swaps don't always reach every
+// grid: with the diagonals rule on 4x4 they only reach some of the 48 grids, and the distance
+// stays at 0.5 however many steps are taken. Larger grids have no known bound, and it isn't known
+// whether swaps reach every grid, so treat KempeChain as approximately uniform there and use more
+// steps to be safer. Each sample starts from a new build, so samples are independent of each
+// other.
+
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use rand::Rng;
+
+use crate::*;
+use super::{Runner, SudokuError};
+use super::builder::{Builder, RULE_DIAGONALS};
+use super::grid::Grid;
+
+// Stop counting after this many nodes unless the method says otherwise. A 4x4 grid takes a few
+// hundred.
+const COUNT_NODE_LIMIT: usize = 100_000_000;
+// chain_total_variation() keeps a grid count by grid count matrix, so it refuses more grids than
+// this.
+const CHAIN_MATRIX_GRID_COUNT_MAX: usize = 2_000;
+const CLOCK_CHECK_INTERVAL: usize = 4_096;
+// The z-score for a chi-square test at the 0.1% level.
+const CHI_SQUARE_Z: f64 = 3.09;
+
+pub fn main() {
+    try_sampler();
+}
+
+fn try_sampler() {
+    // The exact distance from uniform for the chain on 4x4 grids, then both methods against the
+    // distribution check, then a 9x9 grid from the chain.
+    for (label, diagonals) in [("4x4", false), ("4x4 diagonals", true)].iter() {
+        let builder = if *diagonals { Builder::with_size(4).rule(RULE_DIAGONALS) } else { Builder::with_size(4) };
+        let mut sampler = GridSampler::new(builder, SamplingMethod::KempeChain { step_count: 0 });
+        for step_count in [10, 30, 64, 150].iter() {
+            match sampler.chain_total_variation(*step_count) {
+                Ok(distance) => println!("try_sampler(): {}, {} steps: total variation distance = {:.6}", label, step_count, distance),
+                Err(error) => println!("try_sampler(): {}: {}", label, error),
+            }
+        }
+    }
+    for method in [SamplingMethod::ExactCounting { node_limit: COUNT_NODE_LIMIT }, SamplingMethod::KempeChain { step_count: 150 }].iter() {
+        match check_uniform_4x4(method.clone(), 28_800) {
+            Ok(check) => println!("try_sampler(): {:?}: {}", method, check),
+            Err(error) => println!("try_sampler(): {:?}: {}", method, error),
+        }
+    }
+    let mut sampler = GridSampler::new(Builder::with_size(9), SamplingMethod::KempeChain { step_count: 10_000 });
+    sampler.sample().unwrap().print_simple("try_sampler(): 9x9 KempeChain");
+}
+
+#[derive(Clone, Debug)]
+pub enum SamplingMethod {
+    ExactCounting { node_limit: usize },
+    KempeChain { step_count: usize },
+}
+
+pub struct GridSampler {
+    pub builder: Builder,
+    pub method: SamplingMethod,
+    // A grid built with the builder's settings, used for its related cells and symbols.
+    template: Option<Grid>,
+    related_cell_indexes: Vec<Vec<u16>>,
+    // For each cell, the related cells with a lower index.
+    earlier_related_cell_indexes: Vec<Vec<u16>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct UniformityCheck {
+    pub grid_count: usize,
+    pub sample_count: usize,
+    // The number of different grids among the samples.
+    pub sampled_grid_count: usize,
+    pub chi_square: f64,
+    pub critical_value: f64,
+    pub passed: bool,
+}
+
+impl GridSampler {
+
+    pub fn new(builder: Builder, method: SamplingMethod) -> Self {
+        Self {
+            builder,
+            method,
+            template: None,
+            related_cell_indexes: vec![],
+            earlier_related_cell_indexes: vec![],
+        }
+    }
+
+    pub fn sample(&mut self) -> Result<Grid, SudokuError> {
+        match self.method {
+            SamplingMethod::ExactCounting { node_limit } => {
+                self.set_up()?;
+                let template = self.template.as_ref().unwrap();
+                let mut runner = self.runner(node_limit);
+                let mut values = vec![NO_VALUE; template.cell_count as usize];
+                for index in 0..values.len() {
+                    // Count the grids that follow from each value this cell can take, then pick
+                    // one with probability proportional to its count.
+                    let mut counts = vec![];
+                    for value in self.possible_values(&values, index) {
+                        values[index] = value;
+                        counts.push((value, self.count_grids(&mut values, index + 1, &mut runner, &mut None)?));
+                    }
+                    let total: u64 = counts.iter().map(|(_, count)| count).sum();
+                    if total == 0 {
+                        return Err(runner.failure_clone());
+                    }
+                    let mut pick = rng().gen_range(0, total);
+                    for (value, count) in counts {
+                        if pick < count {
+                            values[index] = value;
+                            break;
+                        }
+                        pick -= count;
+                    }
+                }
+                Ok(template.clone_with_value_list(values))
+            },
+            SamplingMethod::KempeChain { step_count } => {
+                self.set_up()?;
+                let mut grid = self.builder.build()?;
+                let mut values = grid.values.clone();
+                let mut in_chain = vec![false; values.len()];
+                for _ in 0..step_count {
+                    self.kempe_step(&mut values, &mut in_chain);
+                }
+                grid.replace_values(&values);
+                Ok(grid)
+            },
+        }
+    }
+
+    pub fn grid_count(&mut self) -> Result<u64, SudokuError> {
+        // The number of complete grids with the builder's dimensions and rules.
+        self.set_up()?;
+        let mut runner = self.runner(self.count_node_limit());
+        let mut values = vec![NO_VALUE; self.related_cell_indexes.len()];
+        self.count_grids(&mut values, 0, &mut runner, &mut None)
+    }
+
+    pub fn all_grids(&mut self) -> Result<Vec<Vec<u8>>, SudokuError> {
+        // The values of every complete grid, in the order a search by cell index finds them.
+        self.set_up()?;
+        let mut runner = self.runner(self.count_node_limit());
+        let mut values = vec![NO_VALUE; self.related_cell_indexes.len()];
+        let mut grids = Some(vec![]);
+        self.count_grids(&mut values, 0, &mut runner, &mut grids)?;
+        Ok(grids.unwrap())
+    }
+
+    pub fn chain_total_variation(&mut self, step_count: usize) -> Result<f64, SudokuError> {
+        // The largest total variation distance from uniform after step_count steps of KempeChain,
+        // over every starting grid. This lists every grid and works out the chance of each step
+        // from each grid to each other, then multiplies the matrix of chances by itself to get the
+        // chances after step_count steps.
+        let grids = self.all_grids()?;
+        let grid_count = grids.len();
+        if grid_count > CHAIN_MATRIX_GRID_COUNT_MAX {
+            return Err(SudokuError::UnsupportedRules { reason: format!("There are {} grids, too many to work out the distance exactly. The most is {}.", grid_count, CHAIN_MATRIX_GRID_COUNT_MAX) });
+        }
+        let grid_indexes = grids.iter().enumerate().map(|(grid_index, values)| (values.clone(), grid_index)).collect::<HashMap<_, _>>();
+
+        let cell_count = self.related_cell_indexes.len();
+        let max_value = self.template.as_ref().unwrap().max_value;
+        let mut step = vec![vec![0.0; grid_count]; grid_count];
+        let mut in_chain = vec![false; cell_count];
+        for (grid_index, values) in grids.iter().enumerate() {
+            step[grid_index][grid_index] += 0.5;
+            if max_value < 2 {
+                step[grid_index][grid_index] += 0.5;
+                continue;
+            }
+            let move_chance = 0.5 / (cell_count as f64 * (max_value - 1) as f64);
+            for index in 0..cell_count {
+                for other_value in (1..=max_value).filter(|value| *value != values[index]) {
+                    let mut next_values = values.clone();
+                    self.swap_chain(&mut next_values, index, other_value, &mut in_chain);
+                    step[grid_index][grid_indexes[&next_values]] += move_chance;
+                }
+            }
+        }
+
+        // Raise the step matrix to the power step_count by squaring.
+        let mut chances = identity_matrix(grid_count);
+        let mut power = step;
+        let mut remaining_step_count = step_count;
+        while remaining_step_count > 0 {
+            if remaining_step_count % 2 == 1 {
+                chances = multiply_matrices(&chances, &power);
+            }
+            remaining_step_count /= 2;
+            if remaining_step_count > 0 {
+                power = multiply_matrices(&power, &power);
+            }
+        }
+
+        let uniform_chance = 1.0 / grid_count as f64;
+        Ok(chances
+            .iter()
+            .map(|row| row.iter().map(|chance| (chance - uniform_chance).abs()).sum::<f64>() / 2.0)
+            .fold(0.0, f64::max))
+    }
+
+    fn set_up(&mut self) -> Result<(), SudokuError> {
+        // Build one grid to check the settings and get the related cells, unless that's done.
+        if self.template.is_some() {
+            return Ok(());
+        }
+        let template = self.builder.build()?;
+        if template.max_value as usize > u64::BITS as usize {
+            return Err(SudokuError::UnsupportedRules { reason: format!("The sampler handles up to {} values but the grid has {}.", u64::BITS, template.max_value) });
+        }
+        self.related_cell_indexes = (0..template.cell_count)
+            .map(|index| template.index_to_related_cell_indexes(index))
+            .collect();
+        self.earlier_related_cell_indexes = self.related_cell_indexes
+            .iter()
+            .enumerate()
+            .map(|(index, related_cell_indexes)| related_cell_indexes.iter().copied().filter(|related_cell_index| (*related_cell_index as usize) < index).collect())
+            .collect();
+        self.template = Some(template);
+        Ok(())
+    }
+
+    fn runner(&self, node_limit: usize) -> Runner {
+        let mut runner = Runner::new(self.builder.time_limit);
+        runner.control = self.builder.control.clone();
+        runner.control.node_limit = Some(node_limit);
+        runner
+    }
+
+    fn count_node_limit(&self) -> usize {
+        match self.method {
+            SamplingMethod::ExactCounting { node_limit } => node_limit,
+            SamplingMethod::KempeChain { .. } => COUNT_NODE_LIMIT,
+        }
+    }
+
+    fn possible_values(&self, values: &[u8], index: usize) -> Vec<u8> {
+        // The values the cell at index can take given the cells before it. The cells after it
+        // are still empty.
+        let max_value = self.template.as_ref().unwrap().max_value;
+        let mut taken = 0u64;
+        for related_cell_index in self.earlier_related_cell_indexes[index].iter() {
+            taken |= 1 << (values[*related_cell_index as usize] - 1);
+        }
+        (1..=max_value).filter(|value| taken & (1 << (value - 1)) == 0).collect()
+    }
+
+    fn count_grids(&self, values: &mut Vec<u8>, index: usize, runner: &mut Runner, grids: &mut Option<Vec<Vec<u8>>>) -> Result<u64, SudokuError> {
+        // The number of complete grids that follow from the values before index, with the cells
+        // from index on empty. If grids is Some, add each complete grid to it.
+        if index == values.len() {
+            if let Some(grids) = grids {
+                grids.push(values.clone());
+            }
+            return Ok(1);
+        }
+        if !runner.count_node(index as u16, index as u16)
+            || (runner.node_count.is_multiple_of(CLOCK_CHECK_INTERVAL) && !runner.check_continue()) {
+            return Err(runner.failure_clone());
+        }
+        let mut count = 0;
+        for value in self.possible_values(values, index) {
+            values[index] = value;
+            count += self.count_grids(values, index + 1, runner, grids)?;
+        }
+        values[index] = NO_VALUE;
+        Ok(count)
+    }
+
+    fn kempe_step(&self, values: &mut [u8], in_chain: &mut [bool]) {
+        // Half the time do nothing, which keeps the chain from going back and forth between the
+        // same grids in step. Otherwise swap a random chain.
+        let max_value = self.template.as_ref().unwrap().max_value;
+        if max_value < 2 || rng().gen::<bool>() {
+            return;
+        }
+        let index = rng().gen_range(0, values.len());
+        // Any value but the one in the cell, each as likely as the others.
+        let mut other_value = rng().gen_range(1, max_value);
+        if other_value >= values[index] {
+            other_value += 1;
+        }
+        self.swap_chain(values, index, other_value, in_chain);
+    }
+
+    fn swap_chain(&self, values: &mut [u8], index: usize, other_value: u8, in_chain: &mut [bool]) {
+        // Swap the two values in the cells holding either of them that are connected to the cell
+        // at index. Any cell related to the chain holds neither value, so the grid stays valid.
+        // in_chain must be all false and is left that way.
+        let value = values[index];
+        let mut chain = vec![index];
+        in_chain[index] = true;
+        let mut next = 0;
+        while next < chain.len() {
+            let chain_index = chain[next];
+            next += 1;
+            for related_cell_index in self.related_cell_indexes[chain_index].iter() {
+                let related_cell_index = *related_cell_index as usize;
+                let related_value = values[related_cell_index];
+                if !in_chain[related_cell_index] && (related_value == value || related_value == other_value) {
+                    in_chain[related_cell_index] = true;
+                    chain.push(related_cell_index);
+                }
+            }
+        }
+        for chain_index in chain {
+            values[chain_index] = if values[chain_index] == value { other_value } else { value };
+            in_chain[chain_index] = false;
+        }
+    }
+
+}
+
+pub fn check_uniform_4x4(method: SamplingMethod, sample_count: usize) -> Result<UniformityCheck, SudokuError> {
+    // Sample 4x4 grids and compare how often each of the 288 grids comes up with a chi-square test
+    // at the 0.1% level. Every grid also has to come up at least once, so use at least a few
+    // thousand samples. Even a uniform sampler fails this one time in a thousand.
+    let mut sampler = GridSampler::new(Builder::with_size(4), method);
+    let grids = sampler.all_grids()?;
+    let mut sample_counts = grids.into_iter().map(|values| (values, 0usize)).collect::<HashMap<_, _>>();
+    for _ in 0..sample_count {
+        let grid = sampler.sample()?;
+        match sample_counts.get_mut(&grid.values) {
+            Some(count) => *count += 1,
+            None => return Err(SudokuError::SampleNotListed { values: grid.values }),
+        }
+    }
+
+    let grid_count = sample_counts.len();
+    let expected_count = sample_count as f64 / grid_count as f64;
+    let chi_square = sample_counts
+        .values()
+        .map(|count| (*count as f64 - expected_count).powi(2) / expected_count)
+        .sum::<f64>();
+    // Wilson and Hilferty's approximation to the chi-square distribution.
+    let degrees_of_freedom = (grid_count - 1) as f64;
+    let spread = 2.0 / (9.0 * degrees_of_freedom);
+    let critical_value = degrees_of_freedom * (1.0 - spread + CHI_SQUARE_Z * spread.sqrt()).powi(3);
+    let sampled_grid_count = sample_counts.values().filter(|count| **count > 0).count();
+    Ok(UniformityCheck {
+        grid_count,
+        sample_count,
+        sampled_grid_count,
+        chi_square,
+        critical_value,
+        passed: sampled_grid_count == grid_count && chi_square <= critical_value,
+    })
+}
+
+fn identity_matrix(size: usize) -> Vec<Vec<f64>> {
+    (0..size).map(|row| (0..size).map(|column| if row == column { 1.0 } else { 0.0 }).collect()).collect()
+}
+
+fn multiply_matrices(a: &[Vec<f64>], b: &[Vec<f64>]) -> Vec<Vec<f64>> {
+    let size = b.len();
+    a.iter()
+        .map(|a_row| {
+            let mut row = vec![0.0; size];
+            for (a_value, b_row) in a_row.iter().zip(b.iter()) {
+                if *a_value != 0.0 {
+                    for (value, b_value) in row.iter_mut().zip(b_row.iter()) {
+                        *value += a_value * b_value;
+                    }
+                }
+            }
+            row
+        })
+        .collect()
+}
+
+impl Display for UniformityCheck {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{} samples, {} of {} grids seen, chi-square = {:.1} (critical value {:.1}), {}",
+            self.sample_count, self.sampled_grid_count, self.grid_count, self.chi_square, self.critical_value,
+            if self.passed { "passed" } else { "FAILED" })
+    }
+}
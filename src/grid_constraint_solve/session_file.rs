@@ -0,0 +1,372 @@
+#![allow(dead_code)]
+
+// Saving and loading game sessions. The file is plain text, one item per line, starting with a
+// header line that gives the format version:
+//
+//   sudoku-session 1
+//   dimensions 9 9 3 3
+//   symbols 123456789
+//   givens 0 3 0 ...
+//   values 5 3 0 ...
+//   related 0 1 2 3 ...          (one line per cell, left out for the standard rules)
+//   pencil_marks 2 1 4 7         (cell index, then the marks)
+//   undo 2 0 1,4,7 5 -           (cell index, value and marks before, value and marks after)
+//   redo ...
+//   elapsed_milliseconds 81234
+//   paused false
+//   hint_count 2
+//   last_hint 40 5 hidden_single block 4
+//
+// Values and cell indexes are numbers rather than symbols so that any symbol set can be saved.
+// The solution isn't saved since it's worked out again from the givens.
+// When the format changes, bump SESSION_FILE_VERSION and keep reading the older versions. A new
+// item should get a default when it's missing, and anything whose meaning changes should be read
+// according to the version in the header. Items that this version doesn't know are skipped.
+// tests/fixtures has saved files for each version, loaded by tests/session_file.rs.
+// This goes with game::GameSession.
+
+use crate::*;
+use super::grid::Grid;
+use super::builder::Builder;
+use super::solver::Solver;
+use super::candidates::House;
+use super::game::{GameSession, Edit, CellState, Hint, HintReason};
+use super::SudokuError;
+use itertools::Itertools;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+pub const SESSION_FILE_VERSION: u32 = 1;
+const SESSION_FILE_HEADER: &str = "sudoku-session";
+const NO_PENCIL_MARKS: &str = "-";
+
+pub fn main() {
+    try_save_and_load();
+}
+
+fn try_save_and_load() {
+    let grid = Builder::with_size(9).build().unwrap();
+    let puzzle = Solver::new(&grid).reduce_minimal().unwrap().grid;
+    let mut session = GameSession::new(&puzzle).unwrap();
+    let empty_indexes = (0..session.cell_count()).filter(|index| !session.is_given(*index)).collect::<Vec<_>>();
+    session.toggle_pencil_mark(empty_indexes[0], 1).unwrap();
+    session.toggle_pencil_mark(empty_indexes[0], 2).unwrap();
+    session.set_value(empty_indexes[1], session.solution.values[empty_indexes[1] as usize]).unwrap();
+    session.set_value(empty_indexes[2], 1).unwrap();
+    session.undo();
+    if let Some(hint) = session.hint() {
+        session.apply_hint(&hint).unwrap();
+    }
+    session.pause();
+
+    let path = std::env::temp_dir().join("try_save_and_load.sudoku");
+    let path = path.to_str().unwrap();
+    session.save_to_file(path).unwrap();
+    let loaded_session = GameSession::load_from_file(path).unwrap();
+    println!("{}", std::fs::read_to_string(path).unwrap());
+    println!("try_save_and_load(): same text = {}, same render = {}, elapsed = {:?}",
+             loaded_session.to_session_string() == session.to_session_string(),
+             loaded_session.render(false).unwrap() == session.render(false).unwrap(),
+             loaded_session.elapsed());
+}
+
+impl GameSession {
+
+    pub fn to_session_string(&self) -> String {
+        let puzzle = &self.puzzle;
+        let mut lines = vec![];
+        lines.push(format!("{} {}", SESSION_FILE_HEADER, SESSION_FILE_VERSION));
+        lines.push(format!("dimensions {} {} {} {}", puzzle.width, puzzle.height, puzzle.block_width, puzzle.block_height));
+        lines.push(format!("symbols {}", puzzle.symbols.iter().collect::<String>()));
+        lines.push(format!("givens {}", puzzle.values.iter().join(" ")));
+        lines.push(format!("values {}", self.values.iter().join(" ")));
+        if !puzzle.has_standard_rules() {
+            for index in 0..puzzle.cell_count {
+                lines.push(format!("related {}", puzzle.index_to_related_cell_indexes(index).iter().join(" ")));
+            }
+        }
+        for (index, pencil_marks) in self.pencil_marks.iter().enumerate().filter(|(_, pencil_marks)| !pencil_marks.is_empty()) {
+            lines.push(format!("pencil_marks {} {}", index, pencil_marks.iter().join(" ")));
+        }
+        for edit in self.undo_stack.iter() {
+            lines.push(format!("undo {}", edit_to_string(edit)));
+        }
+        for edit in self.redo_stack.iter() {
+            lines.push(format!("redo {}", edit_to_string(edit)));
+        }
+        lines.push(format!("elapsed_milliseconds {}", self.elapsed().as_millis()));
+        lines.push(format!("paused {}", self.is_paused()));
+        lines.push(format!("hint_count {}", self.hint_count));
+        if let Some(hint) = &self.last_hint {
+            lines.push(format!("last_hint {} {} {}", hint.index, hint.value, hint_reason_to_string(&hint.reason)));
+        }
+        lines.push(String::new());
+        lines.join("\n")
+    }
+
+    pub fn from_session_string(text: &str) -> Result<Self, SudokuError> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(line_index, line)| (line_index + 1, line.trim_end()))
+            .filter(|(_, line)| !line.is_empty());
+
+        let version = match lines.next() {
+            Some((line_number, line)) => {
+                let words = line.split_whitespace().collect::<Vec<_>>();
+                if words.len() != 2 || words[0] != SESSION_FILE_HEADER {
+                    return Err(load_error(line_number, &format!("Expected \"{} <version>\".", SESSION_FILE_HEADER)));
+                }
+                parse_number::<u32>(line_number, words[1])?
+            },
+            None => return Err(SudokuError::InvalidText { reason: "The session file is empty.".to_string() }),
+        };
+        if version == 0 || version > SESSION_FILE_VERSION {
+            return Err(SudokuError::InvalidText { reason: format!("Session file version {} isn't supported. The newest version this can read is {}.", version, SESSION_FILE_VERSION) });
+        }
+
+        // Group the lines by item, keeping the line numbers for error messages.
+        let mut items: Vec<(&str, usize, &str)> = vec![];
+        for (line_number, line) in lines {
+            let (key, rest) = match line.find(' ') {
+                Some(position) => (&line[..position], &line[position + 1..]),
+                None => (line, ""),
+            };
+            items.push((key, line_number, rest));
+        }
+        let find_all = |key: &str| items
+            .iter()
+            .filter(|(item_key, _, _)| *item_key == key)
+            .map(|(_, line_number, rest)| (*line_number, *rest))
+            .collect::<Vec<_>>();
+        let find_one = |key: &str| -> Result<Option<(usize, &str)>, SudokuError> {
+            let found = find_all(key);
+            match found.len() {
+                0 => Ok(None),
+                1 => Ok(Some(found[0])),
+                _ => Err(load_error(found[1].0, &format!("\"{}\" appears more than once.", key))),
+            }
+        };
+        let find_required = |key: &str| -> Result<(usize, &str), SudokuError> {
+            find_one(key)?.ok_or_else(|| SudokuError::InvalidText { reason: format!("The session file has no \"{}\" line.", key) })
+        };
+
+        let puzzle = {
+            let (line_number, rest) = find_required("dimensions")?;
+            let dimensions = parse_numbers::<u8>(line_number, rest)?;
+            if dimensions.len() != 4 {
+                return Err(load_error(line_number, "Expected the width, height, block width, and block height."));
+            }
+            let (width, height, block_width, block_height) = (dimensions[0], dimensions[1], dimensions[2], dimensions[3]);
+            if width == 0 || height == 0 || block_width == 0 || block_height == 0
+                    || !width.is_multiple_of(block_width) || !height.is_multiple_of(block_height)
+                    || block_width as usize * block_height as usize > u8::MAX as usize {
+                return Err(SudokuError::InvalidDimensions { width, height, block_width, block_height, reason: "The session file's dimensions don't make a grid.".to_string() });
+            }
+            let mut puzzle = Grid::new(width, height, block_width, block_height);
+            if let Some((line_number, rest)) = find_one("symbols")? {
+                let symbols = gen_char_array(rest);
+                if symbols.len() < puzzle.max_value as usize || symbols.iter().unique().count() != symbols.len() {
+                    return Err(load_error(line_number, &format!("Expected {} different symbols.", puzzle.max_value)));
+                }
+                puzzle.symbols = symbols;
+            }
+            let related_lines = find_all("related");
+            let related_cell_index_lists = if related_lines.is_empty() {
+                (0..puzzle.cell_count).map(|index| puzzle.standard_related_cell_indexes(index)).collect::<Vec<_>>()
+            } else {
+                if related_lines.len() != puzzle.cell_count as usize {
+                    return Err(load_error(related_lines[0].0, &format!("Expected a \"related\" line for each of the {} cells.", puzzle.cell_count)));
+                }
+                let mut related_cell_index_lists = vec![];
+                for (index, (line_number, rest)) in related_lines.iter().enumerate() {
+                    let related_cell_indexes = parse_numbers::<u16>(*line_number, rest)?;
+                    if related_cell_indexes.iter().any(|related_index| *related_index >= puzzle.cell_count || *related_index as usize == index) {
+                        return Err(load_error(*line_number, "Related cell indexes have to be other cells in the grid."));
+                    }
+                    related_cell_index_lists.push(related_cell_indexes);
+                }
+                related_cell_index_lists
+            };
+            puzzle.set_related_cell_indexes(&related_cell_index_lists);
+            let (line_number, rest) = find_required("givens")?;
+            let givens = parse_values(line_number, rest, &puzzle)?;
+            puzzle.replace_values(&givens);
+            puzzle
+        };
+
+        let mut session = GameSession::new(&puzzle)?;
+
+        if let Some((line_number, rest)) = find_one("values")? {
+            let values = parse_values(line_number, rest, &puzzle)?;
+            if let Some(index) = (0..puzzle.cell_count).find(|index| session.is_given(*index) && values[*index as usize] != puzzle.values[*index as usize]) {
+                return Err(load_error(line_number, &format!("Cell index {} is a given but has a different value.", index)));
+            }
+            session.values = values;
+        }
+
+        for (line_number, rest) in find_all("pencil_marks") {
+            let (index, pencil_marks) = match rest.split_whitespace().collect::<Vec<_>>().split_first() {
+                Some((index, pencil_marks)) => (
+                    parse_number::<u16>(line_number, index)?,
+                    pencil_marks.iter().map(|value| parse_number::<u8>(line_number, value)).collect::<Result<Vec<_>, _>>()?,
+                ),
+                None => return Err(load_error(line_number, "Expected a cell index and then the pencil marks.")),
+            };
+            check_cell_state(line_number, &session, index, &CellState { value: NO_VALUE, pencil_marks: pencil_marks.clone() })?;
+            if session.values[index as usize] != NO_VALUE {
+                return Err(load_error(line_number, &format!("Cell index {} has a value so it can't have pencil marks.", index)));
+            }
+            session.pencil_marks[index as usize] = pencil_marks.into_iter().sorted().dedup().collect();
+        }
+
+        session.undo_stack = find_all("undo")
+            .into_iter()
+            .map(|(line_number, rest)| parse_edit(line_number, rest, &session))
+            .collect::<Result<_, _>>()?;
+        session.redo_stack = find_all("redo")
+            .into_iter()
+            .map(|(line_number, rest)| parse_edit(line_number, rest, &session))
+            .collect::<Result<_, _>>()?;
+
+        session.elapsed_before = match find_one("elapsed_milliseconds")? {
+            Some((line_number, rest)) => Duration::from_millis(parse_number::<u64>(line_number, rest)?),
+            None => Duration::from_secs(0),
+        };
+        // The clock picks up where it left off unless the session was saved while paused.
+        let paused = match find_one("paused")? {
+            Some((line_number, rest)) => parse_number::<bool>(line_number, rest)?,
+            None => false,
+        };
+        session.resumed_at = if paused { None } else { Some(Instant::now()) };
+        if let Some((line_number, rest)) = find_one("hint_count")? {
+            session.hint_count = parse_number::<usize>(line_number, rest)?;
+        }
+        if let Some((line_number, rest)) = find_one("last_hint")? {
+            session.last_hint = Some(parse_hint(line_number, rest, &session)?);
+        }
+        Ok(session)
+    }
+
+    pub fn save_to_file(&self, path: &str) -> Result<(), SudokuError> {
+        std::fs::write(path, self.to_session_string())
+            .map_err(|error| SudokuError::FileError { path: path.to_string(), reason: error.to_string() })
+    }
+
+    pub fn load_from_file(path: &str) -> Result<Self, SudokuError> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|error| SudokuError::FileError { path: path.to_string(), reason: error.to_string() })?;
+        Self::from_session_string(&text)
+    }
+
+}
+
+fn edit_to_string(edit: &Edit) -> String {
+    format!("{} {} {}", edit.index, cell_state_to_string(&edit.before), cell_state_to_string(&edit.after))
+}
+
+fn cell_state_to_string(cell_state: &CellState) -> String {
+    let pencil_marks = if cell_state.pencil_marks.is_empty() {
+        NO_PENCIL_MARKS.to_string()
+    } else {
+        cell_state.pencil_marks.iter().join(",")
+    };
+    format!("{} {}", cell_state.value, pencil_marks)
+}
+
+fn hint_reason_to_string(reason: &HintReason) -> String {
+    match reason {
+        HintReason::Mistake => "mistake".to_string(),
+        HintReason::NakedSingle => "naked_single".to_string(),
+        HintReason::HiddenSingle(House::Row(row)) => format!("hidden_single row {}", row),
+        HintReason::HiddenSingle(House::Column(column)) => format!("hidden_single column {}", column),
+        HintReason::HiddenSingle(House::Block(block)) => format!("hidden_single block {}", block),
+        HintReason::FromSolution => "from_solution".to_string(),
+    }
+}
+
+fn parse_edit(line_number: usize, text: &str, session: &GameSession) -> Result<Edit, SudokuError> {
+    let words = text.split_whitespace().collect::<Vec<_>>();
+    if words.len() != 5 {
+        return Err(load_error(line_number, "Expected a cell index and then the value and pencil marks before and after."));
+    }
+    let index = parse_number::<u16>(line_number, words[0])?;
+    let before = parse_cell_state(line_number, words[1], words[2])?;
+    let after = parse_cell_state(line_number, words[3], words[4])?;
+    check_cell_state(line_number, session, index, &before)?;
+    check_cell_state(line_number, session, index, &after)?;
+    Ok(Edit { index, before, after })
+}
+
+fn parse_cell_state(line_number: usize, value: &str, pencil_marks: &str) -> Result<CellState, SudokuError> {
+    let value = parse_number::<u8>(line_number, value)?;
+    let pencil_marks = if pencil_marks == NO_PENCIL_MARKS {
+        vec![]
+    } else {
+        pencil_marks
+            .split(',')
+            .map(|word| parse_number::<u8>(line_number, word))
+            .collect::<Result<Vec<_>, _>>()?
+    };
+    Ok(CellState { value, pencil_marks })
+}
+
+fn check_cell_state(line_number: usize, session: &GameSession, index: u16, cell_state: &CellState) -> Result<(), SudokuError> {
+    let max_value = session.puzzle.max_value;
+    if index >= session.cell_count() {
+        return Err(load_error(line_number, &format!("Cell index {} is outside the grid.", index)));
+    }
+    if session.is_given(index) {
+        return Err(load_error(line_number, &format!("Cell index {} is a given.", index)));
+    }
+    if cell_state.value > max_value || cell_state.pencil_marks.iter().any(|value| *value == NO_VALUE || *value > max_value) {
+        return Err(load_error(line_number, &format!("Values go from 1 to {}.", max_value)));
+    }
+    Ok(())
+}
+
+fn parse_hint(line_number: usize, text: &str, session: &GameSession) -> Result<Hint, SudokuError> {
+    let words = text.split_whitespace().collect::<Vec<_>>();
+    let bad_hint = || load_error(line_number, "Expected a cell index, a value, and a reason.");
+    if words.len() < 3 {
+        return Err(bad_hint());
+    }
+    let index = parse_number::<u16>(line_number, words[0])?;
+    let value = parse_number::<u8>(line_number, words[1])?;
+    let reason = match &words[2..] {
+        ["mistake"] => HintReason::Mistake,
+        ["naked_single"] => HintReason::NakedSingle,
+        ["hidden_single", "row", number] => HintReason::HiddenSingle(House::Row(parse_number(line_number, number)?)),
+        ["hidden_single", "column", number] => HintReason::HiddenSingle(House::Column(parse_number(line_number, number)?)),
+        ["hidden_single", "block", number] => HintReason::HiddenSingle(House::Block(parse_number(line_number, number)?)),
+        ["from_solution"] => HintReason::FromSolution,
+        _ => return Err(bad_hint()),
+    };
+    if index >= session.cell_count() || value == NO_VALUE || value > session.puzzle.max_value {
+        return Err(bad_hint());
+    }
+    Ok(Hint { index, value, reason })
+}
+
+fn parse_values(line_number: usize, text: &str, grid: &Grid) -> Result<Vec<u8>, SudokuError> {
+    let values = parse_numbers::<u8>(line_number, text)?;
+    if values.len() != grid.cell_count as usize {
+        return Err(load_error(line_number, &format!("Expected {} values but found {}.", grid.cell_count, values.len())));
+    }
+    if values.iter().any(|value| *value > grid.max_value) {
+        return Err(load_error(line_number, &format!("Values go from 0 for an empty cell to {}.", grid.max_value)));
+    }
+    Ok(values)
+}
+
+fn parse_numbers<T: FromStr>(line_number: usize, text: &str) -> Result<Vec<T>, SudokuError> {
+    text.split_whitespace().map(|word| parse_number(line_number, word)).collect()
+}
+
+fn parse_number<T: FromStr>(line_number: usize, word: &str) -> Result<T, SudokuError> {
+    word.trim().parse::<T>().map_err(|_| load_error(line_number, &format!("Couldn't read \"{}\".", word)))
+}
+
+fn load_error(line_number: usize, reason: &str) -> SudokuError {
+    SudokuError::InvalidText { reason: format!("Line {} of the session file: {}", line_number, reason) }
+}
@@ -5,18 +5,25 @@
 // This goes with grid::Grid.
 
 use crate::*;
-use super::*;
 use super::grid::Grid;
 use super::builder::Builder;
-use super::Runner;
+use super::{Runner, CancellationToken, Progress, SudokuError};
+use super::symmetry::{Symmetry, symmetric_index_combination};
+use super::unique::{Uniqueness, UniquenessChecker};
+use super::diagnostics::{self, Checkpoint, Diagnostics, diagnostic};
+use log::Level;
 // use itertools::Itertools;
-use rand::{thread_rng, Rng};
+use rand::Rng;
+use rand::seq::SliceRandom;
 use std::hash::{Hash, Hasher};
 use std::collections::hash_map::DefaultHasher;
 use std::time::{Instant, Duration};
 
-const VERBOSE: u8 = 0;
-const LOG_LEVEL: u8 = 2;
+// When picking symmetric index combinations, give up after this many attempts that either land on
+// the wrong number of cells or repeat a combination we already have.
+const MAX_FAILED_COMBINATION_ATTEMPTS: usize = 1_000;
+// How many units of clues the local search in reduce_minimal() takes out in each round.
+const LOCAL_SEARCH_REMOVE_COUNT: usize = 2;
 // https://emojipedia.org/
 
 pub fn main() {
@@ -24,6 +31,8 @@ pub fn main() {
     // try_unique_solution_type_2();
     // try_unique_solution_with_one_remaining_strategies();
     // try_reduce_exhaustive();
+    // try_reduce_symmetric();
+    // try_reduce_minimal();
     // time_clones_in_unique_solution_type();
     profile_unique_solution_type();
     // try_has_solution_flat();
@@ -203,6 +212,51 @@ fn try_reduce_exhaustive() {
     }
 }
 
+fn try_reduce_symmetric() {
+    let grid_size = 9;
+    let solve_limit_msec = 60_000;
+    let repeat_count = 3;
+
+    for symmetry in [Symmetry::Rotational180, Symmetry::Rotational90, Symmetry::MirrorDiagonal].iter() {
+        for _ in 0..repeat_count {
+            let grid = Builder::with_size(grid_size).build().unwrap();
+            let mut solver = Solver::new(&grid).limit_milliseconds(solve_limit_msec).symmetry(*symmetry);
+            match solver.reduce_symmetric() {
+                Ok(puzzle) => {
+                    println!("try_reduce_symmetric(): symmetry = {:?}, clues = {}, symmetric = {:?}, time = {:?}",
+                             symmetry, puzzle.solved_cell_count(), puzzle.is_symmetric(*symmetry), solver.runner.time.unwrap());
+                    puzzle.print_simple("");
+                },
+                Err(error) => println!("Solver error: {}", error),
+            }
+        }
+    }
+}
+
+fn try_reduce_minimal() {
+    let grid_size = 9;
+    let solve_limit_msec = 120_000;
+    let inner_limit_msec = 10_000;
+    let local_search_round_count = 20;
+    let repeat_count = 3;
+
+    for _ in 0..repeat_count {
+        let grid = Builder::with_size(grid_size).build().unwrap();
+        let mut solver = Solver::new(&grid)
+            .limit_milliseconds(solve_limit_msec)
+            .inner_limit_milliseconds(inner_limit_msec)
+            .local_search_rounds(local_search_round_count);
+        match solver.reduce_minimal() {
+            Ok(reduced_puzzle) => {
+                println!("try_reduce_minimal(): clues = {}, is_minimal = {}, time = {:?}",
+                         reduced_puzzle.grid.solved_cell_count(), reduced_puzzle.is_minimal, solver.runner.time.unwrap());
+                reduced_puzzle.grid.print_simple("");
+            },
+            Err(error) => println!("Solver error: {}", error),
+        }
+    }
+}
+
 fn time_clones_in_unique_solution_type() {
     let grid_size = 9;
     let remove_cell_count = 55;
@@ -285,9 +339,20 @@ pub enum SolverTask {
     CountSolutions,
     UniqueSolutionType,
     ReduceExhaustive,
+    ReduceSymmetric,
+    ReduceMinimal,
+    Backdoor,
     Unknown,
 }
 
+#[derive(Clone, Debug)]
+pub struct ReducedPuzzle {
+    pub grid: Grid,
+    pub solution: Grid,
+    // True if removing any single clue is known to give more than one solution.
+    pub is_minimal: bool,
+}
+
 #[derive(Debug)]
 pub enum UniqueSolutionType {
     Zero,
@@ -310,6 +375,11 @@ pub struct Solver {
     pub runner: Runner,
     pub unique_solution_type: Option<UniqueSolutionType>,
     pub one_remaining_strategy: SolverOneRemainingStrategy,
+    pub symmetry: Symmetry,
+    pub inner_time_limit: Option<Duration>,
+    pub local_search_round_count: usize,
+    #[derivative(Debug="ignore")]
+    uniqueness_checker: Option<UniquenessChecker>,
     pub min_cells_so_far: u16,
     pub solution_count: usize,
     #[derivative(Debug="ignore")]
@@ -321,13 +391,17 @@ pub struct Solver {
 }
 
 impl Solver {
-    fn new(grid: &Grid) -> Self {
+    pub fn new(grid: &Grid) -> Self {
         let solver = Self {
             task: SolverTask::Unknown,
             grid: grid.clone(),
             runner: Runner::new(None),
             unique_solution_type: None,
             one_remaining_strategy: SolverOneRemainingStrategy::Recursive,
+            symmetry: Symmetry::None,
+            inner_time_limit: None,
+            local_search_round_count: 0,
+            uniqueness_checker: None,
             min_cells_so_far: std::u16::MAX,
             solution_count: 0,
             solution_grid: None,
@@ -336,7 +410,7 @@ impl Solver {
             clone_grid_ref_count: 0,
             clone_time: Duration::from_millis(0),
         };
-        if VERBOSE >= 1 { dbg!(&solver); }
+        diagnostic!(Level::Trace, event = "solver_new"; "{:?}", solver);
         solver
     }
 
@@ -350,11 +424,50 @@ impl Solver {
         self
     }
 
-    pub fn count_solutions(&mut self) -> Result<usize, String> {
+    pub fn symmetry(mut self, symmetry: Symmetry) -> Self {
+        self.symmetry = symmetry;
+        self
+    }
+
+    pub fn inner_limit_milliseconds(mut self, msec: u64) -> Self {
+        self.inner_time_limit = Some(Duration::from_millis(msec));
+        self
+    }
+
+    pub fn local_search_rounds(mut self, local_search_round_count: usize) -> Self {
+        self.local_search_round_count = local_search_round_count;
+        self
+    }
+
+    pub fn cancellation_token(mut self, cancellation_token: CancellationToken) -> Self {
+        self.runner.control.cancellation_token = Some(cancellation_token);
+        self
+    }
+
+    pub fn node_limit(mut self, node_limit: usize) -> Self {
+        self.runner.control.node_limit = Some(node_limit);
+        self
+    }
+
+    pub fn diagnostics(mut self, diagnostics: Diagnostics) -> Self {
+        self.runner.control.diagnostics = diagnostics;
+        self
+    }
+
+    pub fn progress_callback<F>(mut self, progress_interval: usize, callback: F) -> Self
+        where F: Fn(&Progress) + Send + Sync + 'static
+    {
+        self.runner.control.set_progress_callback(progress_interval, callback);
+        self
+    }
+
+    pub fn count_solutions(&mut self) -> Result<usize, SudokuError> {
         self.task = SolverTask::CountSolutions;
-        self.runner = Runner::new(self.runner.time_limit);
+        self.runner = self.runner.restart();
+        let _diagnostics = self.runner.control.diagnostics.enter();
+        self.check_givens()?;
         let clone_grid = self.clone_grid();
-        let result = self.find_solutions(&clone_grid, &mut TriedGrids::new());
+        let result = self.find_solutions(&clone_grid, &mut TriedGrids::new(), 0);
         match result {
             Ok(_0) => {
                 self.runner.mark_end();
@@ -364,11 +477,13 @@ impl Solver {
         }
     }
 
-    pub fn unique_solution_type(&mut self, tried_grids: &mut TriedGrids) -> Result<UniqueSolutionType, String> {
+    pub fn unique_solution_type(&mut self, tried_grids: &mut TriedGrids) -> Result<UniqueSolutionType, SudokuError> {
         self.task = SolverTask::UniqueSolutionType;
-        self.runner = Runner::new(self.runner.time_limit);
+        self.runner = self.runner.restart();
+        let _diagnostics = self.runner.control.diagnostics.enter();
+        self.check_givens()?;
         let clone_grid = self.clone_grid();
-        let result = self.find_solutions(&clone_grid, tried_grids);
+        let result = self.find_solutions(&clone_grid, tried_grids, 0);
         match result {
             Ok(_0) => {
                 self.runner.mark_end();
@@ -382,18 +497,20 @@ impl Solver {
         }
     }
 
-    pub fn reduce_exhaustive(&mut self, inner_time_limit_msec: u64, ascending: bool, solved_cells_min: u16, solved_cells_max: u16) -> Result<Grid, String> {
+    pub fn reduce_exhaustive(&mut self, inner_time_limit_msec: u64, ascending: bool, solved_cells_min: u16, solved_cells_max: u16) -> Result<Grid, SudokuError> {
         self.task = SolverTask::CountSolutions;
-        self.runner = Runner::new(self.runner.time_limit);
-        let mut tried_grids = TriedGrids::new();
-        tried_grids.enabled = true;
+        self.runner = self.runner.restart();
+        let _diagnostics = self.runner.control.diagnostics.enter();
+        self.check_givens()?;
+        // Fail early if the symmetry doesn't fit the grid.
+        self.grid.symmetry_orbits(self.symmetry)?;
         for i in solved_cells_min..=solved_cells_max {
             let solved_cell_count = if ascending {
                 i
             } else {
                 self.grid.cell_count - i
             };
-            let result = self.reduce_exhaustive_try_number_of_cells(solved_cell_count, inner_time_limit_msec, ascending, &mut tried_grids);
+            let result = self.reduce_exhaustive_try_number_of_cells(solved_cell_count, inner_time_limit_msec, ascending);
             match result {
                 Ok(found_solution) => {
                     if found_solution {
@@ -406,13 +523,230 @@ impl Solver {
                 },
             }
         }
-        unreachable!()
+        // We tried every number of solved cells in the range without finding a combination with a
+        // unique solution. This isn't a limit being hit since the search finished.
+        self.runner.mark_end();
+        let (clue_count_min, clue_count_max) = if ascending {
+            (solved_cells_min, solved_cells_max)
+        } else {
+            (self.grid.cell_count - solved_cells_max, self.grid.cell_count - solved_cells_min)
+        };
+        Err(SudokuError::NoUniquePuzzle { clue_count_min, clue_count_max })
+    }
+
+    pub fn reduce_symmetric(&mut self) -> Result<Grid, SudokuError> {
+        // Remove whole orbits of clues under self.symmetry, one at a time in random order, keeping
+        // each removal only if the puzzle still has a unique solution. The result is symmetric and
+        // has a unique solution, and removing any one more orbit would break uniqueness.
+        self.task = SolverTask::ReduceSymmetric;
+        self.runner = self.runner.restart();
+        let _diagnostics = self.runner.control.diagnostics.enter();
+        self.check_givens()?;
+        let orbits = self.grid.symmetry_orbits(self.symmetry)?;
+        let (start_grid, _solution) = self.unique_start_grid()?;
+        let reduced_grid = self.reduce_greedy(&start_grid, &orbits)?;
+        self.runner.success = Some(true);
+        self.runner.mark_end();
+        self.solution_grid = Some(reduced_grid.clone());
+        Ok(reduced_grid)
+    }
+
+    pub fn reduce_minimal(&mut self) -> Result<ReducedPuzzle, SudokuError> {
+        // Remove clues greedily (whole orbits if self.symmetry is set) while the puzzle keeps a
+        // unique solution, then optionally run a local search to get below that count. Finally
+        // check whether removing any single remaining clue gives more than one solution. That
+        // check can only fail if an inner solve ran out of time, or if the symmetry kept a clue
+        // that isn't needed on its own.
+        self.task = SolverTask::ReduceMinimal;
+        self.runner = self.runner.restart();
+        let _diagnostics = self.runner.control.diagnostics.enter();
+        self.check_givens()?;
+        let units = self.grid.symmetry_orbits(self.symmetry)?;
+        let (start_grid, solution) = self.unique_start_grid()?;
+        let mut best_grid = self.reduce_greedy(&start_grid, &units)?;
+        for _ in 0..self.local_search_round_count {
+            if let Some(try_grid) = self.perturb(&best_grid, &solution, &units)? {
+                let try_grid = self.reduce_greedy(&try_grid, &units)?;
+                // Accept ties as well so the search can move sideways across puzzles with the same
+                // number of clues.
+                if try_grid.solved_cell_count() <= best_grid.solved_cell_count() {
+                    best_grid = try_grid;
+                }
+            }
+        }
+        let is_minimal = self.verify_minimal(&best_grid)?;
+        self.runner.success = Some(true);
+        self.runner.mark_end();
+        self.solution_grid = Some(best_grid.clone());
+        Ok(ReducedPuzzle {
+            grid: best_grid,
+            solution,
+            is_minimal,
+        })
+    }
+
+    pub fn verify_minimal(&mut self, puzzle: &Grid) -> Result<bool, SudokuError> {
+        // Returns true only if we can show that the puzzle has a unique solution and that removing
+        // any one of its clues gives more than one solution. An inner solve that runs out of time
+        // counts as not shown.
+        let _diagnostics = self.runner.control.diagnostics.enter();
+        if let Some(UniqueSolutionType::One) = self.check_unique(puzzle)? {
+            for index in 0..puzzle.cell_count {
+                if puzzle.values[index as usize] != NO_VALUE {
+                    let try_grid = Self::without_cells(puzzle, &[index]);
+                    match self.check_unique(&try_grid)? {
+                        Some(UniqueSolutionType::Many) => {},
+                        _ => return Ok(false),
+                    }
+                }
+            }
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn unique_start_grid(&mut self) -> Result<(Grid, Grid), SudokuError> {
+        // Returns a puzzle with a unique solution to start reducing from, along with that
+        // solution. If self.grid has several solutions we start from one of them instead.
+        let grid = self.grid.clone();
+        let mut solution = self.grid.clone();
+        match self.uniqueness_checker(self.inner_time_limit)?.check_grid(&grid)? {
+            Uniqueness::Zero => Err(SudokuError::Unsatisfiable { elapsed: self.runner.elapsed(), node_count: self.runner.node_count }),
+            Uniqueness::One { solution: values } => {
+                solution.replace_values(&values);
+                Ok((grid, solution))
+            },
+            Uniqueness::Many { solution_1: values, .. } => {
+                solution.replace_values(&values);
+                Ok((solution.clone(), solution))
+            },
+        }
+    }
+
+    pub fn uniqueness(&mut self) -> Result<Uniqueness, SudokuError> {
+        // Like unique_solution_type() but using the fast checker, which stops at the second
+        // solution and returns the solutions it found. When there are two they show where the
+        // puzzle is ambiguous.
+        self.runner = self.runner.restart();
+        let _diagnostics = self.runner.control.diagnostics.enter();
+        self.check_givens()?;
+        let grid = self.grid.clone();
+        let result = self.uniqueness_checker(self.runner.time_limit)?.check_grid(&grid);
+        self.runner.mark_end();
+        result
+    }
+
+    fn uniqueness_checker(&mut self, time_limit: Option<Duration>) -> Result<&mut UniquenessChecker, SudokuError> {
+        // The checker is created the first time it's needed and then reused for every check, since
+        // the grids being checked all have the same size and rules as self.grid.
+        if self.uniqueness_checker.is_none() {
+            self.uniqueness_checker = Some(UniquenessChecker::new(&self.grid)?);
+        }
+        let checker = self.uniqueness_checker.as_mut().unwrap();
+        checker.time_limit = time_limit;
+        checker.cancellation_token = self.runner.control.cancellation_token.clone();
+        Ok(checker)
+    }
+
+    fn reduce_greedy(&mut self, start_grid: &Grid, units: &[Vec<u16>]) -> Result<Grid, SudokuError> {
+        // Try removing each unit of cells once, in random order, and keep each removal that leaves
+        // a unique solution. Removing clues only adds solutions, so a unit that couldn't be removed
+        // early on still can't be removed at the end.
+        let mut units = units.to_vec();
+        units.shuffle(&mut rng());
+        let mut reduced_grid = start_grid.clone();
+        for unit in units.iter() {
+            if unit.iter().all(|index| reduced_grid.values[*index as usize] == NO_VALUE) {
+                continue;
+            }
+            let try_grid = Self::without_cells(&reduced_grid, unit);
+            if let Some(UniqueSolutionType::One) = self.check_unique(&try_grid)? {
+                reduced_grid = try_grid;
+                self.runner.record_best(reduced_grid.solved_cell_count() as usize, true);
+            }
+        }
+        Ok(reduced_grid)
+    }
+
+    fn perturb(&mut self, puzzle: &Grid, solution: &Grid, units: &[Vec<u16>]) -> Result<Option<Grid>, SudokuError> {
+        // One step of the local search: take out a few units of clues, then put back other units
+        // from the solution in random order until the solution is unique again. Returns None if it
+        // never is, which can happen because the units taken out are never put back.
+        let mut filled_units = units.iter()
+            .filter(|unit| unit.iter().all(|index| puzzle.values[*index as usize] != NO_VALUE))
+            .collect::<Vec<_>>();
+        let mut empty_units = units.iter()
+            .filter(|unit| unit.iter().all(|index| puzzle.values[*index as usize] == NO_VALUE))
+            .collect::<Vec<_>>();
+        filled_units.shuffle(&mut rng());
+        empty_units.shuffle(&mut rng());
+        let mut try_values = puzzle.values.clone();
+        for unit in filled_units.iter().take(LOCAL_SEARCH_REMOVE_COUNT) {
+            for index in unit.iter() {
+                try_values[*index as usize] = NO_VALUE;
+            }
+        }
+        for unit in empty_units.iter() {
+            for index in unit.iter() {
+                try_values[*index as usize] = solution.values[*index as usize];
+            }
+            let mut try_grid = puzzle.clone();
+            try_grid.replace_values(&try_values);
+            if let Some(UniqueSolutionType::One) = self.check_unique(&try_grid)? {
+                return Ok(Some(try_grid));
+            }
+        }
+        Ok(None)
+    }
+
+    fn check_unique(&mut self, grid: &Grid) -> Result<Option<UniqueSolutionType>, SudokuError> {
+        // Returns None if the inner solve ran out of time, in which case we don't know the answer.
+        if !self.runner.count_node(0, grid.solved_cell_count()) || !self.runner.check_continue() {
+            return Err(self.runner.failure_clone());
+        }
+        let checker = self.uniqueness_checker(self.inner_time_limit)?;
+        match checker.check_grid(grid) {
+            Ok(uniqueness) => Ok(Some(uniqueness.solution_type())),
+            Err(SudokuError::TimeLimitExceeded { .. }) => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    fn without_cells(grid: &Grid, indexes: &[u16]) -> Grid {
+        let mut values = grid.values.clone();
+        for index in indexes.iter() {
+            values[*index as usize] = NO_VALUE;
+        }
+        let mut new_grid = grid.clone();
+        new_grid.replace_values(&values);
+        new_grid
+    }
+
+    pub(crate) fn check_givens(&self) -> Result<(), SudokuError> {
+        // Make sure the starting grid doesn't already break the rules, either by having the same
+        // value in two related cells or by leaving an empty cell with nothing it could hold.
+        let grid = &self.grid;
+        for index in 0..grid.cell_count {
+            let value = grid.values[index as usize];
+            let (row, column, _block) = grid.row_col_block(index);
+            if value == NO_VALUE {
+                if grid.remaining_value_counts[index as usize] == 0 {
+                    return Err(SudokuError::ContradictoryGivens { index, row, column, value, conflicting_index: None });
+                }
+            } else if let Some(conflicting_index) = grid.index_to_related_cell_indexes(index)
+                    .into_iter()
+                    .find(|related_cell_index| grid.values[*related_cell_index as usize] == value) {
+                return Err(SudokuError::ContradictoryGivens { index, row, column, value, conflicting_index: Some(conflicting_index) });
+            }
+        }
+        Ok(())
     }
 
     /*
     pub fn reduce_exhaustive(&mut self, inner_time_limit_msec: u64) -> Result<Grid, String> {
         self.task = SolverTask::CountSolutions;
-        self.runner = Runner::new(self.runner.time_limit);
+        self.runner = self.runner.restart();
         let result = self.reduce_exhaustive_next_cell(&self.grid.clone(), 0, inner_time_limit_msec);
         match result {
             Ok(_0) => {
@@ -427,7 +761,7 @@ impl Solver {
     /*
     pub fn reduce_with_single_solution(&mut self) -> Result<usize, Grid> {
         self.task = SolverTask::ReduceWithSingleSolution;
-        self.runner = Runner::new(self.runner.time_limit);
+        self.runner = self.runner.restart();
         let mut try_grid = self.grid.clone();
         while try_grid.solved_cell_count() > 0 {
             try_grid.remove_cells(1);
@@ -442,19 +776,27 @@ impl Solver {
     }
     */
 
-    fn find_solutions(&mut self, grid_to_now: &Grid, tried_grids: &mut TriedGrids) -> Result<(), String> {
-        if !self.runner.check_continue() {
+    fn find_solutions(&mut self, grid_to_now: &Grid, tried_grids: &mut TriedGrids, depth: u16) -> Result<(), SudokuError> {
+        if !self.runner.count_node(depth, grid_to_now.solved_cell_count()) || !self.runner.check_continue() {
             if self.runner.success.unwrap() {
                 return Ok(());
             } else {
-                return Err(self.runner.failure_message_clone());
+                return Err(self.runner.failure_clone());
             }
         }
 
         // Simply take the first empty cell.
-        let try_cell_index = (0..grid_to_now.cell_count)
-            .find(|index| grid_to_now.values[*index as usize] == NO_VALUE)
-            .unwrap();
+        let try_cell_index = match (0..grid_to_now.cell_count)
+            .find(|index| grid_to_now.values[*index as usize] == NO_VALUE) {
+            Some(index) => index,
+            None => {
+                // We were given a complete grid. The givens have already been checked so it's the
+                // one and only solution.
+                self.solution_count += 1;
+                self.solution_grid = Some(grid_to_now.clone());
+                return Ok(());
+            }
+        };
 
         for try_value in 1..=grid_to_now.max_value {
             let has_remaining_value = grid_to_now.has_remaining_value(try_cell_index, try_value);
@@ -479,6 +821,12 @@ impl Solver {
                                     // The grid is complete. What we do with that fact depends on what solver task
                                     // we're trying to accomplish.
                                     self.solution_count += 1;
+                                    self.runner.record_best(self.solution_count, false);
+                                    if self.solution_count == 1 {
+                                        // Hold on to the first solution in case the caller needs
+                                        // a complete grid.
+                                        self.solution_grid = Some(try_grid.clone());
+                                    }
                                     match &self.task {
                                         SolverTask::CountSolutions => {},
                                         SolverTask::UniqueSolutionType => {
@@ -495,7 +843,7 @@ impl Solver {
                                 } else {
                                     // The grid is valid though still incomplete, so keep going by filling in
                                     // another cell.
-                                    let result = self.find_solutions(&try_grid, tried_grids);
+                                    let result = self.find_solutions(&try_grid, tried_grids, depth + 1);
                                     match result {
                                         Ok(_0) => {}
                                         _ => return result
@@ -511,7 +859,7 @@ impl Solver {
                         if self.runner.success.unwrap() {
                             return Ok(());
                         } else {
-                            return Err(self.runner.failure_message_clone());
+                            return Err(self.runner.failure_clone());
                         }
                     }
                 }
@@ -520,13 +868,13 @@ impl Solver {
         Ok(())
     }
 
-    fn set_value(&mut self, grid: &mut Grid, index: u16, value: u8) -> Result<bool, String> {
+    fn set_value(&mut self, grid: &mut Grid, index: u16, value: u8) -> Result<bool, SudokuError> {
         // Return true if the grid was still valid after setting the value. That is, none of the
         // other cells ended up with zero possible values.
 
         if !self.runner.check_continue() {
             // We're out of time.
-            return Err(self.runner.failure_message_clone());
+            return Err(self.runner.failure_clone());
         }
 
         debug_assert!(grid.values[index as usize] == NO_VALUE);
@@ -567,7 +915,7 @@ impl Solver {
                 }
             }
 
-            if RUN_INVARIANT { grid.invariant(); }
+            if diagnostics::checks(Checkpoint::SolveStep) { grid.invariant(); }
 
             match self.one_remaining_strategy {
                 SolverOneRemainingStrategy::Recursive => {
@@ -602,12 +950,12 @@ impl Solver {
             }
         }
 
-        if RUN_INVARIANT { grid.invariant(); }
+        if diagnostics::checks(Checkpoint::SolveStep) { grid.invariant(); }
 
         Ok(true)
     }
 
-    fn resolve_cells_with_one_remaining(&mut self, grid: &mut Grid) -> bool {
+    pub(crate) fn resolve_cells_with_one_remaining(&mut self, grid: &mut Grid) -> bool {
         // Returns true if it's OK to keep moving forward with this partial grid because there
         // are no unsolved cells with zero remaining value or false if there are such cells
         // meaning the partial grid must be abandoned.
@@ -636,7 +984,7 @@ impl Solver {
         }
     }
 
-    fn reduce_exhaustive_try_number_of_cells(&mut self, solved_cell_count: u16, inner_time_limit_msec: u64, ascending: bool, tried_grids: &mut TriedGrids) -> Result<bool, String> {
+    fn reduce_exhaustive_try_number_of_cells(&mut self, solved_cell_count: u16, inner_time_limit_msec: u64, ascending: bool) -> Result<bool, SudokuError> {
 
         let combination_limit = 10_000;
         let include = solved_cell_count < self.grid.cell_count / 2;
@@ -644,7 +992,7 @@ impl Solver {
         let found_many = false;
 
         if !self.runner.check_continue() {
-            return Err(self.runner.failure_message_clone());
+            return Err(self.runner.failure_clone());
         }
 
         let mut good_index_combinations = vec![];
@@ -659,29 +1007,27 @@ impl Solver {
 
         //let index_combinations = (0..self.grid.cell_count ).combinations(solved_cell_count as usize).collect::<Vec<_>>();
         //bg!(solved_cell_count, &index_combinations.len());
-        //bg!(&tried_grids);
         // for index_combination in index_combinations {
-        let mut combinations_tried = 0;
         while !index_combinations.is_empty() {
-            let one_combination = index_combinations.remove(thread_rng().gen_range(0, index_combinations.len()));
+            let one_combination = index_combinations.remove(rng().gen_range(0, index_combinations.len()));
             //bg!(&index_combination);
             let try_grid = self.grid.partial_grid_from_indexes(&one_combination, include);
-            combinations_tried += 1;
-            //dbg!(combinations_tried, &one_combination);
-            println!("reduce_exhaustive_try_number_of_cells(): solved_cell_count = {}, combinations_tried = {}", solved_cell_count, combinations_tried);
+            if !self.runner.count_node(0, solved_cell_count) {
+                return Err(self.runner.failure_clone());
+            }
             //try_grid.print_simple("");
             //bg!(&try_grid);
             // try_grid.print_simple_and_remaining("reduce_exhaustive_try_number_of_cells()");
-            let mut solver = Solver::new(&try_grid).limit_milliseconds(inner_time_limit_msec);
-            let result = solver.unique_solution_type(tried_grids);
-            //bg!(&solver);
+            let checker = self.uniqueness_checker(Some(Duration::from_millis(inner_time_limit_msec)))?;
+            let result = checker.check_grid(&try_grid);
             match result {
-                Ok(unique_solution_type) => {
-                    match unique_solution_type {
+                Ok(uniqueness) => {
+                    match uniqueness.solution_type() {
                         UniqueSolutionType::Zero => {
                         }
                         UniqueSolutionType::One => {
                             // This is a legal grid with a unique solution.
+                            self.runner.record_best(solved_cell_count as usize, true);
                             if ascending {
                                 self.solution_grid = Some(self.grid.partial_grid_from_indexes(&one_combination, include));
                                 self.runner.success = Some(true);
@@ -705,12 +1051,10 @@ impl Solver {
                 }
             }
             if !self.runner.check_continue() {
-                return Err(self.runner.failure_message_clone());
+                return Err(self.runner.failure_clone());
             }
         }
 
-        println!("reduce_exhaustive_try_number_of_cells(): solved_cell_count = {}, single solution options = {}", solved_cell_count, good_index_combinations.len());
-
         if good_index_combinations.is_empty() {
             return Ok(false);
         }
@@ -722,7 +1066,7 @@ impl Solver {
                 0
             } else {
                 // We found more than one good index combination so choose one at random.
-                thread_rng().gen_range(0, good_index_combinations.len())
+                rng().gen_range(0, good_index_combinations.len())
             };
             self.solution_grid = Some(self.grid.partial_grid_from_indexes(&good_index_combinations[index_combination_index], include));
             self.runner.success = Some(true);
@@ -748,13 +1092,16 @@ impl Solver {
     }
 
     fn index_combinations(&self, solved_cell_count: usize, effective_combination_limit: usize) -> Vec<Vec<u16>> {
+        if self.symmetry != Symmetry::None {
+            return self.index_combinations_symmetric(solved_cell_count, effective_combination_limit);
+        }
         let cell_count = self.grid.cell_count as usize;
         //rintln!("index_combinations(): cell_count = {}, solved_cell_count = {}, effective_combination_limit = {}", cell_count, solved_cell_count, effective_combination_limit);
         let mut v = Vec::with_capacity(effective_combination_limit);
         while v.len() < effective_combination_limit {
             let mut one_combination = Vec::with_capacity(solved_cell_count);
             while one_combination.len() < solved_cell_count {
-                let index = thread_rng().gen_range(0, cell_count) as u16;
+                let index = rng().gen_range(0, cell_count) as u16;
                 if !one_combination.contains(&index) {
                     one_combination.push(index as u16);
                 }
@@ -772,6 +1119,22 @@ impl Solver {
         v
     }
 
+    fn index_combinations_symmetric(&self, solved_cell_count: usize, effective_combination_limit: usize) -> Vec<Vec<u16>> {
+        // Each combination is a union of whole orbits. Not every size can be made from orbits (with
+        // 90 degree symmetry most orbits have four cells) and there may be fewer distinct
+        // combinations than the limit, so this can return fewer combinations than asked for.
+        let orbits = self.grid.symmetry_orbits(self.symmetry).unwrap();
+        let mut v = Vec::with_capacity(effective_combination_limit);
+        let mut failed_attempt_count = 0;
+        while v.len() < effective_combination_limit && failed_attempt_count < MAX_FAILED_COMBINATION_ATTEMPTS {
+            match symmetric_index_combination(&orbits, solved_cell_count) {
+                Some(one_combination) if !v.contains(&one_combination) => v.push(one_combination),
+                _ => failed_attempt_count += 1,
+            }
+        }
+        v
+    }
+
 /*
     pub clone_grid_count: u16,
     pub clone_solution_grid_count: u16,
@@ -811,7 +1174,7 @@ impl Solver {
         }
 
         if !self.runner.check_continue() {
-            return Err(self.runner.failure_message_clone());
+            return Err(self.runner.failure_clone());
         }
 
         //bg!(min_index);
@@ -868,7 +1231,7 @@ impl Solver {
 
 #[derive(Derivative)]
 #[derivative(Debug)]
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct TriedGrids {
     pub enabled: bool,
     pub registered_count: usize,
@@ -878,7 +1241,7 @@ pub struct TriedGrids {
 }
 
 impl TriedGrids {
-    fn new() -> Self {
+    pub fn new() -> Self {
         Self {
             enabled: false,
             registered_count: 0,
@@ -0,0 +1,131 @@
+#![allow(dead_code)]
+
+// Symmetric clue patterns. A symmetry splits the cells into orbits, the sets of cells that the
+// symmetry moves onto each other, and a symmetric puzzle either gives every cell in an orbit or
+// none of them.
+// This goes with grid::Grid and solver::Solver.
+
+use rand::Rng;
+use rand::seq::SliceRandom;
+
+use crate::*;
+use super::grid::Grid;
+use super::SudokuError;
+use super::diagnostics::{self, Checkpoint};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Symmetry {
+    None,
+    Rotational180,
+    Rotational90,
+    // Mirror the grid left to right.
+    MirrorHorizontal,
+    // Mirror the grid top to bottom.
+    MirrorVertical,
+    // Mirror across the diagonal from the top left to the bottom right.
+    MirrorDiagonal,
+    // Mirror across the diagonal from the top right to the bottom left.
+    MirrorAntiDiagonal,
+}
+
+impl Grid {
+
+    pub fn symmetry_orbits(&self, symmetry: Symmetry) -> Result<Vec<Vec<u16>>, SudokuError> {
+        // Returns the orbits in order of their lowest cell index, each one sorted. With
+        // Symmetry::None every cell is its own orbit.
+        let needs_square = matches!(symmetry, Symmetry::Rotational90 | Symmetry::MirrorDiagonal | Symmetry::MirrorAntiDiagonal);
+        if needs_square && self.width != self.height {
+            return Err(SudokuError::InvalidTransform { reason: format!("{:?} symmetry needs a square grid.", symmetry) });
+        }
+        let mut orbits = vec![];
+        let mut in_orbit = vec![false; self.cell_count as usize];
+        for index in 0..self.cell_count {
+            if in_orbit[index as usize] {
+                continue;
+            }
+            let mut orbit = vec![];
+            let mut orbit_index = index;
+            while !in_orbit[orbit_index as usize] {
+                in_orbit[orbit_index as usize] = true;
+                orbit.push(orbit_index);
+                orbit_index = self.symmetric_index(orbit_index, symmetry);
+            }
+            orbit.sort();
+            orbits.push(orbit);
+        }
+        Ok(orbits)
+    }
+
+    #[inline]
+    fn symmetric_index(&self, index: u16, symmetry: Symmetry) -> u16 {
+        let last_row = self.height as u16 - 1;
+        let last_column = self.width as u16 - 1;
+        let row = index / self.width as u16;
+        let column = index % self.width as u16;
+        let (row, column) = match symmetry {
+            Symmetry::None => (row, column),
+            Symmetry::Rotational180 => (last_row - row, last_column - column),
+            Symmetry::Rotational90 => (column, last_column - row),
+            Symmetry::MirrorHorizontal => (row, last_column - column),
+            Symmetry::MirrorVertical => (last_row - row, column),
+            Symmetry::MirrorDiagonal => (column, row),
+            Symmetry::MirrorAntiDiagonal => (last_column - column, last_row - row),
+        };
+        (row * self.width as u16) + column
+    }
+
+    pub fn remove_cells_symmetric(&mut self, remove_cell_count: u16, symmetry: Symmetry) -> Result<(), SudokuError> {
+        // Like remove_cells() but empties whole orbits at a time, picking them at random. This
+        // never empties more than remove_cell_count cells, so with orbits larger than one cell it
+        // may empty a few less.
+        if diagnostics::checks(Checkpoint::GridChange) { self.invariant(); }
+        let mut orbits = self.symmetry_orbits(symmetry)?
+            .into_iter()
+            .filter(|orbit| orbit.iter().all(|index| self.values[*index as usize] != NO_VALUE))
+            .collect::<Vec<_>>();
+        let mut removed_count = 0;
+        while !orbits.is_empty() {
+            let orbit = orbits.remove(rng().gen_range(0, orbits.len()));
+            if removed_count + orbit.len() as u16 <= remove_cell_count {
+                for index in orbit.iter() {
+                    self.values[*index as usize] = NO_VALUE;
+                }
+                self.unsolved_cell_count += orbit.len() as u16;
+                removed_count += orbit.len() as u16;
+            }
+        }
+        self.recalc_remaining_values();
+        if diagnostics::checks(Checkpoint::GridChange) { self.invariant(); }
+        Ok(())
+    }
+
+    pub fn is_symmetric(&self, symmetry: Symmetry) -> Result<bool, SudokuError> {
+        // Returns true if the pattern of filled cells has the given symmetry.
+        Ok(self.symmetry_orbits(symmetry)?
+            .iter()
+            .all(|orbit| {
+                let filled_count = orbit.iter().filter(|index| self.values[**index as usize] != NO_VALUE).count();
+                filled_count == 0 || filled_count == orbit.len()
+            }))
+    }
+
+}
+
+pub(crate) fn symmetric_index_combination(orbits: &[Vec<u16>], combination_size: usize) -> Option<Vec<u16>> {
+    // Pick random orbits adding up to exactly combination_size cells, or return None if this
+    // attempt didn't land on the exact size.
+    let mut shuffled_orbits = orbits.iter().collect::<Vec<_>>();
+    shuffled_orbits.shuffle(&mut rng());
+    let mut one_combination = Vec::with_capacity(combination_size);
+    for orbit in shuffled_orbits {
+        if one_combination.len() + orbit.len() <= combination_size {
+            one_combination.extend(orbit.iter());
+        }
+    }
+    if one_combination.len() == combination_size {
+        one_combination.sort();
+        Some(one_combination)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,354 @@
+#![allow(dead_code)]
+
+// Transformations that turn one grid into another grid following the same rules: permuting the
+// rows within a band and the bands themselves, the same for columns and stacks, rotating,
+// reflecting, transposing, and relabelling the values. With nonstandard rules such as the diagonals
+// only some of these keep the grid valid, and Grid::transformed() refuses the others.
+// This goes with grid::Grid.
+
+use itertools::Itertools;
+use rand::seq::SliceRandom;
+
+use crate::*;
+use super::grid::Grid;
+use super::SudokuError;
+use super::diagnostics::{self, Checkpoint};
+use super::builder::{Builder, RULE_DIAGONALS};
+
+pub fn main() {
+    try_transforms();
+}
+
+fn try_transforms() {
+    let grid_size = 9;
+    let remove_cell_count = 45;
+
+    let mut grid = Builder::with_size(grid_size).rule(RULE_DIAGONALS).build().unwrap();
+    grid.remove_cells(remove_cell_count);
+    grid.print_simple("try_transforms(): original");
+    for (label, result) in [
+        ("rotate_90", grid.rotate_90()),
+        ("reflect_horizontal", grid.reflect_horizontal()),
+        ("transpose_anti", grid.transpose_anti()),
+        ("swap_rows", grid.swap_rows(0, 1)),
+        ("swap_bands", grid.swap_bands(0, 2)),
+    ].iter() {
+        match result {
+            Ok(transformed_grid) => transformed_grid.print_simple(&format!("try_transforms(): {}", label)),
+            Err(error) => println!("\ntry_transforms(): {}: {}", label, error),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct GridTransform {
+    pub transpose: bool,
+    // Row r of the new grid comes from row row_order[r] of the old grid, and column c comes from
+    // column column_order[c]. If transpose is true the roles are swapped, so the new cell at row r
+    // and column c comes from the old cell at row row_order[c] and column column_order[r].
+    pub row_order: Vec<u8>,
+    pub column_order: Vec<u8>,
+    // relabel[value] is the value that replaces value in the new grid. relabel[0] is always
+    // NO_VALUE so that empty cells stay empty.
+    pub relabel: Vec<u8>,
+}
+
+impl GridTransform {
+
+    pub fn identity(grid: &Grid) -> Self {
+        Self {
+            transpose: false,
+            row_order: (0..grid.height).collect(),
+            column_order: (0..grid.width).collect(),
+            relabel: (0..=grid.max_value).collect(),
+        }
+    }
+
+    #[inline]
+    pub fn source_index(&self, grid: &Grid, index: u16) -> u16 {
+        // The index of the cell in the old grid that supplies the cell at index in the new grid.
+        let row = index / grid.width as u16;
+        let column = index % grid.width as u16;
+        let (row, column) = if self.transpose { (column, row) } else { (row, column) };
+        let source_row = self.row_order[row as usize] as u16;
+        let source_column = self.column_order[column as usize] as u16;
+        (source_row * grid.width as u16) + source_column
+    }
+
+    pub fn apply_to_values(&self, grid: &Grid) -> Vec<u8> {
+        (0..grid.cell_count)
+            .map(|index| {
+                let value = grid.values[self.source_index(grid, index) as usize];
+                self.relabel[value as usize]
+            })
+            .collect()
+    }
+
+    pub fn apply(&self, grid: &Grid) -> Grid {
+        // Move every cell's value and remaining values to its new position and relabel them. This
+        // doesn't check whether the result still follows the grid's rules, so outside of this
+        // module use Grid::transformed().
+        let mut transformed_grid = grid.clone_empty();
+        for index in 0..grid.cell_count {
+            let source_index = self.source_index(grid, index);
+            let value = grid.values[source_index as usize];
+            transformed_grid.values[index as usize] = self.relabel[value as usize];
+            for value in 1..=grid.max_value {
+                let new_value = self.relabel[value as usize];
+                if grid.has_remaining_value(source_index, value) {
+                    transformed_grid.set_remaining_value(index, new_value);
+                } else {
+                    transformed_grid.clear_remaining_value(index, new_value);
+                }
+            }
+        }
+        transformed_grid.unsolved_cell_count = grid.unsolved_cell_count;
+        if diagnostics::checks(Checkpoint::GridChange) { transformed_grid.invariant(); }
+        transformed_grid
+    }
+
+    pub fn inverse(&self) -> Self {
+        let mut row_order = self.row_order.clone();
+        for (new_row, old_row) in self.row_order.iter().enumerate() {
+            row_order[*old_row as usize] = new_row as u8;
+        }
+        let mut column_order = self.column_order.clone();
+        for (new_column, old_column) in self.column_order.iter().enumerate() {
+            column_order[*old_column as usize] = new_column as u8;
+        }
+        let mut relabel = self.relabel.clone();
+        for (old_value, new_value) in self.relabel.iter().enumerate() {
+            relabel[*new_value as usize] = old_value as u8;
+        }
+        if self.transpose {
+            // Transposing swaps the roles of the row and column orders.
+            Self { transpose: true, row_order: column_order, column_order: row_order, relabel }
+        } else {
+            Self { transpose: false, row_order, column_order, relabel }
+        }
+    }
+
+    fn check_shape(&self, grid: &Grid) -> Result<(), SudokuError> {
+        let invalid_transform = |reason: &str| Err(SudokuError::InvalidTransform { reason: reason.to_string() });
+        if self.transpose && grid.width != grid.height {
+            return invalid_transform("Only a square grid can be transposed or rotated by 90 degrees.");
+        }
+        if !is_permutation(&self.row_order, grid.height) {
+            return invalid_transform("The row order must list every row exactly once.");
+        }
+        if !is_permutation(&self.column_order, grid.width) {
+            return invalid_transform("The column order must list every column exactly once.");
+        }
+        if self.relabel.len() != grid.max_value as usize + 1 || self.relabel[0] != NO_VALUE
+            || !is_permutation(&self.relabel[1..].iter().map(|value| value.wrapping_sub(1)).collect::<Vec<_>>(), grid.max_value) {
+            return invalid_transform("The relabelling must map the values one-to-one onto the same values.");
+        }
+        Ok(())
+    }
+
+}
+
+impl Grid {
+
+    pub fn transformed(&self, transform: &GridTransform) -> Result<Grid, SudokuError> {
+        // Returns a new grid with the transformation applied to the values, remaining values, and
+        // related cells. If a cell in the new grid would end up with a different set of related
+        // cells than the same position has now, the transformation doesn't fit the grid's rules
+        // (for instance swapping two rows when there's a diagonal rule) and is refused.
+        transform.check_shape(self)?;
+        let inverse = transform.inverse();
+        for index in 0..self.cell_count {
+            let source_index = transform.source_index(self, index);
+            let mut moved_related_cell_indexes = self.related_cell_indexes_or_standard(source_index)
+                .iter()
+                .map(|related_cell_index| inverse.source_index(self, *related_cell_index))
+                .collect::<Vec<_>>();
+            moved_related_cell_indexes.sort();
+            let related_cell_indexes = self.related_cell_indexes_or_standard(index);
+            if moved_related_cell_indexes != related_cell_indexes {
+                // Report a cell that would gain or lose its relationship with this one.
+                let related_index = moved_related_cell_indexes
+                    .iter()
+                    .find(|related_cell_index| !related_cell_indexes.contains(related_cell_index))
+                    .or_else(|| related_cell_indexes.iter().find(|related_cell_index| !moved_related_cell_indexes.contains(related_cell_index)))
+                    .copied()
+                    .unwrap();
+                return Err(SudokuError::TransformBreaksRules { index, related_index });
+            }
+        }
+        Ok(transform.apply(self))
+    }
+
+    pub fn rotate_90(&self) -> Result<Grid, SudokuError> {
+        // Clockwise.
+        let mut transform = GridTransform::identity(self);
+        transform.transpose = true;
+        transform.row_order.reverse();
+        self.transformed(&transform)
+    }
+
+    pub fn rotate_180(&self) -> Result<Grid, SudokuError> {
+        let mut transform = GridTransform::identity(self);
+        transform.row_order.reverse();
+        transform.column_order.reverse();
+        self.transformed(&transform)
+    }
+
+    pub fn rotate_270(&self) -> Result<Grid, SudokuError> {
+        // Counterclockwise.
+        let mut transform = GridTransform::identity(self);
+        transform.transpose = true;
+        transform.column_order.reverse();
+        self.transformed(&transform)
+    }
+
+    pub fn reflect_horizontal(&self) -> Result<Grid, SudokuError> {
+        // Mirror the grid left to right.
+        let mut transform = GridTransform::identity(self);
+        transform.column_order.reverse();
+        self.transformed(&transform)
+    }
+
+    pub fn reflect_vertical(&self) -> Result<Grid, SudokuError> {
+        // Mirror the grid top to bottom.
+        let mut transform = GridTransform::identity(self);
+        transform.row_order.reverse();
+        self.transformed(&transform)
+    }
+
+    pub fn transpose(&self) -> Result<Grid, SudokuError> {
+        // Reflect across the main diagonal.
+        let mut transform = GridTransform::identity(self);
+        transform.transpose = true;
+        self.transformed(&transform)
+    }
+
+    pub fn transpose_anti(&self) -> Result<Grid, SudokuError> {
+        // Reflect across the diagonal running from the top right to the bottom left.
+        let mut transform = GridTransform::identity(self);
+        transform.transpose = true;
+        transform.row_order.reverse();
+        transform.column_order.reverse();
+        self.transformed(&transform)
+    }
+
+    pub fn relabel(&self, new_values: &[u8]) -> Result<Grid, SudokuError> {
+        // Value v becomes new_values[v - 1].
+        let mut transform = GridTransform::identity(self);
+        transform.relabel = std::iter::once(NO_VALUE).chain(new_values.iter().copied()).collect();
+        self.transformed(&transform)
+    }
+
+    pub fn swap_rows(&self, row_1: u8, row_2: u8) -> Result<Grid, SudokuError> {
+        let mut transform = GridTransform::identity(self);
+        swap_lines(&mut transform.row_order, row_1, row_2, 1)?;
+        self.transformed(&transform)
+    }
+
+    pub fn swap_columns(&self, column_1: u8, column_2: u8) -> Result<Grid, SudokuError> {
+        let mut transform = GridTransform::identity(self);
+        swap_lines(&mut transform.column_order, column_1, column_2, 1)?;
+        self.transformed(&transform)
+    }
+
+    pub fn swap_bands(&self, band_1: u8, band_2: u8) -> Result<Grid, SudokuError> {
+        let mut transform = GridTransform::identity(self);
+        swap_lines(&mut transform.row_order, band_1, band_2, self.block_height)?;
+        self.transformed(&transform)
+    }
+
+    pub fn swap_stacks(&self, stack_1: u8, stack_2: u8) -> Result<Grid, SudokuError> {
+        let mut transform = GridTransform::identity(self);
+        swap_lines(&mut transform.column_order, stack_1, stack_2, self.block_width)?;
+        self.transformed(&transform)
+    }
+
+    fn related_cell_indexes_or_standard(&self, index: u16) -> Vec<u16> {
+        // The sorted list of cells related to this one. A grid that hasn't been through a builder
+        // has no related cells of its own, so fall back to the standard rules.
+        if self.related_cell_indexes.is_empty() {
+            return self.standard_related_cell_indexes(index);
+        }
+        let mut related_cell_indexes = self.index_to_related_cell_indexes(index);
+        related_cell_indexes.sort();
+        related_cell_indexes.dedup();
+        related_cell_indexes
+    }
+
+    pub(crate) fn standard_related_cell_indexes(&self, index: u16) -> Vec<u16> {
+        // The sorted list of cells in the same row, column, or block as this one.
+        let (row, column, block) = self.row_col_block(index);
+        (0..self.cell_count)
+            .filter(|other_index| {
+                let (other_row, other_column, other_block) = self.row_col_block(*other_index);
+                *other_index != index && (other_row == row || other_column == column || other_block == block)
+            })
+            .collect()
+    }
+
+}
+
+fn is_permutation(order: &[u8], count: u8) -> bool {
+    order.len() == count as usize
+        && order.iter().all(|line| *line < count)
+        && order.iter().unique().count() == order.len()
+}
+
+fn swap_lines(order: &mut [u8], line_1: u8, line_2: u8, band_size: u8) -> Result<(), SudokuError> {
+    // Swap two single lines (band_size = 1) or two whole bands of lines.
+    let line_count = order.len();
+    let (start_1, start_2) = (line_1 as usize * band_size as usize, line_2 as usize * band_size as usize);
+    if start_1 + band_size as usize > line_count || start_2 + band_size as usize > line_count {
+        return Err(SudokuError::InvalidTransform { reason: format!("Can't swap {} and {} in a grid with {} lines.", line_1, line_2, line_count) });
+    }
+    for offset in 0..band_size as usize {
+        order.swap(start_1 + offset, start_2 + offset);
+    }
+    Ok(())
+}
+
+pub(crate) fn band_preserving_orders(line_count: u8, band_size: u8) -> Vec<Vec<u8>> {
+    // Every ordering of the lines (rows or columns) that keeps each band together. For a standard
+    // 9x9 grid this is 3! orders of the bands times 3! orders within each of the three bands, or
+    // 1,296 orders.
+    let band_count = line_count / band_size;
+    let within_band_orders = (0..band_size).permutations(band_size as usize).collect::<Vec<_>>();
+    let mut orders = vec![];
+    for band_order in (0..band_count).permutations(band_count as usize) {
+        let mut partial_orders: Vec<Vec<u8>> = vec![vec![]];
+        for band in band_order {
+            let first_line = band * band_size;
+            partial_orders = partial_orders
+                .iter()
+                .flat_map(|partial_order| {
+                    within_band_orders.iter().map(move |within_band_order| {
+                        let mut order = partial_order.clone();
+                        order.extend(within_band_order.iter().map(|offset| first_line + offset));
+                        order
+                    })
+                })
+                .collect();
+        }
+        orders.extend(partial_orders);
+    }
+    orders
+}
+
+pub(crate) fn random_band_preserving_order(line_count: u8, band_size: u8) -> Vec<u8> {
+    // One of the orders from band_preserving_orders() picked at random, without listing them all.
+    let mut band_order = (0..line_count / band_size).collect::<Vec<_>>();
+    band_order.shuffle(&mut rng());
+    let mut order = Vec::with_capacity(line_count as usize);
+    for band in band_order {
+        let mut lines = (band * band_size..(band + 1) * band_size).collect::<Vec<_>>();
+        lines.shuffle(&mut rng());
+        order.extend(lines);
+    }
+    order
+}
+
+pub(crate) fn band_preserving_order_count(line_count: u8, band_size: u8) -> u64 {
+    let band_count = (line_count / band_size) as u64;
+    let factorial = |n: u64| (1..=n).fold(1u64, |product, x| product.saturating_mul(x));
+    factorial(band_count).saturating_mul(factorial(band_size as u64).saturating_pow(band_count as u32))
+}
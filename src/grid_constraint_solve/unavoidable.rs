@@ -0,0 +1,150 @@
+#![allow(dead_code)]
+
+// Unavoidable sets of a completed grid. An unavoidable set is a set of cells whose values can be
+// rearranged to give a different valid grid while every other cell stays the same, the smallest
+// example being a deadly rectangle of two values in two rows, two columns, and two blocks. If a
+// puzzle has no clue in some unavoidable set it can't have a unique solution, so a puzzle needs at
+// least as many clues as the grid has disjoint unavoidable sets.
+// This goes with grid::Grid and unique::UniquenessChecker.
+
+use crate::*;
+use super::grid::Grid;
+use super::builder::Builder;
+use super::unique::UniquenessChecker;
+use super::SudokuError;
+use std::time::Instant;
+
+// Stop enumerating the alternative grids for one set of values after this many. This only matters
+// when a lot of values are cleared at once, and means some of the larger sets may be missed.
+const MAX_SOLUTIONS_PER_VALUE_SET: usize = 100_000;
+
+pub fn main() {
+    try_unavoidable_sets();
+}
+
+fn try_unavoidable_sets() {
+    let max_set_size = 12;
+    let max_value_count = 3;
+
+    for grid_size in [6, 8, 9].iter() {
+        let grid = Builder::with_size(*grid_size).build().unwrap();
+        let start_time = Instant::now();
+        match grid.unavoidable_sets(max_set_size, max_value_count) {
+            Ok(unavoidable_sets) => {
+                let mut size_counts = vec![0; max_set_size + 1];
+                for unavoidable_set in unavoidable_sets.iter() {
+                    size_counts[unavoidable_set.indexes.len()] += 1;
+                }
+                println!("try_unavoidable_sets(): grid_size = {}, set count = {}, counts by size = {:?}, clue lower bound = {}, time = {:?}",
+                         grid_size, unavoidable_sets.len(), size_counts, clue_lower_bound(&unavoidable_sets), Instant::now() - start_time);
+            },
+            Err(error) => println!("{}", error),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnavoidableSet {
+    // Sorted cell indexes.
+    pub indexes: Vec<u16>,
+    // The values the cells hold in the alternative grid, in the same order as indexes.
+    pub alternative_values: Vec<u8>,
+}
+
+impl Grid {
+
+    pub fn unavoidable_sets(&self, max_set_size: usize, max_value_count: u8) -> Result<Vec<UnavoidableSet>, SudokuError> {
+        // Returns the minimal unavoidable sets with at most max_set_size cells that use at most
+        // max_value_count different values, smallest first. For each combination of values we
+        // clear every cell holding one of them and list the other ways to fill those cells back in.
+        // Each alternative grid differs from this one on an unavoidable set, and a set is only kept
+        // if it doesn't contain a smaller set we found.
+        if self.unsolved_cell_count > 0 {
+            return Err(SudokuError::IncompleteGrid { unsolved_cell_count: self.unsolved_cell_count });
+        }
+        let mut checker = UniquenessChecker::new(self)?;
+        let mut found_sets = vec![];
+        let max_value_count = max_value_count.min(self.max_value);
+        for value_count in 2..=max_value_count {
+            for value_set in value_combinations(self.max_value, value_count) {
+                let cleared_values = self.values
+                    .iter()
+                    .map(|value| if value_set.contains(value) { NO_VALUE } else { *value })
+                    .collect::<Vec<_>>();
+                for alternative in checker.solutions(&cleared_values, MAX_SOLUTIONS_PER_VALUE_SET)? {
+                    let indexes = (0..self.cell_count)
+                        .filter(|index| alternative[*index as usize] != self.values[*index as usize])
+                        .collect::<Vec<_>>();
+                    if !indexes.is_empty() && indexes.len() <= max_set_size {
+                        let alternative_values = indexes.iter().map(|index| alternative[*index as usize]).collect();
+                        found_sets.push(UnavoidableSet { indexes, alternative_values });
+                    }
+                }
+            }
+        }
+
+        // Sort smallest first so that when we get to a set, any smaller set it might contain has
+        // already been kept.
+        found_sets.sort_by(|a, b| a.indexes.len().cmp(&b.indexes.len()).then_with(|| a.indexes.cmp(&b.indexes)));
+        let mut unavoidable_sets: Vec<UnavoidableSet> = vec![];
+        for found_set in found_sets {
+            let contains_smaller_set = unavoidable_sets
+                .iter()
+                .any(|kept_set| kept_set.indexes.iter().all(|index| found_set.indexes.binary_search(index).is_ok()));
+            if !contains_smaller_set {
+                unavoidable_sets.push(found_set);
+            }
+        }
+        Ok(unavoidable_sets)
+    }
+
+    pub fn misses_unavoidable_set<'a>(&self, unavoidable_sets: &'a [UnavoidableSet]) -> Option<&'a UnavoidableSet> {
+        // Returns an unavoidable set with no clue in this puzzle, which proves the puzzle has more
+        // than one solution. Returns None if every set has a clue, which proves nothing.
+        unavoidable_sets
+            .iter()
+            .find(|unavoidable_set| unavoidable_set.indexes.iter().all(|index| self.values[*index as usize] == NO_VALUE))
+    }
+
+}
+
+pub fn clue_lower_bound(unavoidable_sets: &[UnavoidableSet]) -> usize {
+    // Every unavoidable set needs its own clue if the sets don't overlap, so the number of sets we
+    // can pick with no cells in common is a lower bound on the number of clues. The sets are picked
+    // greedily, smallest first, so the bound isn't necessarily the best one these sets allow.
+    let mut used = vec![];
+    let mut disjoint_count = 0;
+    let mut sets = unavoidable_sets.iter().collect::<Vec<_>>();
+    sets.sort_by_key(|unavoidable_set| unavoidable_set.indexes.len());
+    for unavoidable_set in sets {
+        if unavoidable_set.indexes.iter().all(|index| !used.contains(index)) {
+            used.extend(unavoidable_set.indexes.iter().copied());
+            disjoint_count += 1;
+        }
+    }
+    disjoint_count
+}
+
+fn value_combinations(max_value: u8, value_count: u8) -> Vec<Vec<u8>> {
+    // Every combination of value_count values from 1 to max_value, in lexicographic order.
+    let mut combinations = vec![];
+    let mut combination = (1..=value_count).collect::<Vec<_>>();
+    loop {
+        combinations.push(combination.clone());
+        // Find the rightmost value that can still be increased.
+        let mut position = value_count as usize;
+        loop {
+            if position == 0 {
+                return combinations;
+            }
+            position -= 1;
+            if combination[position] < max_value - (value_count - 1 - position as u8) {
+                break;
+            }
+        }
+        combination[position] += 1;
+        for next_position in position + 1..value_count as usize {
+            combination[next_position] = combination[next_position - 1] + 1;
+        }
+    }
+}
@@ -0,0 +1,339 @@
+#![allow(dead_code)]
+
+// A fast check for whether a puzzle has zero, one, or many solutions. Unlike
+// Solver::unique_solution_type() this stops the moment it finds a second solution and keeps its
+// buffers between calls, so it's meant to be created once and then called thousands of times while
+// reducing a puzzle. The candidates for each cell are a bitmask, the search always branches on the
+// cell with the fewest candidates, and each branch is undone from a trail rather than by cloning
+// the grid.
+// This goes with grid::Grid and solver::Solver.
+
+use crate::*;
+use super::grid::Grid;
+use super::builder::Builder;
+use super::solver::UniqueSolutionType;
+use super::{CancellationToken, SudokuError};
+use std::time::{Duration, Instant};
+
+// Only look at the clock this often since it's much slower than the rest of a node.
+const CLOCK_CHECK_INTERVAL: usize = 1_024;
+
+pub fn main() {
+    try_uniqueness_checker();
+}
+
+fn try_uniqueness_checker() {
+    let grid_size = 9;
+    let remove_cell_count = 55;
+    let repeat_count = 1_000;
+
+    let grid = Builder::with_size(grid_size).build().unwrap();
+    let mut checker = UniquenessChecker::new(&grid).unwrap();
+    let mut counts = [0; 3];
+    let start_time = Instant::now();
+    for _ in 0..repeat_count {
+        let mut puzzle = grid.clone();
+        puzzle.remove_cells(remove_cell_count);
+        match checker.check_grid(&puzzle).unwrap() {
+            Uniqueness::Zero => counts[0] += 1,
+            Uniqueness::One { .. } => counts[1] += 1,
+            Uniqueness::Many { .. } => counts[2] += 1,
+        }
+    }
+    println!("try_uniqueness_checker(): zero = {}, one = {}, many = {}, time = {:?}, nodes = {}",
+             counts[0], counts[1], counts[2], Instant::now() - start_time, checker.node_count);
+}
+
+#[derive(Clone, Debug)]
+pub enum Uniqueness {
+    Zero,
+    One { solution: Vec<u8> },
+    // Two different solutions, for showing where the puzzle is ambiguous.
+    Many { solution_1: Vec<u8>, solution_2: Vec<u8> },
+}
+
+impl Uniqueness {
+    pub fn solution_type(&self) -> UniqueSolutionType {
+        match self {
+            Uniqueness::Zero => UniqueSolutionType::Zero,
+            Uniqueness::One { .. } => UniqueSolutionType::One,
+            Uniqueness::Many { .. } => UniqueSolutionType::Many,
+        }
+    }
+
+    pub fn differing_indexes(&self) -> Vec<u16> {
+        // The cells where the two solutions disagree, or nothing if there aren't two solutions.
+        match self {
+            Uniqueness::Many { solution_1, solution_2 } => solution_1
+                .iter()
+                .zip(solution_2.iter())
+                .enumerate()
+                .filter(|(_, (value_1, value_2))| value_1 != value_2)
+                .map(|(index, _)| index as u16)
+                .collect(),
+            _ => vec![],
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct UniquenessChecker {
+    pub time_limit: Option<Duration>,
+    pub cancellation_token: Option<CancellationToken>,
+    // Totals over every call to check(), for profiling.
+    pub check_count: usize,
+    pub node_count: usize,
+    cell_count: usize,
+    max_value: u8,
+    all_candidates: u64,
+    // The related cells for cell i are related_cell_indexes[related_cell_starts[i]..related_cell_starts[i + 1]].
+    related_cell_starts: Vec<usize>,
+    related_cell_indexes: Vec<u16>,
+    values: Vec<u8>,
+    candidates: Vec<u64>,
+    // Each entry is a cell's index, candidates, and value from before a change, so a branch can be
+    // undone by popping entries back to where the branch started.
+    trail: Vec<(u16, u64, u8)>,
+    queue: Vec<(u16, u8)>,
+    solution_count: usize,
+    // The search stops once it has found this many solutions.
+    solution_limit: usize,
+    // Every solution found, but only when called through solutions().
+    collected_solutions: Option<Vec<Vec<u8>>>,
+    solution_1: Vec<u8>,
+    solution_2: Vec<u8>,
+    check_node_count: usize,
+    start_time: Instant,
+}
+
+impl UniquenessChecker {
+
+    pub fn new(grid: &Grid) -> Result<Self, SudokuError> {
+        // The checker works for any grid with the same size and rules as this one.
+        if grid.max_value as u32 > u64::BITS {
+            return Err(SudokuError::InvalidDimensions {
+                width: grid.width,
+                height: grid.height,
+                block_width: grid.block_width,
+                block_height: grid.block_height,
+                reason: format!("The uniqueness checker handles at most {} values per cell.", u64::BITS),
+            });
+        }
+        let cell_count = grid.cell_count as usize;
+        let mut related_cell_starts = Vec::with_capacity(cell_count + 1);
+        let mut related_cell_indexes = vec![];
+        for index in 0..grid.cell_count {
+            related_cell_starts.push(related_cell_indexes.len());
+            let mut one_cell_related_indexes = if grid.related_cell_indexes.is_empty() {
+                grid.standard_related_cell_indexes(index)
+            } else {
+                grid.index_to_related_cell_indexes(index)
+            };
+            one_cell_related_indexes.sort();
+            one_cell_related_indexes.dedup();
+            related_cell_indexes.extend(one_cell_related_indexes);
+        }
+        related_cell_starts.push(related_cell_indexes.len());
+        let all_candidates = if grid.max_value as u32 == u64::BITS { u64::MAX } else { (1 << grid.max_value) - 1 };
+        Ok(Self {
+            time_limit: None,
+            cancellation_token: None,
+            check_count: 0,
+            node_count: 0,
+            cell_count,
+            max_value: grid.max_value,
+            all_candidates,
+            related_cell_starts,
+            related_cell_indexes,
+            values: vec![NO_VALUE; cell_count],
+            candidates: vec![all_candidates; cell_count],
+            trail: Vec::with_capacity(cell_count * grid.max_value as usize),
+            queue: Vec::with_capacity(cell_count),
+            solution_count: 0,
+            solution_limit: 2,
+            collected_solutions: None,
+            solution_1: vec![NO_VALUE; cell_count],
+            solution_2: vec![NO_VALUE; cell_count],
+            check_node_count: 0,
+            start_time: Instant::now(),
+        })
+    }
+
+    pub fn limit_milliseconds(mut self, msec: u64) -> Self {
+        self.time_limit = Some(Duration::from_millis(msec));
+        self
+    }
+
+    pub fn check_grid(&mut self, grid: &Grid) -> Result<Uniqueness, SudokuError> {
+        self.check(&grid.values)
+    }
+
+    pub fn check(&mut self, values: &[u8]) -> Result<Uniqueness, SudokuError> {
+        // The time limit applies to each call separately.
+        self.solution_limit = 2;
+        self.collected_solutions = None;
+        self.run(values)?;
+        Ok(match self.solution_count {
+            0 => Uniqueness::Zero,
+            1 => Uniqueness::One { solution: self.solution_1.clone() },
+            _ => Uniqueness::Many { solution_1: self.solution_1.clone(), solution_2: self.solution_2.clone() },
+        })
+    }
+
+    pub fn solutions(&mut self, values: &[u8], solution_limit: usize) -> Result<Vec<Vec<u8>>, SudokuError> {
+        // Returns every solution, or the first solution_limit of them if there are more.
+        self.solution_limit = solution_limit;
+        self.collected_solutions = Some(vec![]);
+        let result = self.run(values);
+        let solutions = self.collected_solutions.take().unwrap();
+        result.map(|_| solutions)
+    }
+
+    fn run(&mut self, values: &[u8]) -> Result<(), SudokuError> {
+        debug_assert_eq!(self.cell_count, values.len());
+        self.check_count += 1;
+        self.check_node_count = 0;
+        self.start_time = Instant::now();
+        self.solution_count = 0;
+        for index in 0..self.cell_count {
+            self.values[index] = NO_VALUE;
+            self.candidates[index] = self.all_candidates;
+        }
+        self.trail.clear();
+        for (index, value) in values.iter().enumerate() {
+            if *value != NO_VALUE && !self.assign(index as u16, *value) {
+                // The givens contradict each other so there are no solutions.
+                return Ok(());
+            }
+        }
+        // The givens never need to be undone.
+        self.trail.clear();
+
+        let result = self.search();
+        self.node_count += self.check_node_count;
+        result
+    }
+
+    fn search(&mut self) -> Result<(), SudokuError> {
+        self.check_node_count += 1;
+        if self.check_node_count.is_multiple_of(CLOCK_CHECK_INTERVAL) {
+            self.check_limits()?;
+        }
+
+        // Branch on the empty cell with the fewest candidates.
+        let mut try_index = None;
+        let mut min_candidate_count = u32::MAX;
+        for index in 0..self.cell_count {
+            if self.values[index] == NO_VALUE {
+                let candidate_count = self.candidates[index].count_ones();
+                if candidate_count < min_candidate_count {
+                    min_candidate_count = candidate_count;
+                    try_index = Some(index);
+                    if candidate_count <= 1 {
+                        break;
+                    }
+                }
+            }
+        }
+        let try_index = match try_index {
+            Some(index) => index,
+            None => {
+                // Every cell has a value.
+                self.solution_count += 1;
+                if self.solution_count == 1 {
+                    self.solution_1.copy_from_slice(&self.values);
+                } else if self.solution_count == 2 {
+                    self.solution_2.copy_from_slice(&self.values);
+                }
+                if let Some(collected_solutions) = &mut self.collected_solutions {
+                    collected_solutions.push(self.values.clone());
+                }
+                return Ok(());
+            }
+        };
+
+        let mut remaining_candidates = self.candidates[try_index];
+        while remaining_candidates != 0 {
+            let value = remaining_candidates.trailing_zeros() as u8 + 1;
+            remaining_candidates &= remaining_candidates - 1;
+            let trail_len = self.trail.len();
+            if self.assign(try_index as u16, value) {
+                self.search()?;
+            }
+            self.undo(trail_len);
+            if self.solution_count >= self.solution_limit {
+                // When checking uniqueness there's no point looking for a third solution.
+                return Ok(());
+            }
+        }
+        Ok(())
+    }
+
+    fn assign(&mut self, index: u16, value: u8) -> bool {
+        // Set the value, take it out of the candidates of the related cells, and do the same for
+        // any related cell left with a single candidate. Returns false if some cell ends up with no
+        // candidates.
+        self.queue.clear();
+        self.queue.push((index, value));
+        while let Some((index, value)) = self.queue.pop() {
+            let bit = 1 << (value - 1);
+            let cell = index as usize;
+            if self.values[cell] != NO_VALUE {
+                if self.values[cell] == value {
+                    continue;
+                }
+                return false;
+            }
+            if self.candidates[cell] & bit == 0 {
+                return false;
+            }
+            self.trail.push((index, self.candidates[cell], self.values[cell]));
+            self.values[cell] = value;
+            self.candidates[cell] = bit;
+            for lookup_index in self.related_cell_starts[cell]..self.related_cell_starts[cell + 1] {
+                let related_index = self.related_cell_indexes[lookup_index];
+                let related_cell = related_index as usize;
+                if self.values[related_cell] == value {
+                    return false;
+                }
+                if self.values[related_cell] == NO_VALUE && self.candidates[related_cell] & bit != 0 {
+                    self.trail.push((related_index, self.candidates[related_cell], NO_VALUE));
+                    self.candidates[related_cell] &= !bit;
+                    match self.candidates[related_cell].count_ones() {
+                        0 => return false,
+                        1 => {
+                            let only_value = self.candidates[related_cell].trailing_zeros() as u8 + 1;
+                            self.queue.push((related_index, only_value));
+                        },
+                        _ => {},
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    fn undo(&mut self, trail_len: usize) {
+        while self.trail.len() > trail_len {
+            let (index, candidates, value) = self.trail.pop().unwrap();
+            self.candidates[index as usize] = candidates;
+            self.values[index as usize] = value;
+        }
+    }
+
+    fn check_limits(&self) -> Result<(), SudokuError> {
+        if let Some(cancellation_token) = &self.cancellation_token {
+            if cancellation_token.is_cancelled() {
+                return Err(SudokuError::Cancelled { elapsed: self.start_time.elapsed(), node_count: self.check_node_count });
+            }
+        }
+        if let Some(time_limit) = self.time_limit {
+            let elapsed = self.start_time.elapsed();
+            if elapsed >= time_limit {
+                return Err(SudokuError::TimeLimitExceeded { elapsed, node_count: self.check_node_count });
+            }
+        }
+        Ok(())
+    }
+
+}
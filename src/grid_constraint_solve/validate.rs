@@ -0,0 +1,148 @@
+#![allow(dead_code)]
+
+// Checking a grid without panicking. Grid::invariant() asserts, and only runs at the checkpoints
+// turned on in the current Diagnostics, which is right for catching bugs in the builder and solver
+// but not for a player's entries or an imported puzzle. validate() instead returns every problem
+// it finds so the caller can report them.
+// This goes with grid::Grid.
+
+use crate::*;
+use super::grid::Grid;
+use super::builder::{Builder, Cell, EXTRA_RULES, RULE_KNIGHT};
+use std::fmt::{self, Display, Formatter};
+
+pub fn main() {
+    try_validate();
+}
+
+fn try_validate() {
+    let grid = Builder::with_size(9).rule(RULE_KNIGHT).build().unwrap();
+    let mut puzzle = grid.clone();
+    puzzle.remove_cells(50);
+    // Repeat one value in the top row, and another a knight's move away in the next block.
+    let mut values = puzzle.values.clone();
+    values[0] = grid.values[0];
+    values[1] = grid.values[0];
+    values[2] = grid.values[2];
+    values[13] = grid.values[2];
+    puzzle.replace_values(&values);
+    for violation in puzzle.validate_against_solution(&grid.values) {
+        println!("try_validate(): {}", violation);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RelatedBy {
+    Row,
+    Column,
+    Block,
+    // One of builder::EXTRA_RULES, by its name there.
+    Rule(&'static str),
+    // Related by a rule other than the ones above.
+    Other,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Violation {
+    // The values list is the wrong length, so nothing else could be checked.
+    WrongValueCount { expected: usize, found: usize },
+    ValueOutOfRange { index: u16, value: u8 },
+    // Two related cells with the same value. The grid doesn't keep the rules it was built with,
+    // only which cells are related, so related_by lists every known rule that would relate the two
+    // cells. Row, column, and block are listed if any of them apply, and builder::EXTRA_RULES only
+    // if none do.
+    DuplicateValue { index: u16, related_index: u16, value: u8, related_by: Vec<RelatedBy> },
+    // An empty cell with no candidates left, so the grid can't be completed.
+    NoCandidates { index: u16 },
+    // A filled cell whose value isn't the one in the solution.
+    DiffersFromSolution { index: u16, value: u8, solution_value: u8 },
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Violation::WrongValueCount { expected, found } => write!(f, "The grid should have {} values but has {}.", expected, found),
+            Violation::ValueOutOfRange { index, value } => write!(f, "Cell index {} has value {}, which is out of range.", index, value),
+            Violation::DuplicateValue { index, related_index, value, related_by } => write!(f, "Cell indexes {} and {} both have value {} and are related by {:?}.", index, related_index, value, related_by),
+            Violation::NoCandidates { index } => write!(f, "Cell index {} is empty and has no candidates left.", index),
+            Violation::DiffersFromSolution { index, value, solution_value } => write!(f, "Cell index {} has value {} but the solution has {}.", index, value, solution_value),
+        }
+    }
+}
+
+impl Grid {
+
+    pub fn validate(&self) -> Vec<Violation> {
+        // Checks the values against the rules and the candidates of the empty cells. A grid with
+        // no violations may still have no solution; this only finds problems that are visible
+        // without searching.
+        let mut violations = vec![];
+        if self.values.len() != self.cell_count as usize {
+            violations.push(Violation::WrongValueCount { expected: self.cell_count as usize, found: self.values.len() });
+            return violations;
+        }
+        for index in 0..self.cell_count {
+            let value = self.values[index as usize];
+            if value > self.max_value {
+                violations.push(Violation::ValueOutOfRange { index, value });
+            } else if value == NO_VALUE {
+                if self.remaining_value_counts.get(index as usize) == Some(&0) {
+                    violations.push(Violation::NoCandidates { index });
+                }
+            } else if !self.related_cell_indexes.is_empty() {
+                // Only look forward so that each pair is reported once.
+                for related_index in self.index_to_related_cell_indexes(index).into_iter().filter(|related_index| *related_index > index) {
+                    if self.values[related_index as usize] == value {
+                        violations.push(Violation::DuplicateValue { index, related_index, value, related_by: self.related_by(index, related_index) });
+                    }
+                }
+            }
+        }
+        violations
+    }
+
+    pub fn validate_against_solution(&self, solution: &[u8]) -> Vec<Violation> {
+        // Everything from validate() plus each filled cell that doesn't match the solution, such
+        // as GameSession::solution.values or the solution from UniquenessChecker.
+        let mut violations = self.validate();
+        if self.values.len() != solution.len() {
+            return violations;
+        }
+        for (index, (value, solution_value)) in self.values.iter().zip(solution.iter()).enumerate() {
+            if *value != NO_VALUE && *value <= self.max_value && value != solution_value {
+                violations.push(Violation::DiffersFromSolution { index: index as u16, value: *value, solution_value: *solution_value });
+            }
+        }
+        violations
+    }
+
+    fn related_by(&self, index: u16, related_index: u16) -> Vec<RelatedBy> {
+        let (row, column, block) = self.row_col_block(index);
+        let (related_row, related_column, related_block) = self.row_col_block(related_index);
+        let mut related_by = vec![];
+        if row == related_row {
+            related_by.push(RelatedBy::Row);
+        }
+        if column == related_column {
+            related_by.push(RelatedBy::Column);
+        }
+        if block == related_block {
+            related_by.push(RelatedBy::Block);
+        }
+        if !related_by.is_empty() {
+            return related_by;
+        }
+        // Cells are numbered from 1 in the rules, as in Builder::set_up_related_cells().
+        let cell = Cell::new(index + 1, row as i8 + 1, column as i8 + 1, block as i8 + 1);
+        let related_cell = Cell::new(related_index + 1, related_row as i8 + 1, related_column as i8 + 1, related_block as i8 + 1);
+        related_by.extend(EXTRA_RULES
+            .iter()
+            .filter(|(_, rule)| rule(self, &cell, &related_cell))
+            .map(|(name, _)| RelatedBy::Rule(name)));
+        if related_by.is_empty() {
+            related_by.push(RelatedBy::Other);
+        }
+        related_by
+    }
+
+}
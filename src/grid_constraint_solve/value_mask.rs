@@ -0,0 +1,315 @@
+#![allow(dead_code)]
+
+// The remaining values for each cell of a Grid, kept as one bitmask per cell with bit v - 1 set if
+// value v is still possible. This replaces the single BitVec holding max_value bits per cell.
+// Finding whether a value is possible is the same either way, but counting, clearing, and
+// recalculating a cell become a few word operations instead of a loop over the values, and the
+// first remaining value is trailing_zeros().
+// The integer type is the smallest that holds max_value bits, so a 9x9 grid uses a u16 per cell.
+// Grids with more than 128 values, which only happen with custom symbols, use WideMask.
+// This goes with grid::Grid.
+
+use crate::*;
+use super::grid::Grid;
+
+pub fn main() {
+    try_value_mask_kind();
+}
+
+fn try_value_mask_kind() {
+    // Empty grids are enough to see which kind of mask each size gets.
+    for block_size in [3u8, 4, 6, 8, 12].iter() {
+        let grid = Grid::new(block_size * block_size, block_size * block_size, *block_size, *block_size);
+        println!("try_value_mask_kind(): max_value = {}, kind = {}", grid.max_value, grid.remaining_values.kind_name());
+    }
+}
+
+pub trait ValueMask: Copy + Eq {
+    const VALUE_CAPACITY: usize;
+
+    fn empty() -> Self;
+
+    fn with_values_up_to(max_value: u8) -> Self;
+
+    fn has(&self, value: u8) -> bool;
+
+    fn insert(&mut self, value: u8);
+
+    fn remove(&mut self, value: u8);
+
+    fn and_not(self, other: Self) -> Self;
+
+    fn count(&self) -> u32;
+
+    fn lowest_value(&self) -> Option<u8>;
+
+    fn is_empty(&self) -> bool {
+        *self == Self::empty()
+    }
+}
+
+macro_rules! impl_value_mask {
+    ($t:ty) => {
+        impl ValueMask for $t {
+            const VALUE_CAPACITY: usize = <$t>::BITS as usize;
+
+            #[inline]
+            fn empty() -> Self {
+                0
+            }
+
+            #[inline]
+            fn with_values_up_to(max_value: u8) -> Self {
+                debug_assert!(max_value as usize <= Self::VALUE_CAPACITY);
+                if max_value as usize == Self::VALUE_CAPACITY {
+                    <$t>::MAX
+                } else {
+                    (1 << max_value) - 1
+                }
+            }
+
+            #[inline]
+            fn has(&self, value: u8) -> bool {
+                *self & (1 << (value - 1)) != 0
+            }
+
+            #[inline]
+            fn insert(&mut self, value: u8) {
+                *self |= 1 << (value - 1);
+            }
+
+            #[inline]
+            fn remove(&mut self, value: u8) {
+                *self &= !(1 << (value - 1));
+            }
+
+            #[inline]
+            fn and_not(self, other: Self) -> Self {
+                self & !other
+            }
+
+            #[inline]
+            fn count(&self) -> u32 {
+                self.count_ones()
+            }
+
+            #[inline]
+            fn lowest_value(&self) -> Option<u8> {
+                if *self == 0 {
+                    None
+                } else {
+                    Some(self.trailing_zeros() as u8 + 1)
+                }
+            }
+        }
+    };
+}
+
+impl_value_mask!(u16);
+impl_value_mask!(u32);
+impl_value_mask!(u64);
+impl_value_mask!(u128);
+
+// Enough bits for any value that fits in a u8.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WideMask([u64; 4]);
+
+impl ValueMask for WideMask {
+    const VALUE_CAPACITY: usize = 256;
+
+    #[inline]
+    fn empty() -> Self {
+        WideMask([0; 4])
+    }
+
+    fn with_values_up_to(max_value: u8) -> Self {
+        let mut mask = Self::empty();
+        for value in 1..=max_value {
+            mask.insert(value);
+        }
+        mask
+    }
+
+    #[inline]
+    fn has(&self, value: u8) -> bool {
+        let bit = value as usize - 1;
+        self.0[bit / 64] & (1 << (bit % 64)) != 0
+    }
+
+    #[inline]
+    fn insert(&mut self, value: u8) {
+        let bit = value as usize - 1;
+        self.0[bit / 64] |= 1 << (bit % 64);
+    }
+
+    #[inline]
+    fn remove(&mut self, value: u8) {
+        let bit = value as usize - 1;
+        self.0[bit / 64] &= !(1 << (bit % 64));
+    }
+
+    #[inline]
+    fn and_not(self, other: Self) -> Self {
+        let mut mask = self;
+        for (word, other_word) in mask.0.iter_mut().zip(other.0.iter()) {
+            *word &= !other_word;
+        }
+        mask
+    }
+
+    #[inline]
+    fn count(&self) -> u32 {
+        self.0.iter().map(|word| word.count_ones()).sum()
+    }
+
+    fn lowest_value(&self) -> Option<u8> {
+        self.0.iter()
+            .enumerate()
+            .find(|(_, word)| **word != 0)
+            .map(|(word_index, word)| (word_index * 64) as u8 + word.trailing_zeros() as u8 + 1)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum RemainingValues {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+    U64(Vec<u64>),
+    U128(Vec<u128>),
+    Wide(Vec<WideMask>),
+}
+
+// Runs the same code against whichever kind of mask the grid uses. Inside $body, $masks is the
+// Vec of masks and $t is its element type.
+macro_rules! with_masks {
+    ($self:expr, $masks:ident, $t:ident => $body:expr) => {
+        match $self {
+            RemainingValues::U16($masks) => { type $t = u16; $body }
+            RemainingValues::U32($masks) => { type $t = u32; $body }
+            RemainingValues::U64($masks) => { type $t = u64; $body }
+            RemainingValues::U128($masks) => { type $t = u128; $body }
+            RemainingValues::Wide($masks) => { type $t = WideMask; $body }
+        }
+    };
+}
+
+impl RemainingValues {
+
+    pub fn new(cell_count: u16, max_value: u8) -> Self {
+        // Every value starts out possible in every cell.
+        let cell_count = cell_count as usize;
+        let max_value_usize = max_value as usize;
+        if max_value_usize <= u16::VALUE_CAPACITY {
+            RemainingValues::U16(vec![u16::with_values_up_to(max_value); cell_count])
+        } else if max_value_usize <= u32::VALUE_CAPACITY {
+            RemainingValues::U32(vec![u32::with_values_up_to(max_value); cell_count])
+        } else if max_value_usize <= u64::VALUE_CAPACITY {
+            RemainingValues::U64(vec![u64::with_values_up_to(max_value); cell_count])
+        } else if max_value_usize <= u128::VALUE_CAPACITY {
+            RemainingValues::U128(vec![u128::with_values_up_to(max_value); cell_count])
+        } else {
+            RemainingValues::Wide(vec![WideMask::with_values_up_to(max_value); cell_count])
+        }
+    }
+
+    pub fn kind_name(&self) -> &'static str {
+        match self {
+            RemainingValues::U16(_) => "u16",
+            RemainingValues::U32(_) => "u32",
+            RemainingValues::U64(_) => "u64",
+            RemainingValues::U128(_) => "u128",
+            RemainingValues::Wide(_) => "wide",
+        }
+    }
+
+    #[inline]
+    pub fn cell_count(&self) -> usize {
+        with_masks!(self, masks, _T => masks.len())
+    }
+
+    #[inline]
+    pub fn has(&self, index: u16, value: u8) -> bool {
+        with_masks!(self, masks, _T => masks[index as usize].has(value))
+    }
+
+    #[inline]
+    pub fn set(&mut self, index: u16, value: u8) -> bool {
+        // Returns true if the value was not already possible and thus was added.
+        with_masks!(self, masks, _T => {
+            let mask = &mut masks[index as usize];
+            if mask.has(value) {
+                false
+            } else {
+                mask.insert(value);
+                true
+            }
+        })
+    }
+
+    #[inline]
+    pub fn clear(&mut self, index: u16, value: u8) -> bool {
+        // Returns true if the value was possible and thus was removed.
+        with_masks!(self, masks, _T => {
+            let mask = &mut masks[index as usize];
+            if mask.has(value) {
+                mask.remove(value);
+                true
+            } else {
+                false
+            }
+        })
+    }
+
+    #[inline]
+    pub fn clear_cell(&mut self, index: u16) -> u32 {
+        // Returns the number of values that were removed.
+        with_masks!(self, masks, T => {
+            let mask = &mut masks[index as usize];
+            let count = mask.count();
+            *mask = T::empty();
+            count
+        })
+    }
+
+    #[inline]
+    pub fn count(&self, index: u16) -> u32 {
+        with_masks!(self, masks, _T => masks[index as usize].count())
+    }
+
+    #[inline]
+    pub fn lowest_value(&self, index: u16) -> Option<u8> {
+        with_masks!(self, masks, _T => masks[index as usize].lowest_value())
+    }
+
+    pub fn values(&self, index: u16) -> Vec<u8> {
+        with_masks!(self, masks, _T => {
+            let mut mask = masks[index as usize];
+            let mut values = vec![];
+            while let Some(value) = mask.lowest_value() {
+                values.push(value);
+                mask.remove(value);
+            }
+            values
+        })
+    }
+
+    #[inline]
+    pub fn recalc_cell<I>(&mut self, index: u16, max_value: u8, related_values: I) -> u32
+        where I: Iterator<Item = u8>
+    {
+        // Makes every value from 1 through max_value possible except those in related_values, and
+        // returns the new number of remaining values. A related value of NO_VALUE is skipped.
+        with_masks!(self, masks, T => {
+            let mut taken = T::empty();
+            for value in related_values {
+                if value != NO_VALUE {
+                    taken.insert(value);
+                }
+            }
+            let mask = T::with_values_up_to(max_value).and_not(taken);
+            masks[index as usize] = mask;
+            mask.count()
+        })
+    }
+
+}
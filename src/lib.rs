@@ -4,6 +4,8 @@ pub mod builder_obj_log;
 pub mod builder_vec;
 pub mod builder_vec_large;
 pub mod builder_vec_log;
+pub mod differential;
+pub mod engine;
 pub mod grid_constraint;
 pub mod grid_constraint_solve;
 
@@ -12,6 +14,10 @@ pub mod grid_constraint_solve;
 
 use std::time::{Instant, Duration};
 use std::collections::{HashMap, HashSet};
+use std::cell::{Cell, RefCell};
+use std::sync::Once;
+use rand::{thread_rng, RngCore, SeedableRng};
+use rand::rngs::StdRng;
 
 // pub mod text_grid;
 
@@ -94,6 +100,124 @@ impl BuildController {
     }
 }
 
+// The random number generator used by every builder and solver. Normally this is just
+// thread_rng(), but after seed_rng(Some(seed)) it's a StdRng started from that seed, so the same
+// seed and the same sequence of calls give the same grids. The seed applies only to the current
+// thread.
+thread_local! {
+    static SEEDED_RNG: RefCell<Option<StdRng>> = const { RefCell::new(None) };
+}
+
+pub fn seed_rng(seed: Option<u64>) {
+    SEEDED_RNG.with(|seeded_rng| *seeded_rng.borrow_mut() = seed.map(StdRng::seed_from_u64));
+}
+
+// Seeds the random number generator until the guard is dropped, then puts back the generator
+// that was there before, even if the code in between panics.
+#[must_use]
+pub struct SeededRng {
+    previous: Option<StdRng>,
+}
+
+pub fn seeded_rng(seed: Option<u64>) -> SeededRng {
+    let previous = SEEDED_RNG.with(|seeded_rng| seeded_rng.replace(seed.map(StdRng::seed_from_u64)));
+    SeededRng { previous }
+}
+
+impl Drop for SeededRng {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        SEEDED_RNG.with(|seeded_rng| *seeded_rng.borrow_mut() = previous);
+    }
+}
+
+pub fn rng() -> SudokuRng {
+    SudokuRng
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SudokuRng;
+
+impl SudokuRng {
+    fn with_rng<T, F>(f: F) -> T
+        where F: FnOnce(&mut dyn RngCore) -> T
+    {
+        SEEDED_RNG.with(|seeded_rng| match seeded_rng.borrow_mut().as_mut() {
+            Some(seeded_rng) => f(seeded_rng),
+            None => f(&mut thread_rng()),
+        })
+    }
+}
+
+impl RngCore for SudokuRng {
+    fn next_u32(&mut self) -> u32 {
+        Self::with_rng(|rng| rng.next_u32())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        Self::with_rng(|rng| rng.next_u64())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        Self::with_rng(|rng| rng.fill_bytes(dest))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        Self::with_rng(|rng| rng.try_fill_bytes(dest))
+    }
+}
+
+// Runs f and returns the panic message if it panics, for harnesses that report panics rather than
+// stopping at the first one.
+pub(crate) fn catch_panic<T, F>(f: F) -> Result<T, String>
+    where F: FnOnce() -> T
+{
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).map_err(|payload| {
+        if let Some(message) = payload.downcast_ref::<&str>() {
+            message.to_string()
+        } else if let Some(message) = payload.downcast_ref::<String>() {
+            message.clone()
+        } else {
+            "unknown panic".to_string()
+        }
+    })
+}
+
+// While a QuietPanics guard is alive, panics on the current thread aren't printed, for harnesses
+// that catch panics with catch_panic() and report them. Swapping out the process-wide hook
+// instead would also silence other threads, and it couldn't be put back if the harness itself
+// panicked, since the hook can't be changed while unwinding. So a hook is installed once that
+// passes each panic on to the previous hook unless the panicking thread holds a guard, and the
+// guard only has to count itself in and out.
+thread_local! {
+    static QUIET_PANIC_COUNT: Cell<usize> = const { Cell::new(0) };
+}
+
+static INSTALL_QUIET_PANIC_HOOK: Once = Once::new();
+
+pub(crate) struct QuietPanics {
+    _private: (),
+}
+
+pub(crate) fn quiet_panics() -> QuietPanics {
+    INSTALL_QUIET_PANIC_HOOK.call_once(|| {
+        let previous_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if QUIET_PANIC_COUNT.with(|count| count.get()) == 0 {
+                previous_hook(info);
+            }
+        }));
+    });
+    QUIET_PANIC_COUNT.with(|count| count.set(count.get() + 1));
+    QuietPanics { _private: () }
+}
+
+impl Drop for QuietPanics {
+    fn drop(&mut self) {
+        QUIET_PANIC_COUNT.with(|count| count.set(count.get() - 1));
+    }
+}
+
 /*
 impl TriedGrid {
     fn new() -> Self {
@@ -7,6 +7,25 @@ fn main() {
     // builder_vec::main();
     // builder_vec_large::main();
     // builder_vec_log::main();
+    // differential::main();
+    // engine::main();
+    // grid_constraint_solve::build_report::main();
     grid_constraint_solve::builder::main();
     // grid_constraint_solve::solver::main();
+    // grid_constraint_solve::backdoor::main();
+    // grid_constraint_solve::candidates::main();
+    // grid_constraint_solve::canonical::main();
+    // grid_constraint_solve::diagnostics::main();
+    // grid_constraint_solve::game::main();
+    // grid_constraint_solve::hint::main();
+    // grid_constraint_solve::pattern::main();
+    // grid_constraint_solve::play::main();
+    // grid_constraint_solve::property::main();
+    // grid_constraint_solve::sampler::main();
+    // grid_constraint_solve::session_file::main();
+    // grid_constraint_solve::transform::main();
+    // grid_constraint_solve::unavoidable::main();
+    // grid_constraint_solve::unique::main();
+    // grid_constraint_solve::validate::main();
+    // grid_constraint_solve::value_mask::main();
 }
@@ -0,0 +1,7 @@
+use sudoku::differential::DifferentialHarness;
+
+#[test]
+fn differential_cases_agree() {
+    let report = DifferentialHarness::new().case_count(50).run();
+    assert_eq!(0, report.failure_count, "{}\n{:#?}", report, report.failures);
+}
@@ -0,0 +1,32 @@
+// Playing a game session with graded hints, the way the h 4 and a commands in play.rs do.
+
+use sudoku::grid_constraint_solve::builder::Builder;
+use sudoku::grid_constraint_solve::game::GameSession;
+use sudoku::grid_constraint_solve::hint::{HintLevel, HintOutcome, StepAction};
+use sudoku::grid_constraint_solve::solver::Solver;
+
+// About half the puzzles need an elimination somewhere, so this many are all but sure to have
+// one between them.
+const PUZZLE_COUNT: usize = 30;
+
+#[test]
+fn level_4_hints_make_progress() {
+    // Each applied action, elimination or placement, must change what the next hint is about.
+    let mut elimination_count = 0;
+    for _ in 0..PUZZLE_COUNT {
+        let grid = Builder::with_size(9).build().unwrap();
+        let puzzle = Solver::new(&grid).reduce_minimal().unwrap().grid;
+        let mut session = GameSession::new(&puzzle).unwrap();
+        let mut last_action = None;
+        while let HintOutcome::Step(hint) = session.graded_hint(HintLevel::Action).unwrap() {
+            let action = hint.action.unwrap();
+            assert_ne!(last_action.as_ref(), Some(&action));
+            if let StepAction::Eliminate { .. } = action {
+                elimination_count += 1;
+            }
+            session.apply_step_action(&action).unwrap();
+            last_action = Some(action);
+        }
+    }
+    assert!(elimination_count > 0, "No eliminations came up, so the test didn't check them.");
+}
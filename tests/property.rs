@@ -0,0 +1,7 @@
+use sudoku::grid_constraint_solve::property::PropertyRunner;
+
+#[test]
+fn properties_hold() {
+    let report = PropertyRunner::new().case_count(50).run();
+    assert!(report.is_success(), "{}", report);
+}
@@ -0,0 +1,23 @@
+use sudoku::seeded_rng;
+use sudoku::grid_constraint_solve::sampler::{check_uniform_4x4, SamplingMethod};
+
+// Twenty samples for each of the 288 grids, so every grid should come up and the chi-square test
+// has enough in each count. The seed makes a failure repeatable.
+const SAMPLE_COUNT: usize = 5_760;
+const SEED: u64 = 50;
+
+fn assert_uniform(method: SamplingMethod) {
+    let _seeded_rng = seeded_rng(Some(SEED));
+    let check = check_uniform_4x4(method.clone(), SAMPLE_COUNT).unwrap();
+    assert!(check.passed, "{:?}: {}", method, check);
+}
+
+#[test]
+fn exact_counting_is_uniform_4x4() {
+    assert_uniform(SamplingMethod::ExactCounting { node_limit: 100_000_000 });
+}
+
+#[test]
+fn kempe_chain_is_uniform_4x4() {
+    assert_uniform(SamplingMethod::KempeChain { step_count: 150 });
+}
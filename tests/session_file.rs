@@ -0,0 +1,70 @@
+// Session files saved by format version 1. These fixtures must keep loading when the format
+// changes, so don't regenerate them with a newer version; add a fixture for the new version
+// instead.
+
+use std::time::Duration;
+
+use sudoku::grid_constraint_solve::game::{GameSession, HintReason};
+
+const SESSION_V1_STANDARD: &str = include_str!("fixtures/session_v1_standard.sudoku");
+const SESSION_V1_RELATED: &str = include_str!("fixtures/session_v1_related.sudoku");
+
+#[test]
+fn load_v1_standard_rules() {
+    let mut session = GameSession::from_session_string(SESSION_V1_STANDARD).unwrap();
+    assert_eq!(81, session.cell_count());
+    assert!(session.is_given(1));
+    assert!(!session.is_given(2));
+    assert_eq!(4, session.value(1));
+    assert_eq!(9, session.value(2));
+    assert_eq!(3, session.value(7));
+    assert_eq!(0, session.value(3));
+    assert_eq!(&[1, 2], session.pencil_marks(0));
+    assert!(session.is_paused());
+    assert_eq!(Duration::from_millis(81234), session.elapsed());
+    assert_eq!(1, session.hint_count);
+    let hint = session.last_hint.clone().unwrap();
+    assert_eq!((7, 3, HintReason::NakedSingle), (hint.index, hint.value, hint.reason));
+    assert!(session.conflicts().is_empty());
+    assert!(session.mistakes().is_empty());
+
+    // The redo line puts back the value that was undone before saving.
+    assert!(session.redo());
+    assert_eq!(1, session.value(3));
+    assert!(session.undo());
+    assert_eq!(0, session.value(3));
+    // Then the undo lines, newest first.
+    assert!(session.undo());
+    assert_eq!(0, session.value(7));
+    assert!(session.undo());
+    assert_eq!(0, session.value(2));
+    assert!(session.undo());
+    assert_eq!(&[1], session.pencil_marks(0));
+    assert!(session.undo());
+    assert!(session.pencil_marks(0).is_empty());
+    assert!(!session.undo());
+}
+
+#[test]
+fn load_v1_related_cells() {
+    let mut session = GameSession::from_session_string(SESSION_V1_RELATED).unwrap();
+    assert_eq!(16, session.cell_count());
+    assert_eq!(&[3, 1, 2, 4], &session.values()[..4]);
+    assert!(session.is_given(0));
+    assert!(!session.is_given(2));
+    assert!(session.is_paused());
+    assert_eq!(Duration::from_millis(4500), session.elapsed());
+    assert_eq!(1, session.hint_count);
+    let hint = session.last_hint.clone().unwrap();
+    assert_eq!((2, 2, HintReason::NakedSingle), (hint.index, hint.value, hint.reason));
+    assert!(session.redo());
+    assert_eq!(1, session.value(4));
+    assert!(session.undo());
+
+    // The related lines carry the diagonals rule. Cell 10 shares no row, column or block with
+    // cell 0 but is on the same diagonal, so the same value there is a conflict.
+    session.set_value(10, 3).unwrap();
+    assert!(session.conflicts().contains(&10));
+    assert!(session.undo());
+    assert!(session.conflicts().is_empty());
+}